@@ -0,0 +1,103 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ut3b2l::engine::*;
+
+// How long each benchmark is run for, after a warm-up of the same length.
+const MEASUREMENT_TIME: Duration = Duration::from_millis(500);
+
+// The games the sample positions are taken from, given as moves in `9 * zone + square` form.
+const SAMPLE_GAMES: [&[Move]; 2] = [
+    &[
+        40, 36, 0, 2, 20, 24, 58, 37, 15, 62, 80, 75, 28, 14, 48, 34, 65,
+    ],
+    &[0, 2, 20, 24, 58, 40, 44, 76, 41, 46, 10, 12, 30, 32, 52, 70],
+];
+
+// Every position reached in the sample games, with the side to move in each.
+fn sample_positions() -> Vec<(Board, bool)> {
+    let mut positions = Vec::new();
+    for game in SAMPLE_GAMES {
+        let mut board: Board = (0, 0, ZONE_ANY << 54);
+        let mut side = false;
+        positions.push((board, side));
+        for &mv in game {
+            assert!(check_move(board, mv).is_ok(), "illegal sample move {}", mv);
+            board = play_move(board, mv, side);
+            side = !side;
+            positions.push((board, side));
+        }
+    }
+    positions
+}
+
+/**
+ * Runs `f` repeatedly for `MEASUREMENT_TIME` and reports the average time per call.
+ * The calls are made in batches which double in size, so that reading the clock
+ * takes up a negligible part of the measurement even for very fast functions.
+ */
+fn bench<R>(name: &str, mut f: impl FnMut() -> R) {
+    let mut measure = || {
+        let start = Instant::now();
+        let mut calls: u64 = 0;
+        let mut batch = 1;
+        while start.elapsed() < MEASUREMENT_TIME {
+            for _ in 0..batch {
+                black_box(f());
+            }
+            calls += batch;
+            batch *= 2;
+        }
+        (calls, start.elapsed())
+    };
+    measure();
+    let (calls, elapsed) = measure();
+    println!(
+        "{:<24} {:>12.1} ns/iter ({} iterations)",
+        name,
+        elapsed.as_nanos() as f64 / calls as f64,
+        calls
+    );
+}
+
+fn main() {
+    let positions = sample_positions();
+
+    // Each of these visits every sample position once per iteration.
+    bench("generate_moves", || {
+        positions
+            .iter()
+            .map(|&(board, _)| generate_moves(black_box(board)).count())
+            .sum::<usize>()
+    });
+    bench("play_move", || {
+        positions
+            .iter()
+            .filter_map(|&(board, side)| {
+                generate_moves(board)
+                    .next()
+                    .map(|mv| play_move(black_box(board), mv, side))
+            })
+            .count()
+    });
+    bench("evaluate", || {
+        positions
+            .iter()
+            .map(|&(board, side)| evaluate(black_box(board), side))
+            .sum::<i32>()
+    });
+
+    // A fixed-depth search from the empty board.
+    bench("alpha_beta depth 5", || {
+        alpha_beta(
+            black_box((0, 0, ZONE_ANY << 54)),
+            false,
+            5,
+            OUTCOME_LOSS,
+            OUTCOME_WIN,
+            5,
+            &mut SearchControl::new(None),
+        )
+        .0
+    });
+}
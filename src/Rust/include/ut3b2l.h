@@ -0,0 +1,62 @@
+/*
+ * The C API of the ut3b2l engine, exported from the shared library built with
+ * `cargo build --release --lib --features ffi`.
+ * This header is written by hand, and must be kept in sync with `src/ffi.rs`.
+ *
+ * All strings are NUL-terminated UTF-8. Strings returned by the engine belong to the caller,
+ * and are freed with `ut3_string_free`. Each engine handle holds its own game and transposition table,
+ * so several handles can be used at once, but one handle must not be used by two threads at the same time.
+ */
+#ifndef UT3B2L_H
+#define UT3B2L_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define UT3_OK 0
+#define UT3_INVALID (-1)
+
+typedef struct Engine Ut3Engine;
+
+/* Called after each completed iteration of a search with an `info` line, as output by `go`. */
+typedef void (*Ut3InfoCallback)(const char *line, void *user_data);
+
+/* Creates an engine at the start of a new game, with a transposition table of `hash_mb` megabytes
+ * (the default size if 0). Returns NULL if the size is too large. */
+Ut3Engine *ut3_engine_new(size_t hash_mb);
+
+/* Frees an engine. Does nothing if `engine` is NULL. */
+void ut3_engine_free(Ut3Engine *engine);
+
+/* Starts a new game from a position string, as taken by `newgame`.
+ * Returns UT3_INVALID, leaving the game unchanged, if the position is not valid. */
+int ut3_engine_set_position(Ut3Engine *engine, const char *position);
+
+/* Returns the position string of the current position. */
+char *ut3_engine_position(const Ut3Engine *engine);
+
+/* Plays a move in either notation for the side to move.
+ * Returns UT3_INVALID, leaving the game unchanged, if the move is illegal. */
+int ut3_engine_play(Ut3Engine *engine, const char *move);
+
+/* Returns the legal moves in the `zone/square` notation, separated by spaces (empty once the game is over). */
+char *ut3_engine_legal_moves(const Ut3Engine *engine);
+
+/* Searches the current position to `depth` plies, or for `movetime_ms` milliseconds if `depth` is 0,
+ * without changing the position. `callback` may be NULL, and is otherwise given `user_data` with each line.
+ * The best move is written to `*best_move`, or NULL if the game is over.
+ * Returns UT3_INVALID if neither limit is given or the depth is too large. */
+int ut3_engine_search(Ut3Engine *engine, unsigned int depth, unsigned int movetime_ms,
+                      Ut3InfoCallback callback, void *user_data, char **best_move);
+
+/* Frees a string returned by the engine. Does nothing if `string` is NULL. */
+void ut3_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
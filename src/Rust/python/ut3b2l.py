@@ -0,0 +1,127 @@
+"""
+Python bindings for the ut3b2l engine, for driving self-play and data collection from Python.
+
+The engine is loaded as a shared library through `ctypes`, so no compiler is needed on the Python side.
+Build the library with `cargo build --release --lib --features python`, which produces
+`target/release/libut3b2l.so` (`libut3b2l.dylib` on macOS, `ut3b2l.dll` on Windows).
+The library is found there relative to this file, or at the path in the `UT3B2L_LIBRARY` environment variable.
+
+Positions are immutable `Board` values, identified by their position strings (the format of `newgame`),
+and moves are strings in the `zone/square` notation, such as `ne/c`.
+
+    from ut3b2l import Board
+    board = Board()
+    while board.legal_moves():
+        result = board.search(depth=6)
+        board = board.play(result.move)
+"""
+
+import ctypes
+import json
+import os
+import sys
+from typing import List, NamedTuple, Optional
+
+# Moves are given and returned in the `zone/square` notation. The `r<row>c<col>` notation is also accepted.
+Move = str
+
+# The position string of the empty board with Player X to move.
+START_POSITION = "9/9/9/9/9/9/9/9/9 any x"
+
+
+def _default_library_path() -> str:
+    name = {"darwin": "libut3b2l.dylib", "win32": "ut3b2l.dll"}.get(sys.platform, "libut3b2l.so")
+    return os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "target", "release", name)
+
+
+def _load_library() -> ctypes.CDLL:
+    library = ctypes.CDLL(os.environ.get("UT3B2L_LIBRARY", _default_library_path()))
+    string_in = [ctypes.c_char_p, ctypes.c_size_t]
+    for name, argtypes, restype in [
+        ("new_game", string_in, ctypes.c_bool),
+        ("play", string_in, ctypes.c_bool),
+        ("legal_moves", [], ctypes.c_size_t),
+        ("board", [], ctypes.c_size_t),
+        ("search", [ctypes.c_size_t, ctypes.c_uint32], ctypes.c_size_t),
+        ("output_ptr", [], ctypes.c_void_p),
+        ("output_len", [], ctypes.c_size_t),
+    ]:
+        function = getattr(library, name)
+        function.argtypes = argtypes
+        function.restype = restype
+    return library
+
+
+_library = _load_library()
+
+
+def _call_with_string(function, string: str) -> bool:
+    data = string.encode()
+    return function(data, len(data))
+
+
+def _output() -> str:
+    return ctypes.string_at(_library.output_ptr(), _library.output_len()).decode()
+
+
+class SearchResult(NamedTuple):
+    # The best move found, or None if the game is over.
+    move: Optional[Move]
+    # The depth of the deepest completed iteration.
+    depth: int
+    # The evaluation for the side to move, written as by `go`: `+16`, `D0`, `W3` or `L4`.
+    eval: str
+    # The principal variation, beginning with `move`.
+    pv: List[Move]
+
+
+class Board:
+    """A position, together with the side to move."""
+
+    def __init__(self, position: str = START_POSITION):
+        # The engine holds a single game, so each operation first sets the game to this position.
+        if not _call_with_string(_library.new_game, position):
+            raise ValueError("invalid position: " + position)
+        _library.board()
+        self.position = _output()
+
+    def __repr__(self) -> str:
+        return "Board({!r})".format(self.position)
+
+    def __eq__(self, other) -> bool:
+        return isinstance(other, Board) and self.position == other.position
+
+    def __hash__(self) -> int:
+        return hash(self.position)
+
+    def _select(self) -> None:
+        _call_with_string(_library.new_game, self.position)
+
+    def legal_moves(self) -> List[Move]:
+        """The legal moves of the position, which is empty once the game is over."""
+        self._select()
+        _library.legal_moves()
+        moves = _output()
+        return moves.split(" ") if moves else []
+
+    def play(self, move: Move) -> "Board":
+        """Returns the position after the side to move plays the move, raising ValueError if it is illegal."""
+        self._select()
+        if not _call_with_string(_library.play, move):
+            raise ValueError("illegal move: " + move)
+        _library.board()
+        return Board(_output())
+
+    def search(self, depth: Optional[int] = None, movetime: Optional[int] = None) -> SearchResult:
+        """
+        Searches the position, either to the given depth or for the given number of milliseconds.
+        The transposition table is emptied before each search, so results do not depend on earlier searches.
+        """
+        if (depth is None) == (movetime is None):
+            raise ValueError("exactly one of depth and movetime must be given")
+        if depth is not None and depth <= 0:
+            raise ValueError("depth must be positive")
+        self._select()
+        _library.search(depth or 0, movetime or 0)
+        result = json.loads(_output())
+        return SearchResult(result["move"], result["depth"], result["eval"], result["pv"])
@@ -0,0 +1,73 @@
+// The thresholds used to end hopeless games early, changed with the `setoption` command.
+// A threshold of 0 disables that kind of adjudication.
+#[derive(Clone, Copy, Default)]
+pub struct AdjudicationSettings {
+    // A side resigns once its evaluation has been at or below `-resign_threshold`
+    // for `resign_moves` of its searches in a row.
+    pub resign_threshold: i32,
+    pub resign_moves: usize,
+    // A side is adjudicated the winner once its evaluation has been at or above `win_threshold`
+    // for `win_moves` of its searches in a row.
+    pub win_threshold: i32,
+    pub win_moves: usize,
+}
+
+// The decision reached by the adjudicator, naming the side it applies to
+// (`false` for Player X, `true` for Player O).
+#[derive(Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Resign(bool),
+    Win(bool),
+}
+
+/**
+ * Tracks the evaluations of consecutive searches for each side over the course of a game,
+ * so that automated play can stop once the result is no longer in doubt.
+ * Each side's streaks are counted separately, since in a match each engine only sees its own searches.
+ */
+pub struct Adjudicator {
+    pub settings: AdjudicationSettings,
+    resign_streaks: [usize; 2],
+    win_streaks: [usize; 2],
+}
+
+impl Adjudicator {
+    pub fn new(settings: AdjudicationSettings) -> Self {
+        Self {
+            settings,
+            resign_streaks: [0; 2],
+            win_streaks: [0; 2],
+        }
+    }
+
+    // Forgets all streaks, such as when a new game is started.
+    pub fn reset(&mut self) {
+        self.resign_streaks = [0; 2];
+        self.win_streaks = [0; 2];
+    }
+
+    // Records the evaluation of a search made by `side`, from that side's perspective,
+    // returning a verdict if the game should now be stopped.
+    pub fn record(&mut self, side: bool, eval: i32) -> Option<Verdict> {
+        let s = self.settings;
+        let i = side as usize;
+
+        let resigning = s.resign_threshold > 0 && eval <= -s.resign_threshold;
+        self.resign_streaks[i] = if resigning {
+            self.resign_streaks[i] + 1
+        } else {
+            0
+        };
+
+        let winning = s.win_threshold > 0 && eval >= s.win_threshold;
+        self.win_streaks[i] = if winning { self.win_streaks[i] + 1 } else { 0 };
+
+        if resigning && self.resign_streaks[i] >= s.resign_moves.max(1) {
+            Some(Verdict::Resign(side))
+        } else if winning && self.win_streaks[i] >= s.win_moves.max(1) {
+            Some(Verdict::Win(side))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::engine::*;
+use crate::{eval_string, move_string, player_string, Notation, SearchRecord};
+
+// The default depth each position is searched to.
+pub const ANNOTATE_DEPTH: usize = 6;
+
+// The default drops in evaluation, from the best move to the move played, that make a move an inaccuracy,
+// a mistake and a blunder.
+pub const DEFAULT_THRESHOLDS: [i32; 3] = [30, 100, 300];
+
+// The names of the marks given by each threshold, and the symbols they are written with in the annotated record.
+const MARKS: [(&str, &str); 3] = [("inaccuracy", "?!"), ("mistake", "?"), ("blunder", "??")];
+
+/**
+ * Searches every position of the game history in which a move was made, and compares the move made
+ * against the best move, writing a line for each move, then the game with each marked move followed by its symbol,
+ * then the number of moves given each mark for each side.
+ * Every move of a position is searched with a full window, so that the evaluation of the move made is exact.
+ */
+pub fn run_annotate(
+    history: &[(Board, Move, bool, Option<SearchRecord>)],
+    depth: usize,
+    thresholds: [i32; 3],
+    notation: Notation,
+) {
+    let mut record = Vec::new();
+    let mut counts = [[0; 3]; 2];
+    for (ply, pair) in history.windows(2).enumerate() {
+        let (board, _, side, _) = pair[0];
+        let mv = pair[1].1;
+        if mv == NULL_MOVE {
+            record.push("null".to_string());
+            continue;
+        }
+        let scores = root_move_scores(board, side, depth, None, &mut SearchControl::new(None));
+        // Only an interrupted search leaves a position with moves unscored, which ends the annotation early.
+        let Some(&(best_move, best_eval, _)) = scores.first() else {
+            break;
+        };
+        let played_eval = scores
+            .iter()
+            .find(|&&(scored, _, _)| scored == mv)
+            .map_or(best_eval, |&(_, eval, _)| eval);
+        // The highest threshold that the drop reaches gives the mark, if any.
+        let mark = thresholds
+            .iter()
+            .rposition(|&threshold| best_eval - played_eval >= threshold);
+        println!(
+            "annotate ply {} {} move {} eval {} best {} {}{}",
+            ply + 1,
+            player_string(side),
+            move_string(mv, notation),
+            eval_string(played_eval, depth),
+            move_string(best_move, notation),
+            eval_string(best_eval, depth),
+            mark.map_or(String::new(), |mark| format!(" {}", MARKS[mark].0))
+        );
+        if let Some(mark) = mark {
+            counts[side as usize][mark] += 1;
+        }
+        record.push(format!(
+            "{}{}",
+            move_string(mv, notation),
+            mark.map_or("", |mark| MARKS[mark].1)
+        ));
+    }
+    if record.is_empty() {
+        println!("annotate none");
+        return;
+    }
+    println!("annotate record {}", record.join(" "));
+    for side in [false, true] {
+        let counts = counts[side as usize];
+        println!(
+            "annotate summary {} inaccuracies {} mistakes {} blunders {}",
+            player_string(side),
+            counts[0],
+            counts[1],
+            counts[2]
+        );
+    }
+}
@@ -0,0 +1,671 @@
+use std::fs::{read_to_string, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::adjudicate::{AdjudicationSettings, Adjudicator, Verdict};
+use crate::engine::*;
+use ut3b2l::notation::*;
+use ut3b2l::rules::{self, FirstMove, Variant, WonZones};
+
+// How long an engine is given to start up and to answer commands other than `go`.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How far past its remaining time an engine may answer before the game is declared lost on time,
+// which covers the time taken to pass the move between processes.
+const TIME_GRACE_MS: u64 = 50;
+
+// The share of the remaining time an engine is given for each move, beyond its increment.
+pub const MOVES_TO_GO: u64 = 20;
+
+// An engine to be played, given by the path of its executable and the options to set before each game.
+#[derive(Clone)]
+pub struct EngineSpec {
+    pub name: String,
+    pub path: String,
+    pub options: Vec<(String, String)>,
+}
+
+// The time each engine has for a game, and the time added after each of its moves, in milliseconds.
+#[derive(Clone, Copy)]
+pub struct TimeControl {
+    pub base_ms: u64,
+    pub increment_ms: u64,
+}
+
+impl TimeControl {
+    // Reads a time control written as `<base>` or `<base>+<increment>`, both in milliseconds.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+        let base_ms = base.parse().ok().filter(|&base| base > 0)?;
+        Some(Self {
+            base_ms,
+            increment_ms: increment.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GameResult {
+    XWins,
+    OWins,
+    Draw,
+}
+
+impl GameResult {
+    // The result as written in game records, from the perspective of Player X.
+    pub fn score_string(self) -> &'static str {
+        match self {
+            GameResult::XWins => "1-0",
+            GameResult::OWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+
+    // Reads a result as written in game records.
+    pub fn from_score_string(text: &str) -> Option<Self> {
+        match text {
+            "1-0" => Some(GameResult::XWins),
+            "0-1" => Some(GameResult::OWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            _ => None,
+        }
+    }
+
+    // The points scored by the given side, out of 2 so that scores stay whole.
+    pub fn points(self, side: bool) -> u32 {
+        match (self, side) {
+            (GameResult::Draw, _) => 1,
+            (GameResult::XWins, false) | (GameResult::OWins, true) => 2,
+            _ => 0,
+        }
+    }
+
+    // The result where the given side wins.
+    pub fn win_for(side: bool) -> Self {
+        if side {
+            GameResult::OWins
+        } else {
+            GameResult::XWins
+        }
+    }
+}
+
+/**
+ * A finished game, with the evaluation and depth each engine reported for its moves.
+ * It is written as a set of tags followed by the moves, much like PGN:
+ * ```
+ * [X "new"]
+ * [O "old"]
+ * [Position "9/9/9/9/9/9/9/9/9 any x"]
+ * [Result "1-0"]
+ * [Termination "finished"]
+ *
+ * c/c {+16/8} c/nw {D0/9} nw/c {+25/10} ...
+ * ```
+ */
+pub struct GameRecord {
+    pub x_name: String,
+    pub o_name: String,
+    pub position: String,
+    pub moves: Vec<(Move, Option<(i32, usize)>)>,
+    pub result: GameResult,
+    pub termination: String,
+}
+
+impl GameRecord {
+    pub fn to_text(&self) -> String {
+        let moves: Vec<_> = self
+            .moves
+            .iter()
+            .map(|&(mv, search)| match search {
+                Some((eval, depth)) => format!(
+                    "{} {{{}/{}}}",
+                    move_string(mv, Notation::Zone),
+                    eval_string(eval, depth),
+                    depth
+                ),
+                None => move_string(mv, Notation::Zone),
+            })
+            .collect();
+        format!(
+            "[X \"{}\"]\n[O \"{}\"]\n[Position \"{}\"]\n[Result \"{}\"]\n[Termination \"{}\"]\n\n{}\n\n",
+            self.x_name,
+            self.o_name,
+            self.position,
+            self.result.score_string(),
+            self.termination,
+            moves.join(" ")
+        )
+    }
+
+    // Reads a single record as written by `to_text`, or `None` if it is malformed.
+    pub fn from_text(text: &str) -> Option<Self> {
+        let mut tags = Vec::new();
+        let mut moves: Vec<(Move, Option<(i32, usize)>)> = Vec::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if let Some(tag) = line.strip_prefix('[').and_then(|tag| tag.strip_suffix(']')) {
+                let (name, value) = tag.split_once(' ')?;
+                tags.push((name, value.trim_matches('"')));
+                continue;
+            }
+            for word in line.split_whitespace() {
+                // An evaluation and depth in braces belong to the move before them.
+                if let Some(search) = word
+                    .strip_prefix('{')
+                    .and_then(|word| word.strip_suffix('}'))
+                {
+                    let (eval, depth) = search.split_once('/')?;
+                    moves.last_mut()?.1 = Some((eval_from_string(eval)?, depth.parse().ok()?));
+                } else {
+                    moves.push((move_from_string(word)?, None));
+                }
+            }
+        }
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|&&(tag, _)| tag == name)
+                .map(|&(_, value)| value.to_string())
+        };
+        Some(Self {
+            x_name: tag("X")?,
+            o_name: tag("O")?,
+            position: tag("Position")?,
+            moves,
+            result: GameResult::from_score_string(&tag("Result")?)?,
+            termination: tag("Termination")?,
+        })
+    }
+}
+
+// A running engine process, whose output lines are read on a separate thread.
+pub struct EngineProcess {
+    pub spec: EngineSpec,
+    child: Child,
+    input: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl EngineProcess {
+    // Starts the engine and waits for it to be ready.
+    pub fn start(spec: &EngineSpec) -> Result<Self, String> {
+        let mut child = Command::new(&spec.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| format!("cannot start {}: {}", spec.path, error))?;
+        let input = child.stdin.take().unwrap();
+        let output = child.stdout.take().unwrap();
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(output).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = Self {
+            spec: spec.clone(),
+            child,
+            input,
+            lines,
+        };
+        engine.expect("ready", RESPONSE_TIMEOUT)?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.input, "{}", command).map_err(|_| format!("{} has exited", self.spec.name))
+    }
+
+    // Waits for a line starting with the given keyword, skipping any others, until the timeout passes.
+    fn expect(&mut self, keyword: &str, timeout: Duration) -> Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) if line.split_whitespace().next() == Some(keyword) => return Ok(line),
+                Ok(_) => continue,
+                Err(_) => return Err(format!("{} did not answer", self.spec.name)),
+            }
+        }
+    }
+
+    // Sets up a new game from the given position, after applying the engine's options.
+    // Search information is always requested in the text format, which is the one read here.
+    fn new_game(&mut self, position: &str) -> Result<(), String> {
+        let options = self.spec.options.clone();
+        for (name, value) in options.iter().map(|(n, v)| (n.as_str(), v.as_str())) {
+            self.send(&format!("setoption {} {}", name, value))?;
+            let answer = self.expect("setoption", RESPONSE_TIMEOUT)?;
+            if answer != "setoption ok" {
+                return Err(format!(
+                    "{} rejected option {} {}",
+                    self.spec.name, name, value
+                ));
+            }
+        }
+        self.send("setoption InfoFormat text")?;
+        self.expect("setoption", RESPONSE_TIMEOUT)?;
+        self.send(&format!("newgame {}", position))?;
+        match self.expect("newgame", RESPONSE_TIMEOUT)? {
+            answer if answer == "newgame ok" => Ok(()),
+            _ => Err(format!("{} rejected position {}", self.spec.name, position)),
+        }
+    }
+
+    /**
+     * Searches for the given time, returning the chosen move with its evaluation and depth
+     * once the engine reports the result of its search, or `None` if it does not within `timeout`.
+     */
+    fn search(&mut self, movetime: u64, timeout: Duration) -> Option<(Move, i32, usize)> {
+        self.send(&format!("go movetime {}", movetime)).ok()?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = self.lines.recv_timeout(remaining).ok()?;
+            let words: Vec<_> = line.split_whitespace().collect();
+            if words.first() != Some(&"info") {
+                continue;
+            }
+            let value = |key: &str| {
+                let i = words.iter().position(|&word| word == key)?;
+                words.get(i + 1).copied()
+            };
+            if let (Some(mv), Some(eval), Some(depth)) =
+                (value("pv"), value("eval"), value("depth"))
+            {
+                return Some((
+                    move_from_string(mv)?,
+                    eval_from_string(eval)?,
+                    depth.parse().ok()?,
+                ));
+            }
+        }
+    }
+
+    // Passes the opponent's move to the engine.
+    fn play(&mut self, mv: Move) -> Result<(), String> {
+        self.send(&format!("play {}", move_string(mv, Notation::Zone)))?;
+        match self.expect("move", RESPONSE_TIMEOUT)? {
+            answer if answer.starts_with("move pos") => Ok(()),
+            _ => Err(format!("{} rejected move", self.spec.name)),
+        }
+    }
+
+    // Whether the process is still running, so that a crashed engine can be restarted for the next game.
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+// An engine is asked to quit, and killed if it has not within a second.
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        self.send("q").ok();
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < deadline {
+            if !self.is_running() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+// The result of a position where the game is over, or `None` if it is still being played.
+pub fn game_result(board: Board) -> Option<GameResult> {
+    if let Some(side) = winner(board) {
+        Some(GameResult::win_for(side))
+    } else if generate_moves(board).next().is_none() {
+        Some(full_grid_winner(board).map_or(GameResult::Draw, GameResult::win_for))
+    } else {
+        None
+    }
+}
+
+/**
+ * Plays a single game between two running engines from the given position.
+ * Each engine's clock runs only while it is thinking, and it is given a share of its remaining time for each move.
+ * The game is lost by an engine that runs out of time, plays an illegal move or stops answering,
+ * and may end early by the adjudication settings, which look at the evaluations each engine reports.
+ * With `show_clock`, each move is written as it is played, with the time it took and the time both engines have left.
+ */
+pub fn play_game(
+    engines: [&mut EngineProcess; 2],
+    position: &str,
+    time_control: TimeControl,
+    adjudication: AdjudicationSettings,
+    show_clock: bool,
+) -> Result<GameRecord, String> {
+    let (mut board, mut side) =
+        board_from_string(position).ok_or_else(|| format!("invalid opening {}", position))?;
+    let [x, o] = engines;
+    x.new_game(position)?;
+    o.new_game(position)?;
+    let engines = [x, o];
+
+    let mut record = GameRecord {
+        x_name: engines[0].spec.name.clone(),
+        o_name: engines[1].spec.name.clone(),
+        position: position.to_string(),
+        moves: Vec::new(),
+        result: GameResult::Draw,
+        termination: "finished".to_string(),
+    };
+    let mut clocks = [time_control.base_ms; 2];
+    let mut adjudicator = Adjudicator::new(adjudication);
+
+    loop {
+        if let Some(result) = game_result(board) {
+            record.result = result;
+            return Ok(record);
+        }
+        let i = side as usize;
+        let movetime = clocks[i] / MOVES_TO_GO + time_control.increment_ms;
+        let start = Instant::now();
+        let searched = engines[i].search(
+            movetime.min(clocks[i]).max(1),
+            Duration::from_millis(clocks[i] + TIME_GRACE_MS),
+        );
+        let elapsed = start.elapsed().as_millis() as u64;
+
+        let Some((mv, eval, depth)) = searched else {
+            // A search still running is stopped, so that its result is not read in a later game.
+            engines[i].send("stop").ok();
+            record.result = GameResult::win_for(!side);
+            record.termination = if engines[i].is_running() {
+                format!("time forfeit {}", player_string(side))
+            } else {
+                format!("crash {}", player_string(side))
+            };
+            return Ok(record);
+        };
+        if elapsed > clocks[i] + TIME_GRACE_MS {
+            record.result = GameResult::win_for(!side);
+            record.termination = format!("time forfeit {}", player_string(side));
+            return Ok(record);
+        }
+        if check_move(board, mv).is_err() {
+            record.result = GameResult::win_for(!side);
+            record.termination = format!(
+                "illegal move {} {}",
+                player_string(side),
+                move_string(mv, Notation::Zone)
+            );
+            return Ok(record);
+        }
+        clocks[i] = clocks[i].saturating_sub(elapsed) + time_control.increment_ms;
+        board = play_move(board, mv, side);
+        record.moves.push((mv, Some((eval, depth))));
+        if show_clock {
+            println!(
+                "clock ply {} {} move {} used {} x {} o {}",
+                record.moves.len(),
+                player_string(side),
+                move_string(mv, Notation::Zone),
+                elapsed,
+                clocks[0],
+                clocks[1]
+            );
+        }
+        if game_result(board).is_none() {
+            engines[1 - i].play(mv)?;
+        }
+
+        match adjudicator.record(side, eval) {
+            Some(Verdict::Resign(player)) => {
+                record.result = GameResult::win_for(!player);
+                record.termination = format!("adjudicated resign {}", player_string(player));
+                return Ok(record);
+            }
+            Some(Verdict::Win(player)) => {
+                record.result = GameResult::win_for(player);
+                record.termination = format!("adjudicated win {}", player_string(player));
+                return Ok(record);
+            }
+            None => {}
+        }
+        side = !side;
+    }
+}
+
+// Reads the opening positions from a file with a position string on each line,
+// ignoring blank lines, anything after a `#`, such as the moves written by `ut3b2l openings`.
+pub fn read_openings(path: &str) -> Result<Vec<String>, String> {
+    let text = read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+    let openings: Vec<_> = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    for opening in &openings {
+        match board_from_string(opening) {
+            Some((board, side)) if validate_position(board, side).is_ok() => {}
+            _ => return Err(format!("invalid opening {}", opening)),
+        }
+    }
+    if openings.is_empty() {
+        return Err(format!("no openings in {}", path));
+    }
+    Ok(openings)
+}
+
+// The settings shared by the commands that play engines against each other.
+pub struct ArenaSettings {
+    pub engines: Vec<EngineSpec>,
+    pub openings: Vec<String>,
+    pub time_control: TimeControl,
+    pub adjudication: AdjudicationSettings,
+    pub output: Option<String>,
+    // Whether every move is written with the clocks of both engines, which `--clock` turns on.
+    pub show_clock: bool,
+    // Any arguments not recognised here, for the command to read itself.
+    pub rest: Vec<String>,
+}
+
+/**
+ * Reads the arguments shared by the commands that play engines against each other:
+ * `--engine <path>` adds an engine, with `--name <name>` and any number of `--option <name>=<value>`
+ * after it applying to that engine, `--tc <base>[+<increment>]` sets the time control in milliseconds,
+ * `--openings <file>` gives the starting positions, `--out <file>` the file game records are appended to,
+ * `--resign <eval> <moves>` and `--win <eval> <moves>` the adjudication thresholds,
+ * and `--clock` writes every move with the clocks of both engines.
+ */
+pub fn parse_settings(args: &[String]) -> Result<ArenaSettings, String> {
+    let mut settings = ArenaSettings {
+        engines: Vec::new(),
+        openings: vec!["9/9/9/9/9/9/9/9/9 any x".to_string()],
+        time_control: TimeControl {
+            base_ms: 10000,
+            increment_ms: 100,
+        },
+        adjudication: AdjudicationSettings::default(),
+        output: None,
+        show_clock: false,
+        rest: Vec::new(),
+    };
+    let mut args = args.iter();
+    let missing = |flag: &str| format!("{} needs a value", flag);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--engine" => {
+                let path = args.next().ok_or_else(|| missing(arg))?;
+                let name = path.rsplit('/').next().unwrap_or(path).to_string();
+                settings.engines.push(EngineSpec {
+                    name,
+                    path: path.clone(),
+                    options: Vec::new(),
+                });
+            }
+            "--name" | "--option" => {
+                let value = args.next().ok_or_else(|| missing(arg))?;
+                let engine = settings
+                    .engines
+                    .last_mut()
+                    .ok_or_else(|| format!("{} must follow --engine", arg))?;
+                if arg == "--name" {
+                    engine.name = value.clone();
+                } else {
+                    let (name, value) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("invalid option {}", value))?;
+                    engine.options.push((name.to_string(), value.to_string()));
+                }
+            }
+            "--tc" => {
+                let value = args.next().ok_or_else(|| missing(arg))?;
+                settings.time_control = TimeControl::parse(value)
+                    .ok_or_else(|| format!("invalid time control {}", value))?;
+            }
+            "--openings" => {
+                settings.openings = read_openings(args.next().ok_or_else(|| missing(arg))?)?;
+            }
+            "--out" => settings.output = Some(args.next().ok_or_else(|| missing(arg))?.clone()),
+            "--clock" => settings.show_clock = true,
+            "--resign" | "--win" => {
+                let eval = args.next().and_then(|eval| eval.parse().ok());
+                let moves = args.next().and_then(|moves| moves.parse().ok());
+                let (Some(eval), Some(moves)) = (eval, moves) else {
+                    return Err(format!("{} needs an evaluation and a number of moves", arg));
+                };
+                if arg == "--resign" {
+                    settings.adjudication.resign_threshold = eval;
+                    settings.adjudication.resign_moves = moves;
+                } else {
+                    settings.adjudication.win_threshold = eval;
+                    settings.adjudication.win_moves = moves;
+                }
+            }
+            _ => settings.rest.push(arg.clone()),
+        }
+    }
+    apply_rules(&settings.engines)?;
+    Ok(settings)
+}
+
+/**
+ * Applies the rules set by `--option Variant=...`, `WonZones=...` and `FirstMove=...` to this process,
+ * so that games are refereed under the rules the engines play by.
+ * The engines must agree on each rule, since a game can only be played under one set of rules.
+ */
+fn apply_rules(engines: &[EngineSpec]) -> Result<(), String> {
+    for rule in ["Variant", "WonZones", "FirstMove"] {
+        let values: Vec<_> = engines
+            .iter()
+            .map(|engine| {
+                engine
+                    .options
+                    .iter()
+                    .rev()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(rule))
+                    .map(|(_, value)| value.to_lowercase())
+            })
+            .collect();
+        if values.windows(2).any(|pair| pair[0] != pair[1]) {
+            return Err(format!("every engine needs the same {}", rule));
+        }
+        let Some(Some(value)) = values.first() else {
+            continue;
+        };
+        let applied = match rule {
+            "Variant" => Variant::parse(value).map(rules::set_variant),
+            "WonZones" => WonZones::parse(value).map(rules::set_won_zones),
+            _ => FirstMove::parse(value).map(rules::set_first_move),
+        };
+        applied.ok_or_else(|| format!("invalid {} {}", rule, value))?;
+    }
+    Ok(())
+}
+
+// Appends a game record to the output file, if one was given.
+pub fn write_record(output: &Option<String>, record: &GameRecord) -> Result<(), String> {
+    let Some(path) = output else {
+        return Ok(());
+    };
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(record.to_text().as_bytes()))
+        .map_err(|error| format!("cannot write {}: {}", path, error))
+}
+
+// Restarts an engine that has exited, so that a crash only loses the game it happened in.
+pub fn ensure_running(engine: &mut EngineProcess) -> Result<(), String> {
+    if !engine.is_running() {
+        *engine = EngineProcess::start(&engine.spec)?;
+    }
+    Ok(())
+}
+
+/**
+ * Plays a match between two engines, as `match --engine <path> ... --engine <path> ... [--games <count>]`.
+ * Each opening is played twice, with the engines swapping sides, so that neither is favoured by the openings.
+ * The result of each game is written as it finishes, followed by the score of the first engine.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let settings = parse_settings(args)?;
+    if settings.engines.len() != 2 {
+        return Err("a match needs exactly two engines".to_string());
+    }
+    let mut games = 2;
+    let mut rest = settings.rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--games" => {
+                games = rest
+                    .next()
+                    .and_then(|games| games.parse().ok())
+                    .filter(|&games| games > 0)
+                    .ok_or("--games needs a positive count")?;
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    let mut first = EngineProcess::start(&settings.engines[0])?;
+    let mut second = EngineProcess::start(&settings.engines[1])?;
+    // The wins, draws and losses of the first engine.
+    let mut score = [0; 3];
+    for game in 0..games {
+        ensure_running(&mut first)?;
+        ensure_running(&mut second)?;
+        let opening = &settings.openings[(game / 2) % settings.openings.len()];
+        let first_side = game % 2 == 1;
+        let engines = if first_side {
+            [&mut second, &mut first]
+        } else {
+            [&mut first, &mut second]
+        };
+        let record = play_game(
+            engines,
+            opening,
+            settings.time_control,
+            settings.adjudication,
+            settings.show_clock,
+        )?;
+        write_record(&settings.output, &record)?;
+        score[2 - record.result.points(first_side) as usize] += 1;
+        println!(
+            "game {} {} vs {} {} {}",
+            game + 1,
+            record.x_name,
+            record.o_name,
+            record.result.score_string(),
+            record.termination
+        );
+    }
+    println!(
+        "score {} {}-{}-{} {}",
+        settings.engines[0].name, score[0], score[1], score[2], settings.engines[1].name
+    );
+    Ok(())
+}
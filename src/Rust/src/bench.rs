@@ -0,0 +1,104 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::engine::*;
+use crate::interrupt::interrupted;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB};
+use crate::{board_from_string, move_string, Notation};
+
+// The depth each position is searched to when no depth is given.
+pub const BENCH_DEPTH: usize = 8;
+
+// The offset basis and prime of the 64-bit FNV-1a hash, which combines the moves chosen into one signature.
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// A fixed set of positions from the opening, middlegame and endgame of several games,
+// in the same format as the `newgame` command.
+const BENCH_POSITIONS: [&str; 10] = [
+    "9/9/9/9/9/9/9/9/9 any x",
+    "9/1x7/6o2/5x3/4o4/9/9/9/9 sw x",
+    "o8/1x7/9/9/4o4/3x5/x8/9/o8 nw x",
+    "7o1/9/5x3/9/4x4/3o1o3/8x/1x4o2/9 w x",
+    "2o3x2/1x7/2o3o2/1o3x3/1x2o4/4o4/x8/o2x5/6x2 n x",
+    "o1x1x4/1x2o4/8o/1o7/x2oox3/3x3o1/x8/4x2x1/o8 c o",
+    "x6o1/x1o6/2oo1x3/x5ox1/4x4/1o1o1o3/o7x/1xx3o2/3x5 se x",
+    "2oo1oxx1/1xx6/2o2xo2/1o3x1x1/1x2ox2o/4o1o2/x6o1/o2x3o1/2x3x2 n x",
+    "o1x1xx2o/ox2o3x/7xo/1o2o4/x2oox3/2xx3oo/x2x1o2o/4x2x1/o3o2x1 ne x",
+    "x6ox/x1oo3o1/2ooxx1x1/xo4oxo/3xx3x/1o1o1o3/o1o1x3x/1xx2oo2/3xo1x2 w x",
+];
+
+/**
+ * Searches every position in the built-in set to the given depth, and reports the nodes searched and time taken.
+ * The total number of nodes serves as a signature of the search: it does not depend on the speed of the machine,
+ * so a change that is meant to only make the search faster should leave it unchanged.
+ * The moves chosen and their evaluations are also hashed together into a second signature,
+ * which only changes when a change to the search makes it play differently.
+ * Every position is searched with an empty transposition table, so that the order does not affect the nodes.
+ */
+pub fn run_bench(depth: usize) {
+    let mut total_nodes = 0;
+    let mut moves_hash = FNV_OFFSET;
+    let table = Arc::new(TranspositionTable::new(DEFAULT_HASH_MB, Replacement::Aging));
+    let start = Instant::now();
+    for (i, position) in BENCH_POSITIONS.iter().enumerate() {
+        let (board, side) = board_from_string(position).unwrap();
+        let position_start = Instant::now();
+        table.clear();
+        let mut control = SearchControl::new(None).with_table(table.clone());
+        let (eval, line) = alpha_beta(
+            board,
+            side,
+            depth,
+            OUTCOME_LOSS,
+            OUTCOME_WIN,
+            depth,
+            &mut control,
+        );
+        if interrupted() {
+            return;
+        }
+        total_nodes += control.nodes;
+        for byte in [line[0] as u8].into_iter().chain(eval.to_be_bytes()) {
+            moves_hash = (moves_hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+        println!(
+            "bench position {} nodes {} time {} move {}",
+            i + 1,
+            control.nodes,
+            position_start.elapsed().as_millis(),
+            if line[0] == NULL_MOVE {
+                "none".to_string()
+            } else {
+                move_string(line[0], Notation::Zone)
+            }
+        );
+    }
+    let time = start.elapsed().as_millis();
+    println!(
+        "bench nodes {} time {} nps {} signature {} moves {:016x}",
+        total_nodes,
+        time,
+        total_nodes as u128 * 1000 / time.max(1),
+        total_nodes,
+        moves_hash
+    );
+}
+
+/**
+ * Runs the benchmark from the command line, as `bench [<depth>]`, and exits,
+ * so that a build's signature can be checked without starting a session.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let depth = match args {
+        [] => BENCH_DEPTH,
+        [depth] => depth
+            .parse()
+            .ok()
+            .filter(|depth| (1..=MAX_PLY).contains(depth))
+            .ok_or_else(|| format!("invalid depth {}", depth))?,
+        _ => return Err("usage: bench [<depth>]".to_string()),
+    };
+    run_bench(depth);
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use crate::engine::*;
+use crate::symmetry::{inverse_symmetry, transform_move};
+use crate::zobrist;
+
+// The size of an entry in a book file.
+pub const ENTRY_BYTES: usize = 16;
+
+/**
+ * A single entry of an opening book: a move to play in a position, with how often to play it.
+ * Like an entry of a Polyglot book, it is written in 16 bytes, all big-endian:
+ * the canonical hash of the position (8 bytes), the move (2), the weight (2) and the learn counter (4).
+ * The move is stored as played in the canonical form of the position, so that one entry
+ * covers every position symmetrical to it.
+ * The weight is relative to the other moves of the position, and the learn counter is free
+ * for tools that update books from their own games, such as by counting the games a move was played in.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub mv: Move,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn to_bytes(self) -> [u8; ENTRY_BYTES] {
+        let mut bytes = [0; ENTRY_BYTES];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&(self.mv as u16).to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mv = u16::from_be_bytes(bytes[8..10].try_into().ok()?) as Move;
+        if mv >= NULL_MOVE {
+            return None;
+        }
+        Some(Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().ok()?),
+            mv,
+            weight: u16::from_be_bytes(bytes[10..12].try_into().ok()?),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().ok()?),
+        })
+    }
+}
+
+/**
+ * An opening book, held as its entries sorted by key so that the moves of a position
+ * are found by binary search.
+ * A book file is nothing but its entries in this order, so it is read straight into memory.
+ */
+#[derive(Default)]
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    // Creates a book from entries in any order, with the moves of each position from the highest weight down.
+    pub fn new(mut entries: Vec<BookEntry>) -> Self {
+        entries.sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight), entry.mv));
+        Self { entries }
+    }
+
+    // Reads a book file, or returns `None` if it is not made up of valid entries in order.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if !bytes.len().is_multiple_of(ENTRY_BYTES) {
+            return None;
+        }
+        let entries = bytes
+            .chunks(ENTRY_BYTES)
+            .map(BookEntry::from_bytes)
+            .collect::<Option<Vec<_>>>()?;
+        if entries.windows(2).any(|pair| pair[0].key > pair[1].key) {
+            return None;
+        }
+        Some(Self { entries })
+    }
+
+    // Writes the book in the form read by `from_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries
+            .iter()
+            .flat_map(|entry| entry.to_bytes())
+            .collect()
+    }
+
+    pub fn entries(&self) -> &[BookEntry] {
+        &self.entries
+    }
+
+    /**
+     * Returns the entries of a position, from the highest weight down,
+     * with each move changed from the canonical form to the position itself.
+     */
+    pub fn lookup(&self, board: Board, side: bool) -> Vec<BookEntry> {
+        let (symmetry, key) = zobrist::canonical(board, side);
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .map(|&entry| BookEntry {
+                mv: transform_move(entry.mv, inverse_symmetry(symmetry)),
+                ..entry
+            })
+            .collect()
+    }
+
+    // The entry for a move played in a position, with the move changed to the canonical form of the position.
+    pub fn entry(board: Board, side: bool, mv: Move, weight: u16, learn: u32) -> BookEntry {
+        let (symmetry, key) = zobrist::canonical(board, side);
+        BookEntry {
+            key,
+            mv: transform_move(mv, symmetry),
+            weight,
+            learn,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::TestRules;
+    use crate::symmetry::{transform_board, SYMMETRIES};
+
+    #[test]
+    fn books_survive_a_round_trip() {
+        let _rules = TestRules::standard();
+        let empty = (0, 0, ZONE_ANY << 54);
+        let after = play_move(empty, 40, false);
+        let book = Book::new(vec![
+            Book::entry(after, true, 36, 3, 1),
+            Book::entry(empty, false, 40, 10, 7),
+            Book::entry(empty, false, 0, 20, 9),
+        ]);
+        let bytes = book.to_bytes();
+        assert_eq!(bytes.len(), 3 * ENTRY_BYTES);
+        let read = Book::from_bytes(&bytes).unwrap();
+        assert!(read.entries() == book.entries());
+
+        assert!(Book::from_bytes(&bytes[1..]).is_none());
+        let unsorted: Vec<u8> = bytes.chunks(ENTRY_BYTES).rev().flatten().copied().collect();
+        assert!(Book::from_bytes(&unsorted).is_none());
+        let mut invalid = bytes.clone();
+        invalid[8..10].copy_from_slice(&(NULL_MOVE as u16).to_be_bytes());
+        assert!(Book::from_bytes(&invalid).is_none());
+    }
+
+    #[test]
+    fn symmetrical_positions_share_entries() {
+        let _rules = TestRules::standard();
+        // No symmetry leaves this position unchanged, so its moves are all told apart.
+        let board = play_move((0, 0, ZONE_ANY << 54), 9, false);
+        let book = Book::new(vec![
+            Book::entry(board, true, 4, 1, 0),
+            Book::entry(board, true, 0, 5, 0),
+        ]);
+        for symmetry in 0..SYMMETRIES {
+            let moves: Vec<_> = book
+                .lookup(transform_board(board, symmetry), true)
+                .iter()
+                .map(|entry| (entry.mv, entry.weight))
+                .collect();
+            assert_eq!(
+                moves,
+                [
+                    (transform_move(0, symmetry), 5),
+                    (transform_move(4, symmetry), 1)
+                ]
+            );
+        }
+        assert!(book.lookup(board, false).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::engine::*;
+use crate::notation::*;
+use crate::timeman::TimeManager;
+use crate::tt::{Replacement, TranspositionTable};
+
+// The prefix of every message addressed to the bot, such as `!ut3 play c/c`.
+pub const PREFIX: &str = "!ut3";
+
+// The size of the transposition table of each session, kept small since many channels may have a game at once.
+const SESSION_HASH_MB: usize = 4;
+
+// The most sessions held at once, after which new games are refused until one ends.
+const MAX_SESSIONS: usize = 256;
+
+// How long the bot thinks about each of its moves, and the default and longest analysis, in milliseconds.
+const MOVE_TIME_MS: u64 = 1000;
+const ANALYSIS_TIME_MS: u64 = 2000;
+const MAX_ANALYSIS_TIME_MS: u64 = 10000;
+
+// The emoji each cell is drawn with: the two players, and empty cells that can and cannot be played in.
+const EMOJI_X: &str = "❌";
+const EMOJI_O: &str = "⭕";
+const EMOJI_LEGAL: &str = "🟨";
+const EMOJI_EMPTY: &str = "⬛";
+
+/**
+ * Draws a board as nine rows of emoji, one for each cell, with a gap between zones,
+ * so that it can be posted in a chat message without a code block.
+ * Empty cells that can be played in next are highlighted.
+ */
+pub fn board_emoji(board: Board) -> String {
+    let legal: Vec<Move> = generate_moves(board).collect();
+    let mut rows = Vec::new();
+    for row in 0..9 {
+        let mut cells = String::new();
+        for col in 0..9 {
+            if col > 0 && col % 3 == 0 {
+                cells.push(' ');
+            }
+            let mv = move_from_row_col(row, col);
+            let (x_cells, o_cells) = zone_cells(board, mv / 9);
+            cells += match ((x_cells >> (mv % 9)) & 1, (o_cells >> (mv % 9)) & 1) {
+                (1, _) => EMOJI_X,
+                (_, 1) => EMOJI_O,
+                _ if legal.contains(&mv) => EMOJI_LEGAL,
+                _ => EMOJI_EMPTY,
+            };
+        }
+        rows.push(cells);
+        if row % 3 == 2 && row < 8 {
+            rows.push(String::new());
+        }
+    }
+    rows.join("\n")
+}
+
+// How a game has ended: won by the given player, or drawn with `None`.
+fn outcome(board: Board) -> Option<Option<bool>> {
+    if let Some(side) = winner(board) {
+        Some(Some(side))
+    } else if generate_moves(board).next().is_none() {
+        Some(full_grid_winner(board))
+    } else {
+        None
+    }
+}
+
+// A game played in one channel against the bot, with its own transposition table.
+struct Session {
+    board: Board,
+    side: bool,
+    // The side the bot plays.
+    bot_side: bool,
+    table: Arc<TranspositionTable>,
+}
+
+impl Session {
+    fn search(&self, movetime: u64) -> (usize, i32, [Move; MAX_PLY]) {
+        self.table.new_search();
+        let mut control = SearchControl::new(None).with_table(self.table.clone());
+        let time = TimeManager::new(movetime);
+        iterative_deepening(self.board, self.side, MAX_PLY, Some(&time), &mut control)
+    }
+
+    // The board with a line saying whose turn it is, or how the game ended.
+    fn status(&self) -> String {
+        let status = match outcome(self.board) {
+            Some(Some(winner)) if winner == self.bot_side => "I win!".to_string(),
+            Some(Some(_)) => "You win!".to_string(),
+            Some(None) => "The game is drawn.".to_string(),
+            None => format!("Your move, as {}.", player_string(self.side).to_uppercase()),
+        };
+        format!("{}\n{}", board_emoji(self.board), status)
+    }
+}
+
+/**
+ * The games the bot is playing, one for each channel, for a chat bot to pass its messages to.
+ * This holds no connection of its own: a bot for Discord or any other service reads messages from its channels,
+ * passes each to `handle`, and posts any reply it returns.
+ * The commands, each following `PREFIX`, are `new [x|o]`, `play <move>`, `board`, `analyse [<ms>]`, `resign` and `help`.
+ */
+#[derive(Default)]
+pub struct SessionManager {
+    sessions: HashMap<u64, Session>,
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The number of channels with a game in progress.
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /**
+     * Handles a message posted in a channel, returning the reply to post, or `None` if it is not addressed to the bot.
+     * Moves by the bot are searched before returning, so this may take as long as a search.
+     */
+    pub fn handle(&mut self, channel: u64, message: &str) -> Option<String> {
+        let mut words = message.split_whitespace();
+        if words.next()? != PREFIX {
+            return None;
+        }
+        let command = words.next().unwrap_or("help");
+        let argument = words.next();
+        Some(match (command, self.sessions.get_mut(&channel)) {
+            ("new", _) => self.new_game(channel, argument),
+            ("play", Some(session)) => {
+                let reply = Self::play(session, argument);
+                if outcome(session.board).is_some() {
+                    self.sessions.remove(&channel);
+                }
+                reply
+            }
+            ("board", Some(session)) => session.status(),
+            ("analyse", Some(session)) => {
+                let movetime = match argument.map(str::parse::<u64>) {
+                    None => ANALYSIS_TIME_MS,
+                    Some(Ok(movetime)) if movetime > 0 => movetime.min(MAX_ANALYSIS_TIME_MS),
+                    Some(_) => return Some("That is not a number of milliseconds.".to_string()),
+                };
+                if outcome(session.board).is_some() {
+                    return Some("The game is over.".to_string());
+                }
+                let (depth, eval, line) = session.search(movetime);
+                format!(
+                    "Depth {}, eval {} for {}: {}",
+                    depth,
+                    eval_string(eval, depth),
+                    player_string(session.side).to_uppercase(),
+                    pv_string(&line, Notation::Zone)
+                )
+            }
+            ("resign", Some(_)) => {
+                self.sessions.remove(&channel);
+                "You resigned. Start another game with `!ut3 new`.".to_string()
+            }
+            ("play" | "board" | "analyse" | "resign", None) => {
+                "There is no game in this channel. Start one with `!ut3 new`.".to_string()
+            }
+            _ => format!(
+                "Commands: `{0} new [x|o]` starts a game with you as X or O, `{0} play <move>` plays a move such as `c/nw`, \
+                 `{0} board` shows the game, `{0} analyse [ms]` analyses it, and `{0} resign` ends it.",
+                PREFIX
+            ),
+        })
+    }
+
+    fn new_game(&mut self, channel: u64, side: Option<&str>) -> String {
+        let bot_side = match side {
+            None | Some("x") => true,
+            Some("o") => false,
+            Some(_) => return "Choose to play as `x` or `o`.".to_string(),
+        };
+        if !self.sessions.contains_key(&channel) && self.sessions.len() >= MAX_SESSIONS {
+            return "Too many games are being played. Try again later.".to_string();
+        }
+        let mut session = Session {
+            board: (0, 0, ZONE_ANY << 54),
+            side: false,
+            bot_side,
+            table: Arc::new(TranspositionTable::new(SESSION_HASH_MB, Replacement::Aging)),
+        };
+        let mut reply = String::new();
+        if bot_side == session.side {
+            let (_, _, line) = session.search(MOVE_TIME_MS);
+            session.board = play_move(session.board, line[0], session.side);
+            session.side = !session.side;
+            reply = format!("I play {}.\n", move_string(line[0], Notation::Zone));
+        }
+        reply += &session.status();
+        self.sessions.insert(channel, session);
+        reply
+    }
+
+    // Plays the user's move, then the bot's reply unless the game is over.
+    fn play(session: &mut Session, mv: Option<&str>) -> String {
+        if session.side == session.bot_side {
+            return "It is not your move.".to_string();
+        }
+        let Some(mv) = mv.and_then(move_from_string) else {
+            return "That is not a move. Moves are written as `zone/square`, such as `c/nw`."
+                .to_string();
+        };
+        if check_move(session.board, mv).is_err() {
+            return format!("{} is not a legal move.", move_string(mv, Notation::Zone));
+        }
+        session.board = play_move(session.board, mv, session.side);
+        session.side = !session.side;
+        if outcome(session.board).is_some() {
+            return session.status();
+        }
+        let (_, _, line) = session.search(MOVE_TIME_MS);
+        session.board = play_move(session.board, line[0], session.side);
+        session.side = !session.side;
+        format!(
+            "I play {}.\n{}",
+            move_string(line[0], Notation::Zone),
+            session.status()
+        )
+    }
+}
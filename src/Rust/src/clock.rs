@@ -0,0 +1,44 @@
+// The clock used to time searches.
+// Everywhere but WebAssembly, this is the monotonic clock of the standard library.
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+pub use self::wasm_clock::Instant;
+
+/**
+ * WebAssembly in the browser has no clock of its own, so `std::time::Instant::now()` panics.
+ * Instead, the host is asked for the time through an imported `now_ms` function,
+ * which the JavaScript glue provides as `performance.now()`.
+ * Only the operations the search needs are implemented.
+ */
+#[cfg(target_arch = "wasm32")]
+mod wasm_clock {
+    use std::ops::Add;
+    use std::time::Duration;
+
+    extern "C" {
+        fn now_ms() -> f64;
+    }
+
+    #[derive(Clone, Copy, PartialEq, PartialOrd)]
+    pub struct Instant(f64);
+
+    impl Instant {
+        pub fn now() -> Self {
+            Self(unsafe { now_ms() })
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Duration::from_secs_f64((Self::now().0 - self.0).max(0.0) / 1000.0)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Self;
+
+        fn add(self, duration: Duration) -> Self {
+            Self(self.0 + duration.as_secs_f64() * 1000.0)
+        }
+    }
+}
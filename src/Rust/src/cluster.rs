@@ -0,0 +1,268 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crate::arena::{game_result, GameResult};
+use crate::engine::*;
+use ut3b2l::notation::*;
+
+// Passes the lines of a worker connection to its engine process, and the engine's output back, until either closes.
+fn serve_worker_connection(stream: TcpStream) -> std::io::Result<()> {
+    let mut child = Command::new(std::env::current_exe()?)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut input = child.stdin.take().unwrap();
+    let output = child.stdout.take().unwrap();
+    let reader = BufReader::new(stream.try_clone()?);
+    thread::spawn(move || {
+        for line in reader.lines().map_while(Result::ok) {
+            if writeln!(input, "{}", line).is_err() {
+                return;
+            }
+        }
+    });
+    let mut stream = stream;
+    for line in BufReader::new(output).lines().map_while(Result::ok) {
+        if writeln!(stream, "{}", line).is_err() {
+            break;
+        }
+    }
+    child.kill().ok();
+    child.wait()?;
+    Ok(())
+}
+
+/**
+ * Serves the command protocol over plain TCP on the given address, for a coordinator to send searches to.
+ * As with the WebSocket server, each connection is a session of its own, run in a separate engine process.
+ */
+pub fn serve_worker(address: &str) {
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("cannot listen on {}: {}", address, error);
+            return;
+        }
+    };
+    eprintln!("listening on {}", address);
+    for stream in listener.incoming().flatten() {
+        thread::spawn(move || serve_worker_connection(stream));
+    }
+}
+
+/**
+ * Converts the evaluation of a position into the evaluation of its parent, from the other side.
+ * Forced wins and losses are one ply further away from the parent.
+ */
+fn parent_eval(eval: i32) -> i32 {
+    if eval >= OUTCOME_WIN - MAX_PLY as i32 {
+        -eval + 1
+    } else if eval <= OUTCOME_LOSS + MAX_PLY as i32 {
+        -eval - 1
+    } else {
+        -eval
+    }
+}
+
+// The result of searching a single root move: its evaluation from the root, its line, and the worker that searched it.
+type MoveResult = (Move, i32, Vec<Move>, String);
+
+// A connection to a worker, through which it searches one position at a time.
+struct Worker {
+    address: String,
+    stream: TcpStream,
+    lines: BufReader<TcpStream>,
+}
+
+impl Worker {
+    fn connect(address: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address)
+            .map_err(|error| format!("cannot connect to {}: {}", address, error))?;
+        let lines = BufReader::new(stream.try_clone().map_err(|error| error.to_string())?);
+        let mut worker = Self {
+            address: address.to_string(),
+            stream,
+            lines,
+        };
+        worker
+            .expect("ready")
+            .ok_or_else(|| format!("{} is not a worker", address))?;
+        Ok(worker)
+    }
+
+    // Reads lines until one starts with the given word, returning it, or `None` if the connection closes.
+    fn expect(&mut self, word: &str) -> Option<String> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.lines.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            if line.split_whitespace().next() == Some(word) {
+                return Some(line.trim_end().to_string());
+            }
+        }
+    }
+
+    // Searches a position to the given depth, returning its evaluation and principal variation.
+    fn search(&mut self, board: Board, side: bool, depth: usize) -> Option<(i32, Vec<Move>)> {
+        writeln!(self.stream, "newgame {}", board_string(board, side)).ok()?;
+        if self.expect("newgame")? != "newgame ok" {
+            return None;
+        }
+        writeln!(self.stream, "go {}", depth).ok()?;
+        let line = self.expect("info")?;
+        let words: Vec<_> = line.split_whitespace().collect();
+        let eval_at = words.iter().position(|&word| word == "eval")?;
+        let pv_at = words.iter().position(|&word| word == "pv")?;
+        let eval = eval_from_string(words.get(eval_at + 1)?)?;
+        let pv = words[pv_at + 1..eval_at]
+            .iter()
+            .map(|mv| move_from_string(mv))
+            .collect::<Option<Vec<_>>>()?;
+        Some((eval, pv))
+    }
+}
+
+/**
+ * Searches root moves taken from the shared queue until it is empty, sending the result of each.
+ * A move whose search fails is put back for another worker, and this worker is given up on.
+ */
+fn run_worker(
+    mut worker: Worker,
+    board: Board,
+    side: bool,
+    depth: usize,
+    queue: Arc<Mutex<Vec<Move>>>,
+    results: Sender<MoveResult>,
+) {
+    loop {
+        let Some(mv) = queue.lock().unwrap().pop() else {
+            return;
+        };
+        let child = play_move(board, mv, side);
+        match worker.search(child, !side, depth - 1) {
+            Some((eval, pv)) => {
+                let mut line = vec![mv];
+                line.extend(pv);
+                let result = (mv, parent_eval(eval), line, worker.address.clone());
+                if results.send(result).is_err() {
+                    return;
+                }
+            }
+            None => {
+                eprintln!("worker {} failed", worker.address);
+                queue.lock().unwrap().push(mv);
+                return;
+            }
+        }
+    }
+}
+
+/**
+ * Searches a position across worker processes on other machines, as
+ * `cluster --worker <address> ... [--depth <depth>] [--position <position>]`,
+ * where each worker is an engine started with `--worker <address>`.
+ * The root moves are shared out among the workers, each taking the next move as soon as it finishes one,
+ * and every move is searched with a full window to the given depth (10 by default), so that each evaluation is exact.
+ * The result of each move is written as it arrives, followed by the best move in the usual `info` format.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut addresses = Vec::new();
+    let mut depth = 10;
+    let mut position = "9/9/9/9/9/9/9/9/9 any x".to_string();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--worker" => addresses.push(value.clone()),
+            "--depth" => {
+                depth = value
+                    .parse()
+                    .ok()
+                    .filter(|depth| (2..=MAX_PLY).contains(depth))
+                    .ok_or_else(|| format!("depth must be from 2 to {}", MAX_PLY))?
+            }
+            "--position" => position = value.clone(),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    if addresses.is_empty() {
+        return Err("a cluster needs at least one worker".to_string());
+    }
+    let (board, side) =
+        board_from_string(&position).ok_or_else(|| format!("invalid position {}", position))?;
+    if game_result(board).is_some() {
+        return Err("the game is already over".to_string());
+    }
+
+    let start = Instant::now();
+    let (sender, receiver) = channel();
+    // Moves that end the game need no search, and are scored straight away.
+    let mut queue = Vec::new();
+    for mv in generate_moves(board) {
+        match game_result(play_move(board, mv, side)) {
+            Some(GameResult::Draw) => {
+                sender
+                    .send((mv, OUTCOME_DRAW, vec![mv], "local".to_string()))
+                    .ok();
+            }
+            // Under the misère variant, the move that ends the game can lose it.
+            Some(result) => {
+                let eval = if result == GameResult::win_for(side) {
+                    OUTCOME_WIN - 1
+                } else {
+                    OUTCOME_LOSS + 1
+                };
+                sender.send((mv, eval, vec![mv], "local".to_string())).ok();
+            }
+            None => queue.push(mv),
+        }
+    }
+    let total = generate_moves(board).count();
+    // The queue is taken from the back, so it is reversed to search moves in generation order.
+    queue.reverse();
+    let queue = Arc::new(Mutex::new(queue));
+    for address in &addresses {
+        let worker = Worker::connect(address)?;
+        let (queue, sender) = (queue.clone(), sender.clone());
+        thread::spawn(move || run_worker(worker, board, side, depth, queue, sender));
+    }
+    drop(sender);
+
+    let mut results: Vec<MoveResult> = Vec::new();
+    for result in receiver {
+        println!(
+            "cluster move {} eval {} worker {}",
+            move_string(result.0, Notation::Zone),
+            eval_string(result.1, depth),
+            result.3
+        );
+        results.push(result);
+    }
+    if results.len() < total {
+        return Err(format!(
+            "every worker failed with {} moves unsearched",
+            total - results.len()
+        ));
+    }
+    // Ties are broken by move generation order, as in a search on a single machine.
+    results.sort_by_key(|&(mv, eval, _, _)| (-eval, mv));
+    let (_, eval, line, _) = &results[0];
+    println!(
+        "info depth {} pv {} eval {} time {}",
+        depth,
+        pv_string(line, Notation::Zone),
+        eval_string(*eval, depth),
+        start.elapsed().as_millis()
+    );
+    Ok(())
+}
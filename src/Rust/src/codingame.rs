@@ -0,0 +1,87 @@
+use std::io::stdin;
+use std::sync::Arc;
+
+use crate::engine::*;
+use crate::eval_string;
+use crate::timeman::TimeManager;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB};
+
+// Time budgets given by the CodinGame referee, in milliseconds.
+const FIRST_TURN_MS: u64 = 1000;
+const TURN_MS: u64 = 100;
+
+// Reads the next line of input as a list of integers,
+// returning `None` once input has ended or the line is malformed.
+fn read_numbers(lines: &mut impl Iterator<Item = std::io::Result<String>>) -> Option<Vec<i64>> {
+    lines
+        .next()?
+        .ok()?
+        .split_whitespace()
+        .map(|n| n.parse().ok())
+        .collect()
+}
+
+/**
+ * Plays a game under the CodinGame Ultimate Tic-Tac-Toe conventions.
+ * Each turn, the referee sends the opponent's last move as a 0-indexed row and column
+ * (`-1 -1` if the engine moves first), followed by the number of valid actions and the actions themselves.
+ * The engine replies with the row and column of its move, searching as deep as the time budget for the turn allows.
+ * Search information is written to standard error, which the referee displays but does not read.
+ */
+pub fn run() {
+    let mut lines = stdin().lines();
+    let mut board: Board = (0, 0, ZONE_ANY << 54);
+
+    // The engine plays Player X (`false`) unless the opponent has moved before the first turn.
+    let mut side = false;
+    let mut first_turn = true;
+
+    // The table is kept between turns, since most of each search is below a position searched on the last turn.
+    let table = Arc::new(TranspositionTable::new(DEFAULT_HASH_MB, Replacement::Aging));
+
+    while let Some(opponent_move) = read_numbers(&mut lines) {
+        let time = TimeManager::new(if first_turn { FIRST_TURN_MS } else { TURN_MS });
+
+        if let [row @ 0..=8, col @ 0..=8] = opponent_move[..] {
+            if first_turn {
+                side = true;
+            }
+            board = play_move(board, move_from_row_col(row as u64, col as u64), !side);
+        }
+
+        // The valid actions are implied by the board, so they only need to be read past.
+        let action_count = match read_numbers(&mut lines).as_deref() {
+            Some(&[count]) => count,
+            _ => return,
+        };
+        for _ in 0..action_count {
+            if read_numbers(&mut lines).is_none() {
+                return;
+            }
+        }
+
+        table.new_search();
+        let (depth, eval, line) = iterative_deepening(
+            board,
+            side,
+            MAX_PLY,
+            Some(&time),
+            &mut SearchControl::new(None).with_table(table.clone()),
+        );
+        let mv = if line[0] == NULL_MOVE {
+            match generate_moves(board).next() {
+                Some(mv) => mv,
+                None => return,
+            }
+        } else {
+            line[0]
+        };
+
+        let (row, col) = move_to_row_col(mv);
+        println!("{} {}", row, col);
+        eprintln!("depth {} eval {}", depth, eval_string(eval, depth));
+
+        board = play_move(board, mv, side);
+        first_turn = false;
+    }
+}
@@ -0,0 +1,199 @@
+use crate::arena::GameRecord;
+use crate::db::positions;
+use crate::engine::*;
+use ut3b2l::json::Json;
+use ut3b2l::notation::*;
+
+// The size of a sample in the binary format: three planes of 81 cells, the result, then the score.
+pub const SAMPLE_BYTES: usize = 3 * 81 + 1 + 4;
+
+/**
+ * A position from a game whose move was chosen by a search, with that search's result and the game's result,
+ * all from the perspective of the side to move.
+ * The result is the points scored by the side to move, out of 2 as in `GameResult::points`.
+ */
+pub struct Sample {
+    pub game: usize,
+    pub ply: usize,
+    pub board: Board,
+    pub side: bool,
+    pub mv: Move,
+    pub eval: i32,
+    pub depth: usize,
+    pub result: u32,
+}
+
+impl Sample {
+    // The cells of the side to move, the cells of the other side, and the legal moves,
+    // each as 81 values of 0 or 1 indexed by the move of the cell, `9 * zone + square`.
+    pub fn planes(&self) -> [[u8; 81]; 3] {
+        let mut planes = [[0; 81]; 3];
+        for zone in 0..9 {
+            let (x_cells, o_cells) = zone_cells(self.board, zone);
+            let (own, other) = if self.side {
+                (o_cells, x_cells)
+            } else {
+                (x_cells, o_cells)
+            };
+            for square in 0..9 {
+                let cell = (9 * zone + square) as usize;
+                planes[0][cell] = ((own >> square) & 1) as u8;
+                planes[1][cell] = ((other >> square) & 1) as u8;
+            }
+        }
+        for mv in generate_moves(self.board) {
+            planes[2][mv as usize] = 1;
+        }
+        planes
+    }
+
+    // The cells in move order as `x`, `o` or `.`, so that a position can be read without parsing a position string.
+    fn cells(&self) -> String {
+        (0..9)
+            .flat_map(|zone| {
+                let (x_cells, o_cells) = zone_cells(self.board, zone);
+                (0..9).map(
+                    move |square| match ((x_cells >> square) & 1, (o_cells >> square) & 1) {
+                        (1, _) => 'x',
+                        (_, 1) => 'o',
+                        _ => '.',
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn zone(&self) -> u64 {
+        (self.board.2 >> 54) & 0b1111
+    }
+
+    pub fn csv_header() -> &'static str {
+        "game,ply,position,cells,zone,side,move,score,depth,result"
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.game,
+            self.ply,
+            board_string(self.board, self.side),
+            self.cells(),
+            self.zone(),
+            player_string(self.side),
+            move_string(self.mv, Notation::Zone),
+            self.eval,
+            self.depth,
+            self.result
+        )
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("game", Json::Number(self.game as i64)),
+            ("ply", Json::Number(self.ply as i64)),
+            (
+                "position",
+                Json::String(board_string(self.board, self.side)),
+            ),
+            ("cells", Json::String(self.cells())),
+            ("zone", Json::Number(self.zone() as i64)),
+            ("side", Json::String(player_string(self.side).to_string())),
+            ("move", Json::String(move_string(self.mv, Notation::Zone))),
+            ("score", Json::Number(self.eval as i64)),
+            ("depth", Json::Number(self.depth as i64)),
+            ("result", Json::Number(self.result as i64)),
+        ])
+    }
+
+    // The sample in the binary format: the three planes, one byte each, then the result as a byte,
+    // then the score as a big-endian signed 32-bit number.
+    pub fn to_bytes(&self) -> [u8; SAMPLE_BYTES] {
+        let mut bytes = [0; SAMPLE_BYTES];
+        for (i, plane) in self.planes().iter().enumerate() {
+            bytes[81 * i..81 * (i + 1)].copy_from_slice(plane);
+        }
+        bytes[3 * 81] = self.result as u8;
+        bytes[3 * 81 + 1..].copy_from_slice(&self.eval.to_be_bytes());
+        bytes
+    }
+}
+
+// Every position of the games whose move was chosen by a search, numbering games and plies from 1 and 0.
+pub fn samples(records: &[GameRecord]) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        for (ply, (&(board, side), &(mv, search))) in
+            positions(record).iter().zip(&record.moves).enumerate()
+        {
+            if let Some((eval, depth)) = search {
+                samples.push(Sample {
+                    game: index + 1,
+                    ply,
+                    board,
+                    side,
+                    mv,
+                    eval,
+                    depth,
+                    result: record.result.points(side),
+                });
+            }
+        }
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::GameResult;
+
+    #[test]
+    fn samples_are_taken_from_searched_moves() {
+        let record = GameRecord {
+            x_name: "a".to_string(),
+            o_name: "b".to_string(),
+            position: "9/9/9/9/9/9/9/9/9 any x".to_string(),
+            moves: vec![(40, Some((12, 5))), (36, None), (4, Some((-3, 6)))],
+            result: GameResult::XWins,
+            termination: "normal".to_string(),
+        };
+        let samples = samples(&[record]);
+        let found: Vec<_> = samples
+            .iter()
+            .map(|sample| {
+                (
+                    sample.game,
+                    sample.ply,
+                    sample.side,
+                    sample.mv,
+                    sample.eval,
+                    sample.result,
+                )
+            })
+            .collect();
+        assert_eq!(found, [(1, 0, false, 40, 12, 2), (1, 2, false, 4, -3, 2)]);
+    }
+
+    #[test]
+    fn binary_samples_are_seen_from_the_side_to_move() {
+        let board = play_move((0, 0, ZONE_ANY << 54), 40, false);
+        let sample = Sample {
+            game: 1,
+            ply: 1,
+            board,
+            side: true,
+            mv: 36,
+            eval: -37,
+            depth: 4,
+            result: 0,
+        };
+        let bytes = sample.to_bytes();
+        assert!(bytes[..81].iter().all(|&cell| cell == 0));
+        let other: Vec<_> = (0..81).filter(|&cell| bytes[81 + cell] == 1).collect();
+        assert_eq!(other, [40]);
+        let legal: Vec<_> = (0..81).filter(|&cell| bytes[162 + cell] == 1).collect();
+        assert_eq!(legal, [36, 37, 38, 39, 41, 42, 43, 44]);
+        assert_eq!(bytes[243], 0);
+        assert_eq!(i32::from_be_bytes(bytes[244..].try_into().unwrap()), -37);
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{metadata, read, read_to_string, write, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use crate::arena::{GameRecord, GameResult};
+use crate::dataset::{samples, Sample};
+use crate::engine::*;
+use crate::puzzles::{write_puzzles, DEFAULT_PUZZLE_DEPTH};
+use crate::zobrist;
+use ut3b2l::book::{Book, BookEntry};
+use ut3b2l::notation::*;
+use ut3b2l::symmetry::{inverse_symmetry, transform_board, transform_move, SYMMETRIES};
+
+// How many plies of each game are put in an opening book when no number is given.
+const DEFAULT_BOOK_PLIES: usize = 8;
+
+// The bounds of the ranges of evaluations that the results of searched positions are grouped into.
+const CALIBRATION_BOUNDS: [i32; 10] = [-500, -200, -100, -50, -20, 20, 50, 100, 200, 500];
+
+// The size of an entry in an index file.
+const INDEX_ENTRY_BYTES: usize = 24;
+
+// The byte ranges of the records in the text of a file of game records.
+fn record_ranges(text: &str) -> Vec<Range<usize>> {
+    // Each record starts with its `X` tag, so the file is split before every one.
+    let mut starts: Vec<_> = text.match_indices("[X \"").map(|(i, _)| i).collect();
+    starts.push(text.len());
+    starts.windows(2).map(|range| range[0]..range[1]).collect()
+}
+
+// Reads every game in a file of game records, as appended by `--out`.
+pub fn read_records(path: &str) -> Result<Vec<GameRecord>, String> {
+    let text = read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+    record_ranges(&text)
+        .into_iter()
+        .enumerate()
+        .map(|(index, range)| {
+            GameRecord::from_text(&text[range])
+                .ok_or_else(|| format!("invalid record {} in {}", index + 1, path))
+        })
+        .collect()
+}
+
+/**
+ * An entry of the index of a file of game records, for one position reached in one game.
+ * It is written in 24 bytes, all big-endian: the canonical hash of the position (8 bytes),
+ * the offset of the record of the game in the file (8), the length of the record (4)
+ * and the index of the game in the file (4).
+ * A game never reaches two positions symmetrical to each other, as every move adds a piece,
+ * so it has at most one entry for each key.
+ */
+#[derive(Clone, Copy)]
+struct IndexEntry {
+    key: u64,
+    offset: u64,
+    length: u32,
+    game: u32,
+}
+
+impl IndexEntry {
+    fn to_bytes(self) -> [u8; INDEX_ENTRY_BYTES] {
+        let mut bytes = [0; INDEX_ENTRY_BYTES];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..16].copy_from_slice(&self.offset.to_be_bytes());
+        bytes[16..20].copy_from_slice(&self.length.to_be_bytes());
+        bytes[20..24].copy_from_slice(&self.game.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().ok()?),
+            offset: u64::from_be_bytes(bytes[8..16].try_into().ok()?),
+            length: u32::from_be_bytes(bytes[16..20].try_into().ok()?),
+            game: u32::from_be_bytes(bytes[20..24].try_into().ok()?),
+        })
+    }
+}
+
+// The path of the index of a file of game records.
+fn index_path(path: &str) -> String {
+    format!("{}.index", path)
+}
+
+/**
+ * Writes the index of a file of game records next to it, as `<file>.index`:
+ * an entry for every position reached in every game, sorted by key like the entries of a book,
+ * so that the games reaching a position are found by binary search without reading the other records.
+ */
+fn build_index(path: &str) -> Result<(), String> {
+    let text = read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+    let mut entries = Vec::new();
+    let ranges = record_ranges(&text);
+    for (game, range) in ranges.iter().enumerate() {
+        let record = GameRecord::from_text(&text[range.clone()])
+            .ok_or_else(|| format!("invalid record {} in {}", game + 1, path))?;
+        entries.extend(
+            positions(&record)
+                .into_iter()
+                .map(|(board, side)| IndexEntry {
+                    key: zobrist::canonical(board, side).1,
+                    offset: range.start as u64,
+                    length: range.len() as u32,
+                    game: game as u32,
+                }),
+        );
+    }
+    entries.sort_by_key(|entry| (entry.key, entry.game));
+    let bytes: Vec<u8> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+    let index = index_path(path);
+    write(&index, bytes).map_err(|error| format!("cannot write {}: {}", index, error))?;
+    println!(
+        "db index games {} positions {}",
+        ranges.len(),
+        entries.len()
+    );
+    Ok(())
+}
+
+/**
+ * Reads the games that reached a position or any position symmetrical to it, with their indices.
+ * If the file has an index at least as new as itself, only the records it points to are read,
+ * and otherwise every record is read and the games are picked out from all of them.
+ */
+fn games_reaching(
+    path: &str,
+    board: Board,
+    side: bool,
+) -> Result<Vec<(usize, GameRecord)>, String> {
+    let key = zobrist::canonical(board, side).1;
+    let index = index_path(path);
+    let modified = |path: &str| metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let fresh = matches!((modified(&index), modified(path)), (Some(index), Some(records)) if index >= records);
+    if !fresh {
+        return Ok(read_records(path)?
+            .into_iter()
+            .enumerate()
+            .filter(|(_, record)| {
+                positions(record)
+                    .iter()
+                    .any(|&(board, side)| zobrist::canonical(board, side).1 == key)
+            })
+            .collect());
+    }
+
+    let bytes = read(&index).map_err(|error| format!("cannot read {}: {}", index, error))?;
+    if !bytes.len().is_multiple_of(INDEX_ENTRY_BYTES) {
+        return Err(format!("invalid index {}", index));
+    }
+    let entries: Vec<_> = bytes
+        .chunks(INDEX_ENTRY_BYTES)
+        .filter_map(IndexEntry::from_bytes)
+        .collect();
+    let start = entries.partition_point(|entry| entry.key < key);
+    let mut file = File::open(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+    entries[start..]
+        .iter()
+        .take_while(|entry| entry.key == key)
+        .map(|entry| {
+            let mut text = vec![0; entry.length as usize];
+            file.seek(SeekFrom::Start(entry.offset))
+                .and_then(|_| file.read_exact(&mut text))
+                .map_err(|error| format!("cannot read {}: {}", path, error))?;
+            String::from_utf8(text)
+                .ok()
+                .and_then(|text| GameRecord::from_text(&text))
+                .map(|record| (entry.game as usize, record))
+                .ok_or_else(|| format!("invalid record {} in {}", entry.game + 1, path))
+        })
+        .collect()
+}
+
+// The positions of a game in order, starting from its opening, with the side to move in each.
+pub fn positions(record: &GameRecord) -> Vec<(Board, bool)> {
+    let Some((mut board, mut side)) = board_from_string(&record.position) else {
+        return Vec::new();
+    };
+    let mut positions = vec![(board, side)];
+    for &(mv, _) in &record.moves {
+        board = play_move(board, mv, side);
+        side = !side;
+        positions.push((board, side));
+    }
+    positions
+}
+
+// The number of games won by Player X, drawn and won by Player O.
+fn result_counts<'a>(results: impl Iterator<Item = &'a GameResult>) -> [usize; 3] {
+    let mut counts = [0; 3];
+    for result in results {
+        counts[match result {
+            GameResult::XWins => 0,
+            GameResult::Draw => 1,
+            GameResult::OWins => 2,
+        }] += 1;
+    }
+    counts
+}
+
+// Summarises a database: its results, how its games ended, their length, and the depth of their searches.
+fn stats(records: &[GameRecord]) {
+    let [x, draws, o] = result_counts(records.iter().map(|record| &record.result));
+    println!("db games {} x {} draws {} o {}", records.len(), x, draws, o);
+
+    let mut terminations: Vec<(&str, usize)> = Vec::new();
+    for record in records {
+        match terminations
+            .iter_mut()
+            .find(|(termination, _)| *termination == record.termination)
+        {
+            Some((_, count)) => *count += 1,
+            None => terminations.push((&record.termination, 1)),
+        }
+    }
+    for (termination, count) in terminations {
+        println!("db termination {} {}", termination, count);
+    }
+
+    let plies: usize = records.iter().map(|record| record.moves.len()).sum();
+    let depths: Vec<usize> = records
+        .iter()
+        .flat_map(|record| &record.moves)
+        .filter_map(|&(_, search)| search.map(|(_, depth)| depth))
+        .collect();
+    let positions: HashSet<u64> = records
+        .iter()
+        .flat_map(positions)
+        .map(|(board, side)| zobrist::hash(board, side))
+        .collect();
+    println!(
+        "db plies {} average {:.1}",
+        plies,
+        plies as f64 / records.len().max(1) as f64
+    );
+    println!(
+        "db depth average {:.1}",
+        depths.iter().sum::<usize>() as f64 / depths.len().max(1) as f64
+    );
+    println!("db positions {}", positions.len());
+}
+
+// The share of the points available that a number of points out of `games` games was, counting 2 for a win.
+fn score(points: u32, games: usize) -> f64 {
+    points as f64 / (2 * games.max(1)) as f64
+}
+
+/**
+ * Reports what self-play games say about the engine, for tuning its evaluation and contempt:
+ * how the player moving first fares, how long games are, which zone is won first and by whom,
+ * and how the evaluations of searched positions compare with the results that followed them.
+ */
+fn selfplay_stats(records: &[GameRecord]) {
+    let mut first = [0; 3];
+    for record in records {
+        let side = board_from_string(&record.position).is_some_and(|(_, side)| side);
+        first[2 - record.result.points(side) as usize] += 1;
+    }
+    println!(
+        "db first games {} wins {} draws {} losses {} score {:.3}",
+        records.len(),
+        first[0],
+        first[1],
+        first[2],
+        score((2 * first[0] + first[1]) as u32, records.len())
+    );
+
+    let lengths: Vec<_> = records.iter().map(|record| record.moves.len()).collect();
+    println!(
+        "db length average {:.1} min {} max {}",
+        lengths.iter().sum::<usize>() as f64 / lengths.len().max(1) as f64,
+        lengths.iter().min().unwrap_or(&0),
+        lengths.iter().max().unwrap_or(&0)
+    );
+
+    // The games in which each zone was won first, by Player X and by Player O,
+    // and the games that its winner went on to win.
+    let mut zones = [[0; 4]; 9];
+    let mut unclaimed = 0;
+    for record in records {
+        let positions = positions(record);
+        let Some((&(board, _), rest)) = positions.split_first() else {
+            continue;
+        };
+        let start = (board.2 >> 36) & 0x3ffff;
+        let claimed = rest
+            .iter()
+            .map(|&(board, side)| ((board.2 >> 36) & 0x3ffff & !start, !side))
+            .find(|&(grid, _)| grid != 0);
+        match claimed {
+            Some((grid, side)) => {
+                let zone = grid.trailing_zeros() as usize % 9;
+                zones[zone][0] += 1;
+                zones[zone][1 + side as usize] += 1;
+                if record.result == GameResult::win_for(side) {
+                    zones[zone][3] += 1;
+                }
+            }
+            None => unclaimed += 1,
+        }
+    }
+    for (zone, [games, x, o, wins]) in zones.iter().enumerate() {
+        println!(
+            "db firstzone {} games {} x {} o {} wins {}",
+            ZONE_ARRAY_LOWER[zone], games, x, o, wins
+        );
+    }
+    println!("db firstzone none games {}", unclaimed);
+
+    // Forced losses and wins are grouped apart from the ranges of the other evaluations.
+    let mut buckets = vec![(0, 0); CALIBRATION_BOUNDS.len() + 3];
+    for sample in samples(records) {
+        let bucket = if sample.eval <= OUTCOME_LOSS + MAX_PLY as i32 {
+            0
+        } else if sample.eval >= OUTCOME_WIN - MAX_PLY as i32 {
+            buckets.len() - 1
+        } else {
+            1 + CALIBRATION_BOUNDS
+                .iter()
+                .filter(|&&bound| sample.eval >= bound)
+                .count()
+        };
+        buckets[bucket].0 += 1;
+        buckets[bucket].1 += sample.result;
+    }
+    for (bucket, &(samples, points)) in buckets.iter().enumerate() {
+        let range = match bucket {
+            0 => "L".to_string(),
+            1 => format!("..{}", CALIBRATION_BOUNDS[0]),
+            _ if bucket == buckets.len() - 1 => "W".to_string(),
+            _ if bucket == buckets.len() - 2 => format!("{}..", CALIBRATION_BOUNDS[bucket - 2]),
+            _ => format!(
+                "{}..{}",
+                CALIBRATION_BOUNDS[bucket - 2],
+                CALIBRATION_BOUNDS[bucket - 1]
+            ),
+        };
+        println!(
+            "db calibration eval {} samples {} score {:.3}",
+            range,
+            samples,
+            score(points, samples)
+        );
+    }
+}
+
+// A game that reached a position: its index, the ply it was reached at, and the move played from it, if any.
+type Found<'a> = (usize, usize, &'a GameRecord, Option<Move>);
+
+/**
+ * Reports the games that reached a position under the given command name, giving the results of those games,
+ * then the moves played from the position with the results of the games each was played in,
+ * most played first, then each game with the ply at which the position was reached.
+ */
+fn report(command: &str, found: &[Found]) {
+    let [x, draws, o] = result_counts(found.iter().map(|(_, _, record, _)| &record.result));
+    println!(
+        "db {} games {} x {} draws {} o {}",
+        command,
+        found.len(),
+        x,
+        draws,
+        o
+    );
+
+    let mut moves: HashMap<Move, Vec<GameResult>> = HashMap::new();
+    for &(_, _, record, mv) in found {
+        if let Some(mv) = mv {
+            moves.entry(mv).or_default().push(record.result);
+        }
+    }
+    let mut moves: Vec<_> = moves.into_iter().collect();
+    moves.sort_by_key(|(mv, results)| (std::cmp::Reverse(results.len()), *mv));
+    for (mv, results) in moves {
+        let [x, draws, o] = result_counts(results.iter());
+        println!(
+            "db move {} games {} x {} draws {} o {}",
+            move_string(mv, Notation::Zone),
+            results.len(),
+            x,
+            draws,
+            o
+        );
+    }
+    for &(index, ply, record, _) in found {
+        println!(
+            "db game {} ply {} {} vs {} {}",
+            index + 1,
+            ply,
+            record.x_name,
+            record.o_name,
+            record.result.score_string()
+        );
+    }
+}
+
+// Finds every game that reached exactly the given position, out of games that reached it or a symmetrical position.
+fn find(records: &[(usize, GameRecord)], board: Board, side: bool) {
+    let key = zobrist::hash(board, side);
+    let mut found = Vec::new();
+    for &(index, ref record) in records {
+        let ply = positions(record)
+            .iter()
+            .position(|&(board, side)| zobrist::hash(board, side) == key);
+        if let Some(ply) = ply {
+            found.push((index, ply, record, record.moves.get(ply).map(|&(mv, _)| mv)));
+        }
+    }
+    report("find", &found);
+}
+
+/**
+ * Finds every game that reached the given position or any position symmetrical to it,
+ * as an opening explorer of the games. The moves played from a symmetrical position
+ * are turned into the moves they correspond to in the given position, and moves that are
+ * symmetrical to each other in the given position, such as the corners of the empty board, count as one.
+ */
+fn lookup(records: &[(usize, GameRecord)], board: Board, side: bool) {
+    let (symmetry, key) = zobrist::canonical(board, side);
+    let unchanged: Vec<_> = (0..SYMMETRIES)
+        .filter(|&symmetry| transform_board(board, symmetry) == board)
+        .collect();
+    let mut found = Vec::new();
+    for &(index, ref record) in records {
+        let reached = positions(record)
+            .iter()
+            .enumerate()
+            .map(|(ply, &(board, side))| (ply, zobrist::canonical(board, side)))
+            .find(|&(_, (_, game_key))| game_key == key);
+        if let Some((ply, (game_symmetry, _))) = reached {
+            let mv = record.moves.get(ply).map(|&(mv, _)| {
+                let mv = transform_move(
+                    transform_move(mv, game_symmetry),
+                    inverse_symmetry(symmetry),
+                );
+                unchanged
+                    .iter()
+                    .map(|&symmetry| transform_move(mv, symmetry))
+                    .min()
+                    .unwrap()
+            });
+            found.push((index, ply, record, mv));
+        }
+    }
+    report("lookup", &found);
+}
+
+/**
+ * Writes an opening book of the moves played in the first `plies` plies of every game.
+ * The weight of a move is the number of points scored with it, counting 2 for a win and 1 for a draw
+ * as in Polyglot books, and its learn counter is the number of games it was played in.
+ * Moves played in positions symmetrical to each other count towards the same entry.
+ */
+fn build_book(records: &[GameRecord], path: &str, plies: usize) -> Result<(), String> {
+    let mut counts: HashMap<(u64, Move), BookEntry> = HashMap::new();
+    for record in records {
+        for (&(board, side), &(mv, _)) in positions(record).iter().zip(&record.moves).take(plies) {
+            let entry = Book::entry(board, side, mv, 0, 0);
+            let counted = counts.entry((entry.key, entry.mv)).or_insert(entry);
+            counted.weight = counted
+                .weight
+                .saturating_add(record.result.points(side) as u16);
+            counted.learn = counted.learn.saturating_add(1);
+        }
+    }
+    let book = Book::new(counts.into_values().collect());
+    write(path, book.to_bytes()).map_err(|error| format!("cannot write {}: {}", path, error))?;
+    println!("db book entries {}", book.entries().len());
+    Ok(())
+}
+
+/**
+ * Writes every position whose move was chosen by a search as training data, in one of the formats
+ * `csv`, `jsonl` (one object per line) or `binary` (fixed-size samples described in `dataset.rs`).
+ */
+fn export(records: &[GameRecord], format: &str, path: &str) -> Result<(), String> {
+    let samples = samples(records);
+    let bytes = match format {
+        "csv" => {
+            let mut lines = vec![Sample::csv_header().to_string()];
+            lines.extend(samples.iter().map(Sample::to_csv));
+            (lines.join("\n") + "\n").into_bytes()
+        }
+        "jsonl" => samples
+            .iter()
+            .map(|sample| sample.to_json().to_string() + "\n")
+            .collect::<String>()
+            .into_bytes(),
+        "binary" => samples.iter().flat_map(Sample::to_bytes).collect(),
+        _ => return Err(format!("unknown format {}", format)),
+    };
+    write(path, bytes).map_err(|error| format!("cannot write {}: {}", path, error))?;
+    println!("db export samples {}", samples.len());
+    Ok(())
+}
+
+/**
+ * Queries a file of game records, as written by `--out`, with `db stats <file>`,
+ * `db selfplay-stats <file>`, `db find <file> <position>` or `db lookup <file> <position>`,
+ * where the position is a position string, indexes it for the last two with `db index <file>`,
+ * turns it into an opening book with `db book <file> <book> [<plies>]`,
+ * into training data with `db export <file> <format> <output>`,
+ * or into a set of puzzles with `db puzzles <file> <output> [<depth>]`.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    match args {
+        [command, path] if command == "stats" => stats(&read_records(path)?),
+        [command, path] if command == "selfplay-stats" => selfplay_stats(&read_records(path)?),
+        [command, path] if command == "index" => build_index(path)?,
+        [command, path, position @ ..] if command == "find" && !position.is_empty() => {
+            let position = position.join(" ");
+            let (board, side) = board_from_string(&position)
+                .ok_or_else(|| format!("invalid position {}", position))?;
+            find(&games_reaching(path, board, side)?, board, side);
+        }
+        [command, path, position @ ..] if command == "lookup" && !position.is_empty() => {
+            let position = position.join(" ");
+            let (board, side) = board_from_string(&position)
+                .ok_or_else(|| format!("invalid position {}", position))?;
+            lookup(&games_reaching(path, board, side)?, board, side);
+        }
+        [command, path, book, plies @ ..] if command == "book" && plies.len() <= 1 => {
+            let plies = match plies.first() {
+                None => DEFAULT_BOOK_PLIES,
+                Some(plies) => plies
+                    .parse()
+                    .map_err(|_| format!("invalid plies {}", plies))?,
+            };
+            build_book(&read_records(path)?, book, plies)?;
+        }
+        [command, path, format, output] if command == "export" => {
+            export(&read_records(path)?, format, output)?;
+        }
+        [command, path, output, depth @ ..] if command == "puzzles" && depth.len() <= 1 => {
+            let depth = match depth.first() {
+                None => DEFAULT_PUZZLE_DEPTH,
+                Some(depth) => depth
+                    .parse()
+                    .ok()
+                    .filter(|depth| (2..=MAX_PLY).contains(depth))
+                    .ok_or_else(|| format!("invalid depth {}", depth))?,
+            };
+            write_puzzles(&read_records(path)?, output, depth)?;
+        }
+        _ => return Err(
+            "usage: db stats <file> | db selfplay-stats <file> | db find <file> <position> | db lookup <file> <position> | db index <file> | db book <file> <book> [<plies>] | db export <file> <format> <output> | db puzzles <file> <output> [<depth>]"
+                .to_string(),
+        ),
+    }
+    Ok(())
+}
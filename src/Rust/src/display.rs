@@ -0,0 +1,393 @@
+use crate::engine::*;
+use crate::{move_string, player_string, Notation, ZONE_ARRAY_LOWER, ZONE_ARRAY_UPPER};
+
+// The characters used to draw the borders of the grids.
+// The corners are given in left, middle and right order.
+struct Glyphs {
+    horizontal: &'static str,
+    vertical: &'static str,
+    top: [&'static str; 3],
+    inner: [&'static str; 3],
+    bottom: [&'static str; 3],
+}
+
+const ASCII: Glyphs = Glyphs {
+    horizontal: "-",
+    vertical: "|",
+    top: ["+", "+", "+"],
+    inner: ["+", "+", "+"],
+    bottom: ["+", "+", "+"],
+};
+
+const BOX_DRAWING: Glyphs = Glyphs {
+    horizontal: "─",
+    vertical: "│",
+    top: ["┌", "┬", "┐"],
+    inner: ["├", "┼", "┤"],
+    bottom: ["└", "┴", "┘"],
+};
+
+// ANSI escape sequences used when rendering in color.
+const RESET: &str = "\x1b[0m";
+const X_COLOR: &str = "\x1b[1;31m";
+const O_COLOR: &str = "\x1b[1;34m";
+const PLAYABLE_COLOR: &str = "\x1b[32m";
+const VACANT_COLOR: &str = "\x1b[2m";
+const LAST_MOVE_COLOR: &str = "\x1b[7m";
+
+// The width of a zone in the first grid, in characters, excluding its borders.
+const ZONE_WIDTH: usize = 7;
+
+// The possible contents of a cell or large grid square, determining how it is drawn.
+#[derive(Clone, Copy)]
+enum Mark {
+    X,
+    O,
+    Playable,
+    Vacant,
+}
+
+// Draws a mark, in color if required, and highlighted if it is the most recent move.
+fn mark_string(mark: Mark, color: bool, highlight: bool) -> String {
+    let (symbol, escape) = match mark {
+        Mark::X => ("X", X_COLOR),
+        Mark::O => ("O", O_COLOR),
+        Mark::Playable => ("*", PLAYABLE_COLOR),
+        Mark::Vacant => (".", VACANT_COLOR),
+    };
+    match (color, highlight) {
+        (false, _) => symbol.to_string(),
+        (true, false) => format!("{}{}{}", escape, symbol, RESET),
+        (true, true) => format!("{}{}{}{}", escape, LAST_MOVE_COLOR, symbol, RESET),
+    }
+}
+
+// Draws a horizontal border across the three zones of the first grid,
+// optionally with the names of the zones in the given row of zones centred in it.
+fn border(glyphs: &Glyphs, corners: [&str; 3], zone_row: Option<usize>) -> String {
+    let segments: Vec<_> = (0..3)
+        .map(|j| match zone_row {
+            Some(row) => {
+                let label = format!(" {} ", ZONE_ARRAY_LOWER[3 * row + j]);
+                let left = (ZONE_WIDTH - label.len()) / 2;
+                let right = ZONE_WIDTH - label.len() - left;
+                format!(
+                    "{}{}{}",
+                    glyphs.horizontal.repeat(left),
+                    label,
+                    glyphs.horizontal.repeat(right)
+                )
+            }
+            None => glyphs.horizontal.repeat(ZONE_WIDTH),
+        })
+        .collect();
+    format!(
+        "    {}{}{}",
+        corners[0],
+        segments.join(corners[1]),
+        corners[2]
+    )
+}
+
+/**
+ * Produces the lines of the rendered board with the given glyphs, coloring the marks when `color` is set.
+ * The last move (if not `NULL_MOVE`) is named below the grids, and with color, it is highlighted
+ * in the first grid, along with the zone it sends the opponent to in the large grid.
+ */
+fn render(board: Board, side: bool, last_move: Move, glyphs: &Glyphs, color: bool) -> Vec<String> {
+    let share = board.2;
+    let zone = (share >> 54) & 0b1111;
+    let legal_moves: Vec<Move> = generate_moves(board).collect();
+    // The zone the last move sent the opponent to, unless it let them play in any zone.
+    let sent_to = (last_move != NULL_MOVE && zone == last_move % 9).then_some(zone);
+
+    // Each cell is either occupied by one of the players, vacant and playable next, or vacant.
+    let cell = |mv: Move| {
+        let (x_cells, o_cells) = zone_cells(board, mv / 9);
+        if ((x_cells >> (mv % 9)) & 1) == 1 {
+            Mark::X
+        } else if ((o_cells >> (mv % 9)) & 1) == 1 {
+            Mark::O
+        } else if legal_moves.contains(&mv) {
+            Mark::Playable
+        } else {
+            Mark::Vacant
+        }
+    };
+
+    // Similar mapping for large grid, where the playable zones are those containing a legal move.
+    let large = |z: u64| {
+        if ((share >> (36 + z)) & 1) == 1 {
+            Mark::X
+        } else if ((share >> (45 + z)) & 1) == 1 {
+            Mark::O
+        } else if legal_moves.iter().any(|&mv| mv / 9 == z) {
+            Mark::Playable
+        } else {
+            Mark::Vacant
+        }
+    };
+
+    let mut lines = vec!["      1 2 3   4 5 6   7 8 9".to_string()];
+    for row in 0..9 {
+        // The border above each row of zones carries the names of those zones.
+        if row % 3 == 0 {
+            let corners = if row == 0 { glyphs.top } else { glyphs.inner };
+            lines.push(border(glyphs, corners, Some(row as usize / 3)));
+        }
+        let zones: Vec<_> = (0..3)
+            .map(|j| {
+                (0..3)
+                    .map(|k| {
+                        let mv = move_from_row_col(row, 3 * j + k);
+                        mark_string(cell(mv), color, mv == last_move)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        lines.push(format!(
+            "  {} {v} {} {v}",
+            row + 1,
+            zones.join(&format!(" {} ", glyphs.vertical)),
+            v = glyphs.vertical
+        ));
+    }
+    lines.push(border(glyphs, glyphs.bottom, None));
+    lines.push(String::new());
+
+    let large_border = |corners: [&str; 3]| {
+        format!(
+            "    {}{}{}",
+            corners[0],
+            glyphs.horizontal.repeat(ZONE_WIDTH),
+            corners[2]
+        )
+    };
+    lines.push(large_border(glyphs.top));
+    for i in (0..9).step_by(3) {
+        lines.push(format!(
+            "    {v} {} {v}",
+            (i..i + 3)
+                .map(|z| mark_string(large(z), color, sent_to == Some(z)))
+                .collect::<Vec<_>>()
+                .join(" "),
+            v = glyphs.vertical
+        ));
+    }
+    lines.push(large_border(glyphs.bottom));
+
+    lines.push(format!(
+        "ZONE: {}",
+        if zone == ZONE_ANY {
+            "ANY"
+        } else {
+            ZONE_ARRAY_UPPER[zone as usize]
+        }
+    ));
+    if last_move != NULL_MOVE {
+        lines.push(format!(
+            "LAST: {}/{}",
+            ZONE_ARRAY_UPPER[(last_move / 9) as usize],
+            ZONE_ARRAY_UPPER[(last_move % 9) as usize]
+        ));
+    }
+    lines.push(format!(
+        "TURN: {}",
+        mark_string(if side { Mark::O } else { Mark::X }, color, false)
+    ));
+    lines
+}
+
+// Used to output an ASCII art representation of the board,
+// with rows and columns numbered and each zone labelled with its name, and the last move named below.
+pub fn print_board(board: Board, side: bool, last_move: Move) {
+    for line in board_lines(board, side, last_move) {
+        println!("{}", line);
+    }
+}
+
+// The lines of the ASCII art representation, for output that does not go to standard output.
+pub fn board_lines(board: Board, side: bool, last_move: Move) -> Vec<String> {
+    render(board, side, last_move, &ASCII, false)
+}
+
+// Outputs the same representation as `print_board`, but drawn with box-drawing characters
+// and colored with ANSI escape sequences, with the most recent move and the zone it sends to highlighted.
+pub fn print_board_color(board: Board, side: bool, last_move: Move) {
+    for line in board_lines_color(board, side, last_move) {
+        println!("{}", line);
+    }
+}
+
+// The lines of the colored representation, for interfaces that draw the board alongside other output.
+pub fn board_lines_color(board: Board, side: bool, last_move: Move) -> Vec<String> {
+    render(board, side, last_move, &BOX_DRAWING, true)
+}
+
+/**
+ * Lists the differences between two positions, for spotting the change between two dumps of a board:
+ * each cell occupied differently as `<cell> <first> <second>`, with `x`, `o` or `.` for its occupant,
+ * followed by `zone <first> <second>` and `side <first> <second>` if those differ.
+ */
+pub fn board_differences(first: (Board, bool), second: (Board, bool)) -> Vec<String> {
+    let occupant = |board: Board, mv: Move| {
+        let (x_cells, o_cells) = zone_cells(board, mv / 9);
+        if (x_cells >> (mv % 9)) & 1 == 1 {
+            "x"
+        } else if (o_cells >> (mv % 9)) & 1 == 1 {
+            "o"
+        } else {
+            "."
+        }
+    };
+    let zone = |board: Board| match (board.2 >> 54) & 0b1111 {
+        ZONE_ANY => "any",
+        zone => ZONE_ARRAY_LOWER[zone as usize],
+    };
+    let ((board, side), (other, other_side)) = (first, second);
+    let mut differences: Vec<_> = (0..81)
+        .filter(|&mv| occupant(board, mv) != occupant(other, mv))
+        .map(|mv| {
+            format!(
+                "{} {} {}",
+                move_string(mv, Notation::Zone),
+                occupant(board, mv),
+                occupant(other, mv)
+            )
+        })
+        .collect();
+    if zone(board) != zone(other) {
+        differences.push(format!("zone {} {}", zone(board), zone(other)));
+    }
+    if side != other_side {
+        differences.push(format!(
+            "side {} {}",
+            player_string(side),
+            player_string(other_side)
+        ));
+    }
+    differences
+}
+
+// Dimensions of the SVG rendering, in pixels.
+const SVG_CELL: u64 = 40;
+const SVG_MARGIN: u64 = 20;
+const SVG_SIZE: u64 = 9 * SVG_CELL + 2 * SVG_MARGIN;
+
+// Draws an X or an O centred on the given point, with the given size and stroke width.
+fn svg_mark(cx: u64, cy: u64, size: u64, stroke: u64, is_o: bool) -> String {
+    let r = size * 3 / 10;
+    if is_o {
+        format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke=\"#1f4fbf\" stroke-width=\"{}\"/>",
+            cx, cy, r, stroke
+        )
+    } else {
+        format!(
+            "<path d=\"M{} {}L{} {}M{} {}L{} {}\" stroke=\"#c62828\" stroke-width=\"{}\" stroke-linecap=\"round\"/>",
+            cx - r,
+            cy - r,
+            cx + r,
+            cy + r,
+            cx + r,
+            cy - r,
+            cx - r,
+            cy + r,
+            stroke
+        )
+    }
+}
+
+/**
+ * Renders the board as an SVG document.
+ * The zones that can be played in next are shaded, won zones are covered by a large mark
+ * of the player that won them, and the most recent move (if not `NULL_MOVE`) is outlined.
+ */
+pub fn board_svg(board: Board, last_move: Move) -> String {
+    let share = board.2;
+    let legal_moves: Vec<Move> = generate_moves(board).collect();
+    let mut elements = vec![format!(
+        "<rect width=\"{0}\" height=\"{0}\" fill=\"#ffffff\"/>",
+        SVG_SIZE
+    )];
+
+    // The top left corner of a cell or zone, given its row and column.
+    let corner = |row: u64, col: u64, size: u64| (SVG_MARGIN + col * size, SVG_MARGIN + row * size);
+
+    // Shade the zones that can be played in next.
+    for z in (0..9).filter(|&z| legal_moves.iter().any(|&mv| mv / 9 == z)) {
+        let (x, y) = corner(z / 3, z % 3, 3 * SVG_CELL);
+        elements.push(format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"#fff4c2\"/>",
+            x,
+            y,
+            3 * SVG_CELL
+        ));
+    }
+
+    // Thin lines separate the cells, and thick lines separate the zones.
+    for i in 0..=9 {
+        let offset = SVG_MARGIN + i * SVG_CELL;
+        let width = if i % 3 == 0 { 3 } else { 1 };
+        elements.push(format!(
+            "<path d=\"M{0} {1}L{0} {2}M{1} {0}L{2} {0}\" stroke=\"#333333\" stroke-width=\"{3}\"/>",
+            offset,
+            SVG_MARGIN,
+            SVG_SIZE - SVG_MARGIN,
+            width
+        ));
+    }
+
+    for mv in 0..81 {
+        let (row, col) = move_to_row_col(mv);
+        let (x, y) = corner(row, col, SVG_CELL);
+        let (x_cells, o_cells) = zone_cells(board, mv / 9);
+        if ((x_cells | o_cells) >> (mv % 9)) & 1 == 1 {
+            let is_o = (o_cells >> (mv % 9)) & 1 == 1;
+            elements.push(svg_mark(
+                x + SVG_CELL / 2,
+                y + SVG_CELL / 2,
+                SVG_CELL,
+                3,
+                is_o,
+            ));
+        }
+        if mv == last_move {
+            elements.push(format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"none\" stroke=\"#2e7d32\" stroke-width=\"3\"/>",
+                x + 2,
+                y + 2,
+                SVG_CELL - 4
+            ));
+        }
+    }
+
+    // Won zones are dimmed and covered by a large mark of the winning player.
+    for z in 0..9 {
+        let x_won = (share >> (36 + z)) & 1 == 1;
+        let o_won = (share >> (45 + z)) & 1 == 1;
+        if x_won || o_won {
+            let (x, y) = corner(z / 3, z % 3, 3 * SVG_CELL);
+            elements.push(format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"#ffffff\" fill-opacity=\"0.6\"/>",
+                x + 2,
+                y + 2,
+                3 * SVG_CELL - 4
+            ));
+            elements.push(svg_mark(
+                x + 3 * SVG_CELL / 2,
+                y + 3 * SVG_CELL / 2,
+                3 * SVG_CELL,
+                9,
+                o_won,
+            ));
+        }
+    }
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\">\n{1}\n</svg>\n",
+        SVG_SIZE,
+        elements.join("\n")
+    )
+}
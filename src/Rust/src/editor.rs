@@ -0,0 +1,126 @@
+use crate::display::print_board;
+use crate::engine::*;
+use crate::{board_string, move_from_string, position_error_string, ZONE_ARRAY_LOWER};
+
+/**
+ * The state of the position editor, entered with the `edit` command.
+ * The position being edited is kept separate from the game history,
+ * which is only replaced once the edited position is committed with `done`.
+ */
+pub struct Editor {
+    board: Board,
+    side: bool,
+}
+
+// What the main loop should do after the editor has handled a command.
+pub enum EditorAction {
+    // Remain in the editor.
+    Continue,
+    // Leave the editor, starting a new game from the given position and side to move.
+    Commit(Board, bool),
+    // Leave the editor without changing the game.
+    Cancel,
+    // Abandon the edit and exit the program, as `q` does outside the editor.
+    Quit,
+}
+
+// Parses the name of a player as given in a position string.
+fn player_from_string(player: &str) -> Option<bool> {
+    match player {
+        "x" => Some(false),
+        "o" => Some(true),
+        _ => None,
+    }
+}
+
+impl Editor {
+    pub fn new(board: Board, side: bool) -> Self {
+        Self { board, side }
+    }
+
+    // Handles a single command given while in the editor.
+    // All responses from the editor begin with the `edit` keyword.
+    pub fn execute(&mut self, command: &[String]) -> EditorAction {
+        match &command[0] as &str {
+            "place" => {
+                if command.len() < 3 {
+                    println!("edit invalid args");
+                    return EditorAction::Continue;
+                }
+                match (
+                    player_from_string(&command[1]),
+                    move_from_string(&command[2]),
+                ) {
+                    (Some(player), Some(mv)) => {
+                        self.board = set_cell(self.board, mv, Some(player));
+                        println!("edit ok");
+                    }
+                    (None, _) => println!("edit invalid player"),
+                    (_, None) => println!("edit invalid cell"),
+                }
+            }
+            "remove" => {
+                if command.len() < 2 {
+                    println!("edit invalid args");
+                    return EditorAction::Continue;
+                }
+                if let Some(mv) = move_from_string(&command[1]) {
+                    self.board = set_cell(self.board, mv, None);
+                    println!("edit ok");
+                } else {
+                    println!("edit invalid cell");
+                }
+            }
+            "clear" => {
+                self.board = (0, 0, ZONE_ANY << 54);
+                self.side = false;
+                println!("edit ok");
+            }
+            "zone" => {
+                if command.len() < 2 {
+                    println!("edit invalid args");
+                    return EditorAction::Continue;
+                }
+                if let Some(z) = ZONE_ARRAY_LOWER.iter().position(|&z| z == command[1]) {
+                    self.board = set_zone(self.board, z as u64);
+                    println!("edit ok");
+                } else if command[1] == "any" {
+                    self.board = set_zone(self.board, ZONE_ANY);
+                    println!("edit ok");
+                } else {
+                    println!("edit invalid zone");
+                }
+            }
+            "side" => {
+                if command.len() < 2 {
+                    println!("edit invalid args");
+                    return EditorAction::Continue;
+                }
+                if let Some(player) = player_from_string(&command[1]) {
+                    self.side = player;
+                    println!("edit ok");
+                } else {
+                    println!("edit invalid player");
+                }
+            }
+            "d" => print_board(self.board, self.side, NULL_MOVE),
+            "gamepos" => println!("{}", board_string(self.board, self.side)),
+            // A zone that has been filled while editing no longer holds the player to it.
+            "done" => match validate_position(with_playable_zone(self.board), self.side) {
+                Ok(()) => {
+                    self.board = with_playable_zone(self.board);
+                    println!("edit done");
+                    return EditorAction::Commit(self.board, self.side);
+                }
+                Err(error) => println!("edit invalid {}", position_error_string(error)),
+            },
+            "cancel" => {
+                println!("edit cancelled");
+                return EditorAction::Cancel;
+            }
+            "q" | "quit" => return EditorAction::Quit,
+            _ => println!("edit badkeyword"),
+        }
+        EditorAction::Continue
+    }
+}
@@ -1,9 +1,21 @@
 // Used for initialising static lookup tables once upon first use.
 use std::sync::LazyLock;
+// Used for stopping a search from another thread.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 // Used for returning opaque iterator types in legal move generation.
 use auto_enums::auto_enum;
 
+// Used for checking whether a search has run out of time.
+use crate::clock::Instant;
+use crate::interrupt::interrupted;
+use crate::rules::{first_move, variant, won_zones, FirstMove, Variant, WonZones};
+use crate::stats::{self, Counter};
+use crate::timeman::TimeManager;
+use crate::tt::{Bound, TranspositionTable};
+use crate::zobrist;
+
 /**
  * The bitboard structure is represented here as a tuple of 3 `u64`s.
  * Though the elements do not have inherent names, all elements
@@ -23,6 +35,10 @@ const BIG_ONE_COUNT: i32 = 20;
 const SMALL_TWO_COUNT: i32 = 8;
 const SMALL_ONE_COUNT: i32 = 1;
 
+// The weight of each zone won more than the opponent under the majority variant,
+// where a game ending without a line of zones is won by the player with more zones.
+const MAJORITY_ZONE: i32 = 40;
+
 // Weights for positional scoring.
 const CENTRE: i32 = 9;
 const CORNER: i32 = 7;
@@ -30,7 +46,6 @@ const EDGE: i32 = 5;
 const SQ_BIG: i32 = 25;
 
 // Masks for use in changing bitboards.
-const LINE: u64 = 0b111;
 const CHUNK: u64 = 0b111111111;
 const DBLCHUNK: u64 = (CHUNK << 9) | CHUNK;
 const EXCLZONE: u64 = !(0b1111u64 << 54);
@@ -52,7 +67,17 @@ pub const NULL_MOVE: Move = 81;
 // storage in the stack against a flexible upper bound of total depth searchable.
 pub const MAX_PLY: usize = 32;
 
-/**
+// Converts a row and column of the 9x9 grid, both in the 0-8 range, to a move.
+pub fn move_from_row_col(row: u64, col: u64) -> Move {
+    9 * (3 * (row / 3) + col / 3) + 3 * (row % 3) + col % 3
+}
+
+// Converts a move to its row and column in the 9x9 grid, both in the 0-8 range.
+pub fn move_to_row_col(mv: Move) -> (u64, u64) {
+    (3 * (mv / 27) + (mv % 9) / 3, 3 * ((mv / 9) % 3) + mv % 3)
+}
+
+/*
  * Due to the potential unreadability of an if-block in an arithmetic expression,
  * the `toggle_shift` and `toggle_eval` functions provide functions to adjust
  * a number based on a `bool` flag.
@@ -78,55 +103,66 @@ const fn toggle_eval(side: bool, num: i32) -> i32 {
     }
 }
 
-/**
+/*
  * A grid is represented by the least significant 9 bits in a `u64`.
  * The lines in a grid are represented by the following combinations of zones:
  * NW-N-NE, W-C-E, SW-S-SE, NW-W-SW, N-C-S, NE-E-SE, NW-C-SE, NE-C-SW.
  */
 
-// Returns a 24-bit value where each set of 3 bits
-// represents the occupancy pattern of that line.
-// A 1 bit means that particular position in that line is occupied.
-#[inline]
-pub const fn lines(grid: u64) -> u64 {
-    0b_000_100_000_000_100_000_000_100 * (grid & 1)
-        + 0b_000_000_000_000_010_000_100_000 * ((grid >> 1) & 1)
-        + 0b_100_000_000_000_001_100_000_000 * ((grid >> 2) & 1)
-        + 0b_000_000_000_100_000_000_000_010 * ((grid >> 3) & 1)
-        + 0b_010_010_000_010_000_000_010_000 * ((grid >> 4) & 1)
-        + 0b_000_000_000_001_000_010_000_000 * ((grid >> 5) & 1)
-        + 0b_001_000_100_000_000_000_000_001 * ((grid >> 6) & 1)
-        + 0b_000_000_010_000_000_000_001_000 * ((grid >> 7) & 1)
-        + 0b_000_001_001_000_000_001_000_000 * ((grid >> 8) & 1)
-}
-
-// Returns an 8-bit value that represents
-// the occupancy status of each line in a 3x3 grid
-// where a 1 bit means that line is formed.
+// The cells making up each of the lines above, in the same order,
+// with bits 0 to 8 representing zones NW to SE.
+pub const LINE_MASKS: [u64; 8] = [
+    0b_000_000_111,
+    0b_000_111_000,
+    0b_111_000_000,
+    0b_001_001_001,
+    0b_010_010_010,
+    0b_100_100_100,
+    0b_100_010_001,
+    0b_001_010_100,
+];
+
+// Returns whether any line in a 3x3 grid is fully occupied.
+// Only the least significant 9 bits of `grid` are considered.
+// The comparisons against each line mask are combined without branching.
 #[inline]
 pub const fn line_presence(grid: u64) -> bool {
-    0 != ((0b10110110 | ((grid & 1) * 0xff))
-        & (0b11101110 | (((grid >> 1) & 1) * 0xff))
-        & (0b01011110 | (((grid >> 2) & 1) * 0xff))
-        & (0b11110101 | (((grid >> 3) & 1) * 0xff))
-        & (0b00101101 | (((grid >> 4) & 1) * 0xff))
-        & (0b11011101 | (((grid >> 5) & 1) * 0xff))
-        & (0b01110011 | (((grid >> 6) & 1) * 0xff))
-        & (0b11101011 | (((grid >> 7) & 1) * 0xff))
-        & (0b10011011 | (((grid >> 8) & 1) * 0xff)))
+    let mut formed = false;
+    let mut i = 0;
+    while i < LINE_MASKS.len() {
+        formed |= grid & LINE_MASKS[i] == LINE_MASKS[i];
+        i += 1;
+    }
+    formed
 }
 
+// Flags describing the state of a 3x3 grid, as stored in the third table from `init()`.
+pub const SUB_X_WON: u16 = 1;
+pub const SUB_O_WON: u16 = 2;
+pub const SUB_FULL: u16 = 4;
+// No line can be completed by either player any more, so the grid can no longer be won.
+pub const SUB_BLOCKED: u16 = 8;
+// Bits 4-7 of the entry hold the number of lines Player X could still complete (those without an O),
+// and bits 8-11 the number of lines Player O could still complete.
+pub const SUB_X_OPEN_SHIFT: u16 = 4;
+pub const SUB_O_OPEN_SHIFT: u16 = 8;
+// The player has two cells of a line whose third cell is empty, so they can win the grid with one move.
+pub const SUB_X_THREAT: u16 = 1 << 12;
+pub const SUB_O_THREAT: u16 = 1 << 13;
+
 // The lookup tables required for the evaluation of a position
 // will be stored in this static variable.
-static TABLES: LazyLock<(Vec<i32>, Vec<i32>)> = LazyLock::new(|| init());
+static TABLES: LazyLock<(Vec<i32>, Vec<i32>, Vec<u16>)> = LazyLock::new(init);
 
 /**
  * This function is to be executed at the very start, and only once,
  * to populate the lookup tables to be used in the heuristic evaluation.
- * Since each of the two lookup tables contain 262144 `i32` values,
- * Vec<i32> is returned instead of an array, to avoid stack overflow.
+ * Since each of the lookup tables contain 262144 values,
+ * a `Vec` is returned instead of an array, to avoid stack overflow.
+ * The third table holds the `SUB_` flags and open line counts of every grid,
+ * so that wins and filled grids can be found with a single lookup.
  */
-pub fn init() -> (Vec<i32>, Vec<i32>) {
+pub fn init() -> (Vec<i32>, Vec<i32>, Vec<u16>) {
     // These lookup tables store evaluations for different arrangements of grids,
     // for both small and large grid metrics.
     // These tables will essentially store partial heuristic evaluations
@@ -138,35 +174,55 @@ pub fn init() -> (Vec<i32>, Vec<i32>) {
 
     let mut eval_table_large: Vec<i32> = vec![0; 262144];
     let mut eval_table_small: Vec<i32> = vec![0; 262144];
-
-    // For each integer from 0 to 511, record the number of 1 bits it has.
-    let pop_count: Vec<i32> = (0..512)
-        .map(|i| (0..9).fold(0, |acc, j| acc + ((i >> j) & 1)))
-        .collect();
+    let mut info_table: Vec<u16> = vec![0; 262144];
 
     // We test all the possible arrangements, which is where
     // `us` and `them` each take a value from 0 to 511 each.
     for us in (0..512).map(|us| us as u64) {
         for them in (0..512).map(|them| them as u64) {
+            // Lines containing none of the opponent's cells can still be completed.
+            let x_open = LINE_MASKS.iter().filter(|&&mask| them & mask == 0).count() as u16;
+            let o_open = LINE_MASKS.iter().filter(|&&mask| us & mask == 0).count() as u16;
+            info_table[((them << 9) | us) as usize] = (x_open << SUB_X_OPEN_SHIFT)
+                | (o_open << SUB_O_OPEN_SHIFT)
+                | if line_presence(us) { SUB_X_WON } else { 0 }
+                | if line_presence(them) { SUB_O_WON } else { 0 }
+                | if (us | them) == CHUNK { SUB_FULL } else { 0 }
+                | if x_open == 0 && o_open == 0 {
+                    SUB_BLOCKED
+                } else {
+                    0
+                }
+                | if LINE_MASKS
+                    .iter()
+                    .any(|&mask| (us & mask).count_ones() == 2 && them & mask == 0)
+                {
+                    SUB_X_THREAT
+                } else {
+                    0
+                }
+                | if LINE_MASKS
+                    .iter()
+                    .any(|&mask| (them & mask).count_ones() == 2 && us & mask == 0)
+                {
+                    SUB_O_THREAT
+                } else {
+                    0
+                };
+
             // These evaluation values will be incrementally updated.
             let mut eval_large: i32 = 0;
             let mut eval_small: i32 = 0;
 
-            // Retrieve the lines that each side makes as a bit array,
-            // allowing the number of occupancies in each line to be found.
-            let us_lines = lines(us);
-            let them_lines = lines(them);
-
             // Early escape boolean flags, since no more evaluation is needed
             // if one particular side has made a 3-in-a-row.
             let mut us_won: bool = false;
             let mut them_won: bool = false;
 
-            // We process the bits returned from `lines` in groups of 3.
-            for i in (0..24).step_by(3) {
+            for mask in LINE_MASKS {
                 // Count how many cells each side occupies in this line.
-                let us_count = pop_count[((us_lines >> i) & LINE) as usize];
-                let them_count = pop_count[((them_lines >> i) & LINE) as usize];
+                let us_count = (us & mask).count_ones();
+                let them_count = (them & mask).count_ones();
 
                 // If both sides already occupy a place in this line,
                 // this line is no longer winnable for either side.
@@ -207,15 +263,10 @@ pub fn init() -> (Vec<i32>, Vec<i32>) {
             }
 
             // Add on scores for occupancies in certain positions.
-            let eval_pos = CORNER
-                * (pop_count[(us & CORNER_MASK) as usize]
-                    - pop_count[(them & CORNER_MASK) as usize])
-                + EDGE
-                    * (pop_count[(us & EDGE_MASK) as usize]
-                        - pop_count[(them & EDGE_MASK) as usize])
-                + CENTRE
-                    * (pop_count[(us & CENTRE_MASK) as usize]
-                        - pop_count[(them & CENTRE_MASK) as usize]);
+            let count = |grid: u64, mask: u64| (grid & mask).count_ones() as i32;
+            let eval_pos = CORNER * (count(us, CORNER_MASK) - count(them, CORNER_MASK))
+                + EDGE * (count(us, EDGE_MASK) - count(them, EDGE_MASK))
+                + CENTRE * (count(us, CENTRE_MASK) - count(them, CENTRE_MASK));
 
             // Update large table with evaluation if a decisive result is reached,
             // otherwise update both small and large table with suitable heuristics.
@@ -223,7 +274,7 @@ pub fn init() -> (Vec<i32>, Vec<i32>) {
                 eval_table_large[((them << 9) | us) as usize] = OUTCOME_WIN;
             } else if them_won {
                 eval_table_large[((them << 9) | us) as usize] = OUTCOME_LOSS;
-            } else if pop_count[(us | them) as usize] == 9 {
+            } else if (us | them).count_ones() == 9 {
                 eval_table_large[((them << 9) | us) as usize] = OUTCOME_DRAW;
             } else {
                 eval_table_large[((them << 9) | us) as usize] = eval_large + eval_pos * SQ_BIG;
@@ -233,7 +284,104 @@ pub fn init() -> (Vec<i32>, Vec<i32>) {
     }
 
     // Implicit return.
-    (eval_table_large, eval_table_small)
+    (eval_table_large, eval_table_small, info_table)
+}
+
+// Returns the `SUB_` flags and open line counts of a 3x3 grid,
+// given the cells occupied by each player in its least significant 9 bits.
+#[inline]
+pub fn grid_info(x_cells: u64, o_cells: u64) -> u16 {
+    TABLES.2[(((o_cells & CHUNK) << 9) | (x_cells & CHUNK)) as usize]
+}
+
+// Returns the evaluation of the large grid for Player X from the lookup table,
+// which is reversed under the misère variant, where forming a line of zones loses.
+#[inline]
+fn large_grid_eval(share: u64) -> i32 {
+    let eval = TABLES.0[((share >> 36) & DBLCHUNK) as usize];
+    if variant() == Variant::Misere {
+        -eval
+    } else {
+        eval
+    }
+}
+
+/**
+ * Returns the player who has won the game, if either has: `false` for Player X and `true` for Player O.
+ * This is the player who formed a line of zones, or the other player under the misère variant.
+ */
+pub fn winner(board: Board) -> Option<bool> {
+    let grid = board.2 >> 36;
+    let misere = variant() == Variant::Misere;
+    if line_presence(grid & CHUNK) {
+        Some(misere)
+    } else if line_presence((grid >> 9) & CHUNK) {
+        Some(!misere)
+    } else {
+        None
+    }
+}
+
+/**
+ * Returns the result of a game that has ended without a line of zones, where `None` is a draw.
+ * This is always a draw, except under the majority variant, where the player who won more zones wins.
+ */
+pub fn full_grid_winner(board: Board) -> Option<bool> {
+    let share = board.2;
+    let (x_zones, o_zones) = (
+        ((share >> 36) & CHUNK).count_ones(),
+        ((share >> 45) & CHUNK).count_ones(),
+    );
+    if variant() != Variant::Majority || x_zones == o_zones {
+        None
+    } else {
+        Some(o_zones > x_zones)
+    }
+}
+
+// Returns the evaluation for Player X of a game that has ended without a line of zones.
+#[inline]
+fn full_grid_eval(board: Board) -> i32 {
+    match full_grid_winner(board) {
+        None => OUTCOME_DRAW,
+        Some(false) => OUTCOME_WIN,
+        Some(true) => OUTCOME_LOSS,
+    }
+}
+
+// Returns the zones that cannot be played in because they have been won, under the rules for won zones,
+// as a 9-bit mask. Zones that are completely filled need no mask, since they have no empty cells.
+#[inline]
+fn closed_zones(share: u64) -> u64 {
+    if won_zones() == WonZones::Closed {
+        ((share >> 36) | (share >> 45)) & CHUNK
+    } else {
+        0
+    }
+}
+
+// Returns whether being sent to a zone that has been won lets the player play in any zone instead.
+#[inline]
+pub fn won_zone_sends_anywhere() -> bool {
+    won_zones() != WonZones::Open
+}
+
+/**
+ * Returns the cells the first move of a game may be made in under the first-move rule, as an 81-bit mask,
+ * or `None` if the board is not the empty board with any zone to play in, or the rule allows every cell.
+ * A game is only restricted at its true start, so positions set up with a zone to play in are not.
+ */
+#[inline]
+fn first_move_cells(board: Board) -> Option<u128> {
+    if board != (0, 0, ZONE_ANY << 54) {
+        return None;
+    }
+    let all = (1 << 81) - 1;
+    match first_move() {
+        FirstMove::Any => None,
+        FirstMove::NoCenter => Some(all & !(1 << 40)),
+        FirstMove::Zone(zone) => Some((CHUNK as u128) << (9 * zone)),
+    }
 }
 
 /**
@@ -249,10 +397,14 @@ pub fn init() -> (Vec<i32>, Vec<i32>) {
 pub fn generate_moves(board: Board) -> impl Iterator<Item = Move> {
     let (us, them, share) = board;
 
-    if line_presence(share >> 36) || line_presence(share >> 45) {
+    if grid_info(share >> 36, share >> 45) & (SUB_X_WON | SUB_O_WON) != 0 {
         return std::iter::empty();
     }
 
+    if let Some(cells) = first_move_cells(board) {
+        return (0..81).filter(move |i| (cells >> i) & 1 == 1);
+    }
+
     // Extract the zone to be played from the board.
     let zone = (share >> 54) & 0b1111;
 
@@ -260,11 +412,11 @@ pub fn generate_moves(board: Board) -> impl Iterator<Item = Move> {
     // hence we avoid using `map`, instead only using `filter` and `chain`.
     match zone {
         // If the player is allowed to play in any zone they wish, select all blank squares
-        // that are not in a zone that has a corresponding occupied large grid.
+        // that are not in a zone that is closed by a corresponding occupied large grid.
         ZONE_ANY => {
             let nw_to_sw = us | them;
             let s_to_se = (share >> 18) | share;
-            let large = (share >> 36) | (share >> 45);
+            let large = closed_zones(share);
 
             (0..63)
                 .filter(move |i| ((nw_to_sw >> i) & 1) == 0 && ((large >> (i / 9)) & 1) == 0)
@@ -287,6 +439,243 @@ pub fn generate_moves(board: Board) -> impl Iterator<Item = Move> {
     }
 }
 
+/**
+ * Infers the side to move from the number of cells each player occupies,
+ * for positions that are given without any game history.
+ * Player X always moves first, so equal counts mean Player X (`false`) is to move.
+ */
+pub fn infer_side(board: Board) -> bool {
+    let (us, them, share) = board;
+    let x_count = us.count_ones() + (share & DBLCHUNK).count_ones();
+    let o_count = them.count_ones() + ((share >> 18) & DBLCHUNK).count_ones();
+    x_count > o_count
+}
+
+// Returns the cells occupied by Player X and Player O within the given zone,
+// each as a 9-bit grid.
+pub fn zone_cells(board: Board, zone: u64) -> (u64, u64) {
+    let (us, them, share) = board;
+    if zone > 6 {
+        (
+            (share >> (9 * zone - 63)) & CHUNK,
+            (share >> (9 * zone - 45)) & CHUNK,
+        )
+    } else {
+        ((us >> (9 * zone)) & CHUNK, (them >> (9 * zone)) & CHUNK)
+    }
+}
+
+// Returns the board with the large grid occupancies recalculated from the cells of each zone,
+// for boards that are constructed directly rather than by playing moves.
+// When the rules leave won zones open, both players can have a line in a zone, which belongs to whoever formed theirs first.
+// The cells cannot tell who that was, so the owner the board already has is kept, with Player X taken if it has none.
+pub fn with_large_grid(board: Board) -> Board {
+    let (us, them, mut share) = board;
+    share &= !(DBLCHUNK << 36);
+    for zone in 0..9 {
+        let (x_cells, o_cells) = zone_cells(board, zone);
+        let won = grid_info(x_cells, o_cells) & (SUB_X_WON | SUB_O_WON);
+        let o_owns = match won {
+            SUB_X_WON => false,
+            SUB_O_WON => true,
+            0 => continue,
+            _ => (board.2 >> (45 + zone)) & 1 == 1,
+        };
+        share |= 1 << (36 + zone + toggle_shift(o_owns, 9));
+    }
+    (us, them, share)
+}
+
+// Returns the board with a cell emptied (`None`) or occupied by the given side,
+// with the large grid updated to match.
+// Unlike `play_move`, the zone to be played in is left unchanged.
+pub fn set_cell(board: Board, mv: Move, occupant: Option<bool>) -> Board {
+    let (mut us, mut them, mut share) = board;
+    if mv > 62 {
+        share &= !((1 << (mv - 63)) | (1 << (mv - 45)));
+        if let Some(side) = occupant {
+            share |= 1 << (mv - 63 + toggle_shift(side, 18));
+        }
+    } else {
+        us &= !(1 << mv);
+        them &= !(1 << mv);
+        match occupant {
+            Some(false) => us |= 1 << mv,
+            Some(true) => them |= 1 << mv,
+            None => {}
+        }
+    }
+    with_large_grid((us, them, share))
+}
+
+// Returns the board with a zone to be played in that is completely filled replaced by any zone,
+// as `play_move` does for a move sending the opponent there, for boards that are constructed directly.
+pub fn with_playable_zone(board: Board) -> Board {
+    let zone = (board.2 >> 54) & 0b1111;
+    if zone < ZONE_ANY {
+        let (x_cells, o_cells) = zone_cells(board, zone);
+        if (x_cells | o_cells) == CHUNK {
+            return set_zone(board, ZONE_ANY);
+        }
+    }
+    board
+}
+
+// Returns the board with the zone to be played in replaced.
+pub fn set_zone(board: Board, zone: u64) -> Board {
+    let (us, them, share) = board;
+    (us, them, (share & EXCLZONE) | (zone << 54))
+}
+
+// The reasons a board can be rejected by `validate_position`.
+pub enum PositionError {
+    // The number of cells each player occupies does not fit the side to move.
+    PieceCount,
+    // Both players have formed a line in the given zone.
+    SubBoard(u64),
+    // Both players have formed a line in the large grid.
+    BothWon,
+    // The zone to be played in is already won or completely filled.
+    ClosedZone,
+}
+
+/**
+ * Checks that a board, with the given side to move, could have been reached by a legal sequence of moves
+ * from the empty board, or from the start of a handicap game.
+ * This is used for positions given externally, since the functions in this module
+ * assume they are only ever given valid positions.
+ * Not every unreachable position is detected, but any position that passes
+ * will not cause the search to produce meaningless results.
+ */
+pub fn validate_position(board: Board, side: bool) -> Result<(), PositionError> {
+    let (us, them, share) = board;
+
+    // Player X moves first, so Player X occupies as many cells as Player O with Player X to move,
+    // and one more with Player O to move. Any other difference must be a handicap of at most `MAX_HANDICAP` cells,
+    // which the player given it occupies: `-extra` cells of Player O, or `extra + 1` cells of Player X,
+    // since the first of those takes the place of a first move. A game swapped under the pie rule,
+    // where Player O took over the first move, is counted as a handicap of one cell for Player O.
+    let x_count = us.count_ones() + (share & DBLCHUNK).count_ones();
+    let o_count = them.count_ones() + ((share >> 18) & DBLCHUNK).count_ones();
+    let extra = x_count as i32 - o_count as i32 - side as i32;
+    let (handicap, occupied) = if extra < 0 {
+        (extra.unsigned_abs(), o_count)
+    } else {
+        (extra as u32 + 1, x_count)
+    };
+    if extra != 0 && (handicap > occupied || handicap as usize > MAX_HANDICAP) {
+        return Err(PositionError::PieceCount);
+    }
+
+    // No more moves can be made in a zone once a line has been formed in it,
+    // so only one of the players can have a line there, unless the rules leave won zones open.
+    if won_zones() == WonZones::Closed {
+        for zone in 0..9 {
+            let (x_cells, o_cells) = zone_cells(board, zone);
+            if grid_info(x_cells, o_cells) & (SUB_X_WON | SUB_O_WON) == SUB_X_WON | SUB_O_WON {
+                return Err(PositionError::SubBoard(zone));
+            }
+        }
+    }
+
+    let large = grid_info(share >> 36, share >> 45);
+    if large & (SUB_X_WON | SUB_O_WON) == SUB_X_WON | SUB_O_WON {
+        return Err(PositionError::BothWon);
+    }
+
+    // Once the game is over, the zone no longer matters.
+    let zone = (share >> 54) & 0b1111;
+    if large & (SUB_X_WON | SUB_O_WON) == 0 && zone != ZONE_ANY {
+        let (x_cells, o_cells) = zone_cells(board, zone);
+        if ((share >> (36 + zone)) | (share >> (45 + zone))) & 1 == 1 && won_zone_sends_anywhere()
+            || grid_info(x_cells, o_cells) & SUB_FULL != 0
+        {
+            return Err(PositionError::ClosedZone);
+        }
+    }
+
+    Ok(())
+}
+
+// The most cells a player can be given as a handicap.
+pub const MAX_HANDICAP: usize = 4;
+
+// The reasons a handicap can be rejected by `handicap_board`.
+pub enum HandicapError {
+    // No cells, or more than `MAX_HANDICAP`, were given.
+    Count,
+    // The given cell was given more than once.
+    Occupied(Move),
+    // The cells given form a line in the given zone.
+    Line(u64),
+}
+
+/**
+ * Returns the empty board with the given cells already occupied by one side, the weaker player of a handicap game,
+ * and the other side to move in any zone.
+ * The cells may not win a zone between them, so the board passes `validate_position`.
+ */
+pub fn handicap_board(side: bool, cells: &[Move]) -> Result<Board, HandicapError> {
+    if cells.is_empty() || cells.len() > MAX_HANDICAP {
+        return Err(HandicapError::Count);
+    }
+    let mut board = (0, 0, ZONE_ANY << 54);
+    for &mv in cells {
+        let (x_cells, o_cells) = zone_cells(board, mv / 9);
+        if ((x_cells | o_cells) >> (mv % 9)) & 1 == 1 {
+            return Err(HandicapError::Occupied(mv));
+        }
+        board = set_cell(board, mv, Some(side));
+        if ((board.2 | (board.2 >> 9)) >> (36 + mv / 9)) & 1 == 1 {
+            return Err(HandicapError::Line(mv / 9));
+        }
+    }
+    Ok(board)
+}
+
+// The reasons a move can be rejected by `check_move`.
+pub enum IllegalMove {
+    // The game is already over, so no moves can be made.
+    GameOver,
+    // The move is outside the zone that must be played in, which is given.
+    WrongZone(u64),
+    // The move is in a zone that has already been won.
+    ClosedZone,
+    // The cell is already occupied.
+    Occupied,
+    // The move is the first of the game, and the first-move rule does not allow it.
+    FirstMove,
+}
+
+/**
+ * Checks whether a move is legal, and if not, gives the reason it is illegal.
+ * A move passes this check exactly when it would be produced by `generate_moves`.
+ */
+pub fn check_move(board: Board, mv: Move) -> Result<(), IllegalMove> {
+    let share = board.2;
+    if generate_moves(board).next().is_none() {
+        return Err(IllegalMove::GameOver);
+    }
+    if first_move_cells(board).is_some_and(|cells| (cells >> mv) & 1 == 0) {
+        return Err(IllegalMove::FirstMove);
+    }
+
+    let zone = (share >> 54) & 0b1111;
+    if zone != ZONE_ANY && mv / 9 != zone {
+        return Err(IllegalMove::WrongZone(zone));
+    }
+    if (closed_zones(share) >> (mv / 9)) & 1 == 1 {
+        return Err(IllegalMove::ClosedZone);
+    }
+
+    let (x_cells, o_cells) = zone_cells(board, mv / 9);
+    if ((x_cells | o_cells) >> (mv % 9)) & 1 == 1 {
+        return Err(IllegalMove::Occupied);
+    }
+
+    Ok(())
+}
+
 /**
  * For a given move played by a given player, returs the new board state.
  * Since Board is a tuple of primitive types, copies should be cheap enough,
@@ -297,28 +686,31 @@ pub fn play_move(board: Board, mv: Move, side: bool) -> Board {
     // so we create mutable copies of the `u64` components of the board.
     let (mut us, mut them, mut share) = board;
 
-    // `line_occupancy` stores whether a line within the relevant zone is formed by us
-    // after this move is made.
+    // `own_cells` stores our occupancies of the relevant zone after this move is made.
 
-    let line_occupancy = if mv > 62 {
+    let own_cells = if mv > 62 {
         share |= 1 << (mv - 63 + toggle_shift(side, 18));
 
         // implicit return in block
-        line_presence(share >> (9 * (mv / 9) - 63 + toggle_shift(side, 18)))
+        share >> (9 * (mv / 9) - 63 + toggle_shift(side, 18))
     } else if !side {
         us |= 1 << mv;
 
         // implicit return in block
-        line_presence(us >> (9 * (mv / 9)))
+        us >> (9 * (mv / 9))
     } else {
         them |= 1 << mv;
 
         // implicit return in block
-        line_presence(them >> (9 * (mv / 9)))
+        them >> (9 * (mv / 9))
     };
 
     // If this move forms a line in our zone, occupy the corresponding large grid.
-    if line_occupancy {
+    // Only our own cells are looked up, as they alone decide whether we have a line.
+    // A zone that has already been won, which can only be played in under some rules, stays with its winner.
+    if grid_info(own_cells, 0) & SUB_X_WON != 0
+        && ((share | (share >> 9)) >> (36 + mv / 9)) & 1 == 0
+    {
         share |= 1 << (36 + toggle_shift(side, 9) + mv / 9);
     }
 
@@ -334,10 +726,13 @@ pub fn play_move(board: Board, mv: Move, side: bool) -> Board {
     };
 
     // The next player is allowed to play in any zone if either:
-    // the zone indicated by the most recent move corresponds to a large grid that is won,
+    // the zone indicated by the most recent move corresponds to a large grid that is won
+    // (unless the rules for won zones send them there regardless),
     // or the zone is completely filled with zero vacant cells.
 
-    let zone = if next_chunk == CHUNK || (((share | (share >> 9)) >> (36 + mv % 9)) & 1) == 1 {
+    let zone = if next_chunk == CHUNK
+        || ((((share | (share >> 9)) >> (36 + mv % 9)) & 1) == 1 && won_zone_sends_anywhere())
+    {
         ZONE_ANY
     } else {
         mv % 9
@@ -356,7 +751,7 @@ pub fn evaluate(board: Board, side: bool) -> i32 {
     let (us, them, share) = board;
 
     // First, check the evaluation of the large grid.
-    let eval = (*TABLES).0[((share >> 36) & DBLCHUNK) as usize];
+    let eval = large_grid_eval(share);
 
     // If the large grid has reached a decisive result, the game is over,
     // with either a win or loss depending on the side currently evaluating this position.
@@ -365,12 +760,21 @@ pub fn evaluate(board: Board, side: bool) -> i32 {
     }
 
     // If the large grid does not have a won line,
-    // but is completely filled, the game is a draw.
+    // but is completely filled, the game is a draw (or is won by the majority of zones).
     let large = ((share >> 36) | (share >> 45)) & CHUNK;
     if large == CHUNK {
-        return OUTCOME_DRAW;
+        return toggle_eval(side, full_grid_eval(board));
     }
 
+    // Under the majority variant, every zone won counts towards the result if no line of zones is formed.
+    let eval = if variant() == Variant::Majority {
+        eval + MAJORITY_ZONE
+            * (((share >> 36) & CHUNK).count_ones() as i32
+                - ((share >> 45) & CHUNK).count_ones() as i32)
+    } else {
+        eval
+    };
+
     // Due to the different components that the zones NW to SW and S to SE are stored,
     // we once again chain two iterators together to prevent having to check
     // the condition each time.
@@ -391,7 +795,8 @@ pub fn evaluate(board: Board, side: bool) -> i32 {
                     0
                 } else {
                     // Incrementally add the precomputed evaluation of the small grid.
-                    (*TABLES).1[((them_data << 9) | us_data) as usize] }
+                    TABLES.1[((them_data << 9) | us_data) as usize]
+                }
             })
             .chain((7..9).map(|i| {
                 let us_data = (share >> (9 * i - 63)) & CHUNK;
@@ -400,7 +805,7 @@ pub fn evaluate(board: Board, side: bool) -> i32 {
                 if ((large >> i) & 1) == 1 || (us_data | them_data) == CHUNK {
                     0
                 } else {
-                    (*TABLES).1[((them_data << 9) | us_data) as usize]
+                    TABLES.1[((them_data << 9) | us_data) as usize]
                 }
             }))
             .fold(eval, |acc, x| acc + x),
@@ -409,6 +814,427 @@ pub fn evaluate(board: Board, side: bool) -> i32 {
     // The above implicit returns.
 }
 
+// Returns the empty cells of a 3x3 grid that would complete a line for the player occupying `own`.
+#[inline]
+fn winning_cells(own: u64, other: u64) -> u64 {
+    LINE_MASKS
+        .iter()
+        .filter(|&&mask| (own & mask).count_ones() == 2 && other & mask == 0)
+        .fold(0, |cells, &mask| cells | (mask & !own))
+}
+
+/**
+ * Returns whether a move by `side` that sends the opponent to `zone` is safe: the opponent must play there,
+ * and cannot immediately win it. A zone that is won or full lets the opponent play anywhere, so it is never safe.
+ * `played` is the cell the move itself occupies in `zone`, which is only nonzero for a move within that zone.
+ */
+#[inline]
+fn is_safe_destination(board: Board, zone: u64, side: bool, played: u64) -> bool {
+    if ((board.2 >> (36 + zone)) | (board.2 >> (45 + zone))) & 1 == 1 {
+        return false;
+    }
+    let (mut x_cells, mut o_cells) = zone_cells(board, zone);
+    if side {
+        o_cells |= played;
+    } else {
+        x_cells |= played;
+    }
+    let opponent_threat = if side { SUB_X_THREAT } else { SUB_O_THREAT };
+    grid_info(x_cells, o_cells) & (SUB_X_WON | SUB_O_WON | SUB_FULL | opponent_threat) == 0
+}
+
+// Returns whether playing the move wins its zone for the given side.
+#[inline]
+fn wins_zone(board: Board, mv: Move, side: bool) -> bool {
+    let (x_cells, o_cells) = zone_cells(board, mv / 9);
+    let own_cells = if side { o_cells } else { x_cells };
+    grid_info(own_cells | (1 << (mv % 9)), 0) & SUB_X_WON != 0
+}
+
+// The stages of `MovePicker`, in the order their moves are produced.
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    HashMove,
+    Winning,
+    Killers,
+    ZoneSafe,
+    Rest,
+    Done,
+}
+
+/**
+ * Produces the legal moves of a position in stages, each likely to contain better moves than the next:
+ * the hash move, then moves that win their zone, then the killer moves of the ply,
+ * then moves that send the opponent to a zone they cannot immediately win, and then all other moves.
+ * Each stage is only generated once the stage before it is exhausted,
+ * so a node that cuts off early never generates the later stages at all.
+ * Within a stage, moves are produced in move generation order.
+ * The moves of each stage are kept in the buffer of the ply in the `SearchStack`,
+ * so that the picker itself stays small.
+ */
+pub struct MovePicker {
+    board: Board,
+    side: bool,
+    ply: usize,
+    hash_move: Move,
+    // The killer moves that are legal here and were not already produced as winning moves.
+    killers: [Move; KILLER_SLOTS],
+    stage: Stage,
+    // The zones that can be played in, as a 9-bit mask, and the cells allowed by the first-move rule, as an 81-bit mask.
+    zones: u64,
+    first_move: u128,
+    // The squares whose zones are safe to send the opponent to, found when the zone-safe stage begins.
+    safe_squares: u64,
+    length: usize,
+    next: usize,
+}
+
+impl MovePicker {
+    // `hash_move` is produced first if it is legal, and may be `NULL_MOVE` if there is none.
+    pub fn new(board: Board, side: bool, ply: usize, hash_move: Move) -> Self {
+        let share = board.2;
+        let zone = (share >> 54) & 0b1111;
+        let first_move = first_move_cells(board);
+        let zones = if grid_info(share >> 36, share >> 45) & (SUB_X_WON | SUB_O_WON) != 0 {
+            0
+        } else if let Some(cells) = first_move {
+            (0..9)
+                .filter(|&zone| (cells >> (9 * zone)) as u64 & CHUNK != 0)
+                .fold(0, |zones, zone| zones | (1 << zone))
+        } else if zone == ZONE_ANY {
+            !closed_zones(share) & CHUNK
+        } else {
+            1 << zone
+        };
+
+        // An illegal hash move is dropped, so that the later stages do not skip over it either.
+        let hash_move = if hash_move != NULL_MOVE && check_move(board, hash_move).is_ok() {
+            hash_move
+        } else {
+            NULL_MOVE
+        };
+        Self {
+            board,
+            side,
+            ply,
+            hash_move,
+            killers: [NULL_MOVE; KILLER_SLOTS],
+            stage: Stage::HashMove,
+            zones,
+            first_move: first_move.unwrap_or(!0),
+            safe_squares: 0,
+            // The hash move stage needs no buffer, containing only the hash move itself.
+            length: (hash_move != NULL_MOVE) as usize,
+            next: 0,
+        }
+    }
+
+    // Fills the buffer with the moves of the current stage, other than those produced by earlier stages.
+    fn generate_stage(&mut self, stack: &mut SearchStack) {
+        let (board, side) = (self.board, self.side);
+        let moves = &mut stack.moves[self.ply];
+        self.length = 0;
+        self.next = 0;
+        if self.stage == Stage::Killers {
+            for killer in stack.killers[self.ply] {
+                if killer != NULL_MOVE
+                    && killer != self.hash_move
+                    && check_move(board, killer).is_ok()
+                    && !wins_zone(board, killer, side)
+                {
+                    self.killers[self.length] = killer;
+                    moves[self.length] = killer;
+                    self.length += 1;
+                }
+            }
+            return;
+        }
+        if self.stage == Stage::ZoneSafe {
+            self.safe_squares = (0..9)
+                .filter(|&square| is_safe_destination(board, square, side, 0))
+                .fold(0, |squares, square| squares | (1 << square));
+        }
+
+        let mut zones = self.zones;
+        while zones != 0 {
+            let zone = zones.trailing_zeros() as u64;
+            zones &= zones - 1;
+
+            let (x_cells, o_cells) = zone_cells(board, zone);
+            let empty = !(x_cells | o_cells) & CHUNK & (self.first_move >> (9 * zone)) as u64;
+            let winning = if side {
+                winning_cells(o_cells, x_cells)
+            } else {
+                winning_cells(x_cells, o_cells)
+            } & empty;
+            let mut cells = if self.stage == Stage::Winning {
+                winning
+            } else {
+                // The move to the square matching its own zone changes the zone it sends the opponent to.
+                let safe = (self.safe_squares & !(1 << zone))
+                    | ((is_safe_destination(board, zone, side, 1 << zone) as u64) << zone);
+                if self.stage == Stage::ZoneSafe {
+                    empty & !winning & safe
+                } else {
+                    empty & !winning & !safe
+                }
+            };
+
+            while cells != 0 {
+                let mv = 9 * zone + cells.trailing_zeros() as u64;
+                cells &= cells - 1;
+                if mv != self.hash_move
+                    && (self.stage == Stage::Winning || !self.killers.contains(&mv))
+                {
+                    moves[self.length] = mv;
+                    self.length += 1;
+                }
+            }
+        }
+    }
+
+    // Returns the next move, generating the following stages as required,
+    // or `None` once every legal move has been produced.
+    pub fn next(&mut self, stack: &mut SearchStack) -> Option<Move> {
+        loop {
+            if self.next < self.length {
+                self.next += 1;
+                return Some(if self.stage == Stage::HashMove {
+                    self.hash_move
+                } else {
+                    stack.moves[self.ply][self.next - 1]
+                });
+            }
+            self.stage = match self.stage {
+                Stage::HashMove => Stage::Winning,
+                Stage::Winning => Stage::Killers,
+                Stage::Killers => Stage::ZoneSafe,
+                Stage::ZoneSafe => Stage::Rest,
+                Stage::Rest | Stage::Done => Stage::Done,
+            };
+            if self.stage == Stage::Done {
+                return None;
+            }
+            self.generate_stage(stack);
+        }
+    }
+}
+
+// The number of nodes searched between each check of the search deadline,
+// so that reading the clock does not slow down the search.
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/**
+ * A triangular table of principal variations, indexed by ply from the root of the search.
+ * Row `ply` holds the best line found so far from the node being searched at that ply,
+ * starting at column `ply`, so that a node can extend the line of its best child with its
+ * own move without any line being returned or copied in full.
+ * Lengths are kept for one ply beyond the deepest row, for the leaves at the maximum depth.
+ */
+struct PvTable {
+    lines: [[Move; MAX_PLY]; MAX_PLY],
+    lengths: [usize; MAX_PLY + 1],
+}
+
+impl PvTable {
+    // Makes `mv` followed by the line from the next ply the best line at `ply`.
+    #[inline]
+    fn update(&mut self, ply: usize, mv: Move) {
+        let length = self.lengths[ply + 1];
+        let (upper, lower) = self.lines.split_at_mut(ply + 1);
+        upper[ply][ply] = mv;
+        if length > 0 {
+            upper[ply][ply + 1..ply + 1 + length]
+                .copy_from_slice(&lower[0][ply + 1..ply + 1 + length]);
+        }
+        self.lengths[ply] = length + 1;
+    }
+
+    // The best line at `ply`, in the same form as the principal variations returned by `alpha_beta`.
+    fn line(&self, ply: usize) -> [Move; MAX_PLY] {
+        let mut line = [NULL_MOVE; MAX_PLY];
+        let length = self.lengths[ply];
+        if length > 0 {
+            line[ply..ply + length].copy_from_slice(&self.lines[ply][ply..ply + length]);
+        }
+        line
+    }
+}
+
+// The number of killer moves remembered at each ply.
+const KILLER_SLOTS: usize = 2;
+
+/**
+ * Buffers for every ply of a search, allocated once when the search begins
+ * so that nothing is allocated within the recursion itself.
+ * Each ply has a buffer for the moves of the current `MovePicker` stage,
+ * the killer moves that last caused a beta cutoff there without winning a zone,
+ * and its row of the triangular principal variation table.
+ */
+pub struct SearchStack {
+    moves: [[Move; 81]; MAX_PLY],
+    killers: [[Move; KILLER_SLOTS]; MAX_PLY],
+    pv: PvTable,
+}
+
+impl SearchStack {
+    pub fn new() -> Box<Self> {
+        Box::new(Self {
+            moves: [[NULL_MOVE; 81]; MAX_PLY],
+            killers: [[NULL_MOVE; KILLER_SLOTS]; MAX_PLY],
+            pv: PvTable {
+                lines: [[NULL_MOVE; MAX_PLY]; MAX_PLY],
+                lengths: [0; MAX_PLY + 1],
+            },
+        })
+    }
+
+    // Makes `mv` the first killer move at `ply`, keeping the previous first killer as the second.
+    fn store_killer(&mut self, ply: usize, mv: Move) {
+        let killers = &mut self.killers[ply];
+        if killers[0] != mv {
+            killers[1] = killers[0];
+            killers[0] = mv;
+        }
+    }
+}
+
+// What the search does at a node that has no hash move to search first.
+#[derive(Clone, Copy, PartialEq)]
+pub enum IirMode {
+    // The moves are searched in their staged order, as at any other node.
+    Off,
+    // The node is searched one ply shallower (internal iterative reduction),
+    // since the result is unlikely to be good enough to be worth the full depth.
+    Reduce,
+    // The node is first searched two plies shallower to find a move to search first
+    // (internal iterative deepening), before being searched to the full depth.
+    Search,
+}
+
+// How much shallower the search that finds a move to search first is, for `IirMode::Search`.
+const IID_REDUCTION: usize = 2;
+
+/**
+ * The settings for nodes without a hash move, which only apply when searching with a transposition table.
+ * Nodes with fewer than `min_depth` plies to go, and the root, are always searched in the normal way.
+ */
+#[derive(Clone, Copy)]
+pub struct IirSettings {
+    pub mode: IirMode,
+    pub min_depth: usize,
+}
+
+impl Default for IirSettings {
+    fn default() -> Self {
+        Self {
+            mode: IirMode::Search,
+            min_depth: 4,
+        }
+    }
+}
+
+// Called by `iterative_deepening` with the depth, evaluation and principal variation of each completed iteration.
+pub type IterationCallback = Box<dyn FnMut(usize, i32, &[Move; MAX_PLY]) + Send>;
+
+/**
+ * State shared across all nodes of a single search.
+ * This counts the nodes searched, and records whether the search has been stopped
+ * because its deadline has passed, it has searched as many nodes as it may, or the process was interrupted,
+ * in which case its results are to be discarded.
+ * It also holds the per-ply buffers of the search, so that they are only allocated once per search,
+ * and the principal variation of the previous iteration, whose moves are searched first
+ * for as long as the search is following it.
+ * A transposition table, if given, is shared with the searches before and after this one.
+ */
+pub struct SearchControl {
+    pub nodes: u64,
+    pub stopped: bool,
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    stop_request: Option<Arc<AtomicBool>>,
+    stack: Box<SearchStack>,
+    pv_hint: [Move; MAX_PLY],
+    follow_pv: bool,
+    table: Option<Arc<TranspositionTable>>,
+    iir: IirSettings,
+    on_iteration: Option<IterationCallback>,
+}
+
+impl SearchControl {
+    pub fn new(deadline: Option<Instant>) -> Self {
+        Self {
+            nodes: 0,
+            stopped: false,
+            deadline,
+            node_limit: None,
+            stop_request: None,
+            stack: SearchStack::new(),
+            pv_hint: [NULL_MOVE; MAX_PLY],
+            follow_pv: false,
+            table: None,
+            iir: IirSettings::default(),
+            on_iteration: None,
+        }
+    }
+
+    // Stores and looks up the results of searched positions in the given table.
+    pub fn with_table(mut self, table: Arc<TranspositionTable>) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    // Changes what is done at nodes without a hash move.
+    pub fn with_iir(mut self, iir: IirSettings) -> Self {
+        self.iir = iir;
+        self
+    }
+
+    // Reports the result of every completed iteration of iterative deepening as soon as it is found.
+    pub fn with_iteration_callback(mut self, on_iteration: IterationCallback) -> Self {
+        self.on_iteration = Some(on_iteration);
+        self
+    }
+
+    fn report_iteration(&mut self, depth: usize, eval: i32, line: &[Move; MAX_PLY]) {
+        if let Some(on_iteration) = &mut self.on_iteration {
+            on_iteration(depth, eval, line);
+        }
+    }
+
+    // Additionally stops the search once the given flag is set from another thread,
+    // such as by the `stop` command.
+    pub fn with_stop_request(mut self, stop_request: Arc<AtomicBool>) -> Self {
+        self.stop_request = Some(stop_request);
+        self
+    }
+
+    // Stops the search once it has searched the given number of nodes, to within `DEADLINE_CHECK_INTERVAL`.
+    pub fn with_node_limit(mut self, node_limit: u64) -> Self {
+        self.node_limit = Some(node_limit);
+        self
+    }
+
+    // Counts a node, and checks the deadline, the node limit and for requests to stop once every `DEADLINE_CHECK_INTERVAL` nodes.
+    #[inline]
+    fn visit(&mut self) {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(DEADLINE_CHECK_INTERVAL) {
+            self.stopped = interrupted()
+                || self
+                    .stop_request
+                    .as_ref()
+                    .is_some_and(|stop_request| stop_request.load(Ordering::Relaxed))
+                || self
+                    .node_limit
+                    .is_some_and(|node_limit| self.nodes >= node_limit)
+                || self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline);
+        }
+    }
+}
+
 /**
  * The main alpha-beta minimax function.
  * Uses a negamax construct since the heuristic is symmetric.
@@ -418,88 +1244,727 @@ pub fn alpha_beta(
     board: Board,
     side: bool,
     depth: usize,
-    mut alpha: i32, // The `alpha` variable will be updated throughout, and is cheaply copied.
+    alpha: i32,
     beta: i32,
     max_depth: usize,
+    control: &mut SearchControl,
 ) -> (i32, [u64; MAX_PLY]) {
+    let key = zobrist::hash(board, side);
+    let eval = negamax(
+        board,
+        side,
+        key,
+        depth,
+        alpha,
+        beta,
+        max_depth - depth,
+        control,
+    );
+    (eval, control.stack.pv.line(max_depth - depth))
+}
+
+/**
+ * The recursive part of `alpha_beta`, which returns only the evaluation.
+ * The principal variation of each node is instead left in the triangular table of `control`,
+ * at the row for the ply of the node.
+ * `key` is the Zobrist hash of the position, which is updated from move to move for the transposition table.
+ * `ply` is the distance from the root, which is the row of the PV table this node writes to.
+ * It is passed separately from the depth, since the depth of a node may be reduced.
+ */
+#[allow(clippy::too_many_arguments)]
+fn negamax(
+    board: Board,
+    side: bool,
+    key: u64,
+    mut depth: usize,
+    mut alpha: i32, // The `alpha` variable will be updated throughout, and is cheaply copied.
+    beta: i32,
+    ply: usize,
+    control: &mut SearchControl,
+) -> i32 {
+    control.visit();
+
     // It is not always necessary to destructure the board,
     // as only one branch of this function uses one of the components.
     // The board is otherwise passed as is.
 
     // Leaf node returns static evaluation and empty PV.
     if depth == 0 {
+        stats::count(Counter::LeafNode);
+        control.stack.pv.lengths[ply] = 0;
         let eval = evaluate(board, side);
         // In this branch, we also check whether the evaluation is conclusive or not.
         // If it is conclusive, we adjust it based on the number of moves to win/loss.
-        let adjusted_eval = match eval {
-            OUTCOME_WIN => eval - (max_depth - depth) as i32,
-            OUTCOME_LOSS => eval + (max_depth - depth) as i32,
+        return match eval {
+            OUTCOME_WIN => eval - ply as i32,
+            OUTCOME_LOSS => eval + ply as i32,
             _ => eval,
         };
-        return (adjusted_eval, [NULL_MOVE; MAX_PLY]);
     }
 
-    // Retrieve the iterator for move generation.
-    let mut move_list = generate_moves(board);
+    // The line from this node is empty until a move raises alpha.
+    control.stack.pv.lengths[ply] = 0;
+
+    // A stored result that is deep enough can be returned without searching,
+    // except at the root, which must always produce a move.
+    let entry = control
+        .table
+        .as_ref()
+        .and_then(|table| table.probe(key, ply));
+    if control.table.is_some() {
+        stats::count(if entry.is_some() {
+            Counter::TableHit
+        } else {
+            Counter::TableMiss
+        });
+    }
+    if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+        let cutoff = match entry.bound {
+            Bound::Exact => Some(entry.score.clamp(alpha, beta)),
+            Bound::Lower if entry.score >= beta => Some(beta),
+            Bound::Upper if entry.score <= alpha => Some(alpha),
+            _ => None,
+        };
+        if let Some(eval) = cutoff {
+            stats::count(Counter::TableCutoff);
+            // The line from an exact entry is cut short after its move.
+            if eval > alpha && eval < beta && entry.mv != NULL_MOVE {
+                control.stack.pv.lengths[ply + 1] = 0;
+                control.stack.pv.update(ply, entry.mv);
+            }
+            return eval;
+        }
+    }
+
+    // Only the first move searched at each node can continue along the previous principal variation.
+    // Elsewhere, the move stored in the transposition table is searched first.
+    let hash_move = if control.follow_pv {
+        control.pv_hint[ply]
+    } else {
+        NULL_MOVE
+    };
+    control.follow_pv = hash_move != NULL_MOVE;
+    let mut hash_move = match entry {
+        Some(entry) if hash_move == NULL_MOVE => entry.mv,
+        _ => hash_move,
+    };
+
+    // Without a hash move, the moves are likely to be badly ordered.
+    if hash_move == NULL_MOVE
+        && control.table.is_some()
+        && ply > 0
+        && depth >= control.iir.min_depth
+    {
+        match control.iir.mode {
+            IirMode::Off => {}
+            IirMode::Reduce => {
+                stats::count(Counter::Reduction);
+                depth -= 1;
+            }
+            IirMode::Search => {
+                negamax(
+                    board,
+                    side,
+                    key,
+                    (depth - IID_REDUCTION).max(1),
+                    alpha,
+                    beta,
+                    ply,
+                    control,
+                );
+                if control.stopped {
+                    return alpha;
+                }
+                // The best or refuting move of the shallower search begins the line of this node.
+                if control.stack.pv.lengths[ply] > 0 {
+                    hash_move = control.stack.pv.lines[ply][ply];
+                }
+                control.stack.pv.lengths[ply] = 0;
+            }
+        }
+    }
+
+    // Retrieve the iterator for staged move generation.
+    let mut move_list = MovePicker::new(board, side, ply, hash_move);
 
     // Retrieve first element into mutable binding,
     // branching immediately if `None` first (i.e. empty iterator)
-    if let Some(mut mv) = move_list.next() {
-        // Initialise PV array that will be updated over iterations.
-        let mut pv = [NULL_MOVE; MAX_PLY];
+    if let Some(mut mv) = move_list.next(&mut control.stack) {
+        // The index of the current move in generation order, for the search statistics.
+        let mut index = 0;
+        // The move that raised alpha last, to be stored in the transposition table.
+        let mut best_move = NULL_MOVE;
 
         // Equivalent to do-while loop.
         loop {
-            // Recursive alpha-beta call
-            let (mut eval, mut line) = alpha_beta(
-                play_move(board, mv, side),
+            // Recursive alpha-beta call, with the negative of the evaluation
+            // taken to adjust for our current side.
+            let child = play_move(board, mv, side);
+            let child_key = zobrist::child_hash(key, board, mv, side, child);
+            // Leaves are never looked up, so only the entries of inner nodes are prefetched.
+            if depth > 1 {
+                if let Some(table) = &control.table {
+                    table.prefetch(child_key);
+                }
+            }
+            let eval = -negamax(
+                child,
                 !side,
+                child_key,
                 depth - 1,
                 -beta,
                 -alpha,
-                max_depth,
+                ply + 1,
+                control,
             );
+            control.follow_pv = false;
 
-            // Take the negative of the evaluation to adjust for our current side.
-            eval = -eval;
-
-            // Record this move in the line.
-            line[max_depth - depth] = mv;
+            // A stopped search returns immediately, since its results will be discarded.
+            if control.stopped {
+                return alpha;
+            }
 
             if eval >= beta {
                 // Fail-hard beta cutoff.
-                return (beta, line);
+                // The line of the refuting move is kept, as it is the best line known from this node.
+                stats::count(Counter::CutNode);
+                stats::count_cutoff(index);
+                control.stack.pv.update(ply, mv);
+                if !wins_zone(board, mv, side) {
+                    control.stack.store_killer(ply, mv);
+                }
+                if let Some(table) = &control.table {
+                    table.store(key, ply, beta, mv, depth, Bound::Lower);
+                }
+                return beta;
             } else if eval > alpha {
                 // New best move found. Update PV.
                 alpha = eval;
-                pv = line;
+                best_move = mv;
+                control.stack.pv.update(ply, mv);
             }
 
             // Break out of loop if next move is None, update `mv` binding otherwise.
-            if let Some(new_mv) = move_list.next() {
+            if let Some(new_mv) = move_list.next(&mut control.stack) {
                 mv = new_mv;
+                index += 1;
             } else {
                 break;
             }
         }
+        stats::count(Counter::AllNode);
+        // Without a move raising alpha, the score is only an upper bound,
+        // and the stored move is kept from before for move ordering.
+        if let Some(table) = &control.table {
+            if best_move == NULL_MOVE {
+                table.store(key, ply, alpha, hash_move, depth, Bound::Upper);
+            } else {
+                table.store(key, ply, alpha, best_move, depth, Bound::Exact);
+            }
+        }
         // implicit return
-        (alpha, pv)
+        alpha
     } else {
         // If the very first retrieval was a `None`,
         // this position has no legal moves, and thus the game is over.
+        stats::count(Counter::TerminalNode);
 
-        // We need only to check the evaluation of the large grid.
-        let eval = toggle_eval(side, (*TABLES).0[((board.2 >> 36) & DBLCHUNK) as usize]);
+        // We need only to check the evaluation of the large grid,
+        // and without a line of zones, how the game ends without one.
+        let eval = match large_grid_eval(board.2) {
+            eval @ (OUTCOME_WIN | OUTCOME_LOSS) => toggle_eval(side, eval),
+            _ => toggle_eval(side, full_grid_eval(board)),
+        };
 
         // If the outcome is decisive (win or lose), we scale it inwards
         // by the number of plies it will take to reach the conclusion.
-        let adjusted_eval = match eval {
-            OUTCOME_WIN => eval - (max_depth - depth) as i32,
-            OUTCOME_LOSS => eval + (max_depth - depth) as i32,
+        match eval {
+            OUTCOME_WIN => eval - ply as i32,
+            OUTCOME_LOSS => eval + ply as i32,
             _ => OUTCOME_DRAW,
+        }
+        // The above implicit returns.
+    }
+}
+
+/**
+ * Searches every root move separately with a full window, giving the exact evaluation
+ * of each move rather than only the best one, at the cost of less pruning.
+ * Returns each move with its evaluation and principal variation (beginning with the move itself),
+ * sorted from best to worst for the side to move.
+ * If the search is stopped, or the deadline of the time manager (if any) passes, the move being searched
+ * has no exact evaluation, so only the moves searched before it are returned, which may be none.
+ */
+pub fn root_move_scores(
+    board: Board,
+    side: bool,
+    depth: usize,
+    time: Option<&TimeManager>,
+    control: &mut SearchControl,
+) -> Vec<(Move, i32, [u64; MAX_PLY])> {
+    if let Some(time) = time {
+        control.deadline = Some(time.deadline());
+    }
+    let mut scores = Vec::new();
+    for mv in generate_moves(board) {
+        let (eval, mut line) = alpha_beta(
+            play_move(board, mv, side),
+            !side,
+            depth - 1,
+            OUTCOME_LOSS,
+            OUTCOME_WIN,
+            depth,
+            control,
+        );
+        if control.stopped {
+            break;
+        }
+        line[0] = mv;
+        scores.push((mv, -eval, line));
+    }
+    // A stable sort keeps moves with equal evaluations in move generation order.
+    scores.sort_by_key(|&(_, eval, _)| -eval);
+    scores
+}
+
+/**
+ * Searches to increasing depths, up to `max_depth`, until the search is stopped or the time manager
+ * (if any) signals that there is no time to complete another iteration.
+ * Returns the depth of the deepest completed iteration, with its evaluation and principal variation.
+ * The first iteration is always completed, so that a move is always available.
+ * Without a time manager, every depth up to `max_depth` is searched unless the search is stopped,
+ * so that the result is the same as that of a single search to `max_depth`.
+ */
+pub fn iterative_deepening(
+    board: Board,
+    side: bool,
+    max_depth: usize,
+    time: Option<&TimeManager>,
+    control: &mut SearchControl,
+) -> (usize, i32, [u64; MAX_PLY]) {
+    // The first iteration is too small to ever check whether it should stop.
+    let (eval, line) = alpha_beta(board, side, 1, OUTCOME_LOSS, OUTCOME_WIN, 1, control);
+    let mut result = (1, eval, line);
+    control.report_iteration(1, eval, &line);
+
+    // From here on, an iteration is abandoned as soon as the deadline passes.
+    control.deadline = time.map(|time| time.deadline());
+    for depth in 2..=max_depth {
+        if time.is_some_and(|time| !time.can_start_iteration()) {
+            break;
+        }
+        control.pv_hint = result.2;
+        control.follow_pv = true;
+        let (eval, line) = alpha_beta(
+            board,
+            side,
+            depth,
+            OUTCOME_LOSS,
+            OUTCOME_WIN,
+            depth,
+            control,
+        );
+        if control.stopped {
+            break;
+        }
+        result = (depth, eval, line);
+        control.report_iteration(depth, eval, &line);
+
+        // There is no need to search further once a forced result has been found.
+        if time.is_some() && eval.abs() >= OUTCOME_WIN - MAX_PLY as i32 {
+            break;
+        }
+    }
+    result
+}
+
+// How a game has ended: won by the given player, or drawn with `None`.
+// A game without a line of zones ends once no moves are left, and is decided as in `negamax`.
+fn outcome(board: Board) -> Option<Option<bool>> {
+    if let Some(player) = winner(board) {
+        Some(Some(player))
+    } else if generate_moves(board).next().is_none() {
+        Some(full_grid_winner(board))
+    } else {
+        None
+    }
+}
+
+/**
+ * Returns whether a move by `side` leading to `child` wins for `attacker` within `depth` plies, counting the move.
+ * Under the standard rules, a win can only be made on the attacker's own move, so after an attacker move
+ * that does not win, at least two more plies are needed. Under the other variants, a defending move can
+ * lose straight away, by forming a line under misère or by ending the game a zone behind under majority.
+ */
+fn move_wins(
+    child: Board,
+    side: bool,
+    attacker: bool,
+    depth: usize,
+    control: &mut SearchControl,
+) -> bool {
+    let needed = if side == attacker && variant() == Variant::Standard {
+        3
+    } else {
+        2
+    };
+    match outcome(child) {
+        Some(result) => result == Some(attacker),
+        None => depth >= needed && forced_win(child, !side, attacker, depth - 1, control),
+    }
+}
+
+/**
+ * Returns whether `attacker` can force a win within `depth` plies, with `side` to move.
+ * Unlike `alpha_beta`, this has no heuristic evaluation: a line either wins or it does not.
+ * Moves that cannot win in time are never searched, as described for `move_wins`.
+ */
+fn forced_win(
+    board: Board,
+    side: bool,
+    attacker: bool,
+    depth: usize,
+    control: &mut SearchControl,
+) -> bool {
+    control.visit();
+    if control.stopped {
+        return false;
+    }
+    if side == attacker {
+        generate_moves(board)
+            .any(|mv| move_wins(play_move(board, mv, side), side, attacker, depth, control))
+    } else {
+        // Every defending move must lose. A defender without any moves has reached a draw.
+        let mut has_moves = false;
+        for mv in generate_moves(board) {
+            has_moves = true;
+            if !move_wins(play_move(board, mv, side), side, attacker, depth, control) {
+                return false;
+            }
+        }
+        has_moves
+    }
+}
+
+// Returns the fewest plies, up to `depth`, in which `attacker` can force a win, if possible at all.
+// Under the standard rules the attacker can only win on their own moves, so only every other ply count is tried.
+fn shortest_forced_win(
+    board: Board,
+    side: bool,
+    attacker: bool,
+    depth: usize,
+    control: &mut SearchControl,
+) -> Option<usize> {
+    let standard = variant() == Variant::Standard;
+    let first = if side == attacker || !standard { 1 } else { 2 };
+    (first..=depth)
+        .step_by(if standard { 2 } else { 1 })
+        .find(|&plies| forced_win(board, side, attacker, plies, control))
+}
+
+// The result of `mate_search`: the number of plies a forced win takes and the winning line,
+// no forced win within the given number of moves, or a search that was stopped before it could tell.
+// The line is boxed, since it is much larger than the other variants.
+pub enum MateResult {
+    Found(usize, Box<[u64; MAX_PLY]>),
+    NoMate,
+    Stopped,
+}
+
+/**
+ * Searches only for a forced win for the side to move within the given number of its own moves.
+ * In the winning line, the side to move wins as quickly as possible,
+ * while the other side delays the loss for as long as possible.
+ * A stopped search cannot prove that there is no win, nor finish the line of one it has found,
+ * so it reports neither.
+ */
+pub fn mate_search(
+    board: Board,
+    side: bool,
+    moves: usize,
+    control: &mut SearchControl,
+) -> MateResult {
+    let found = shortest_forced_win(board, side, side, 2 * moves - 1, control);
+    let plies = match found {
+        _ if control.stopped => return MateResult::Stopped,
+        Some(plies) => plies,
+        None => return MateResult::NoMate,
+    };
+
+    // Follow the proof to recover the principal variation.
+    let mut line = [NULL_MOVE; MAX_PLY];
+    let mut current = (board, side);
+    for (ply, slot) in line.iter_mut().enumerate().take(plies) {
+        let (position, to_move) = current;
+        let remaining = plies - ply;
+        let mv = if to_move == side {
+            // Take a winning move that keeps the win within the remaining plies.
+            generate_moves(position).find(|&mv| {
+                move_wins(
+                    play_move(position, mv, to_move),
+                    to_move,
+                    side,
+                    remaining,
+                    control,
+                )
+            })
+        } else {
+            // Take the defence for which the win takes the longest.
+            generate_moves(position).max_by_key(|&mv| {
+                let child = play_move(position, mv, to_move);
+                shortest_forced_win(child, !to_move, side, remaining - 1, control)
+            })
+        };
+        // Each step searches again, so the search may be stopped partway through the line.
+        let Some(mv) = mv.filter(|_| !control.stopped) else {
+            return MateResult::Stopped;
         };
+        *slot = mv;
+        current = (play_move(position, mv, to_move), !to_move);
+    }
+    MateResult::Found(plies, Box::new(line))
+}
 
-        (adjusted_eval, [NULL_MOVE; MAX_PLY])
-        // The above implicit returns.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{board_from_string, board_string};
+    use crate::rules::{set_first_move, set_variant, set_won_zones, TestRules};
+
+    fn position(position: &str) -> (Board, bool) {
+        board_from_string(position).unwrap()
+    }
+
+    #[test]
+    fn piece_counts_must_fit_the_side_to_move() {
+        let _rules = TestRules::standard();
+        let valid = |string| {
+            let (board, side) = position(string);
+            validate_position(board, side).is_ok()
+        };
+        assert!(valid("9/9/9/9/9/9/9/9/9 any x"));
+        assert!(!valid("9/9/9/9/9/9/9/9/9 any o"));
+        assert!(valid("x8/9/9/9/9/9/9/9/9 nw o"));
+        assert!(!valid("x8/9/9/9/9/9/9/9/9 nw x"));
+        assert!(valid("x8/9/9/9/o8/9/9/9/9 w x"));
+        // The same cells with Player O to move are a game swapped under the pie rule.
+        assert!(valid("x8/9/9/9/o8/9/9/9/9 w o"));
+        assert!(!valid("x1x1x1x1x/9/9/x8/9/9/9/9/9 any o"));
+    }
+
+    #[test]
+    fn handicaps_pass_up_to_their_limit() {
+        let _rules = TestRules::standard();
+        let cells = [0, 20, 40, 60, 80];
+        for count in 1..=MAX_HANDICAP {
+            for side in [false, true] {
+                let board = handicap_board(side, &cells[..count]).ok().unwrap();
+                assert!(validate_position(board, !side).is_ok());
+                assert!(validate_position(board, side).is_err());
+            }
+        }
+        assert!(handicap_board(false, &cells).is_err());
+        let (board, side) = position("x3x4/9/9/9/x3x4/9/9/9/x8 any o");
+        assert!(matches!(
+            validate_position(board, side),
+            Err(PositionError::PieceCount)
+        ));
+    }
+
+    #[test]
+    fn illegal_moves_give_their_reason() {
+        let _rules = TestRules::standard();
+        let (board, _) = position("x8/9/9/9/9/9/9/9/9 nw o");
+        assert!(check_move(board, 4).is_ok());
+        assert!(matches!(check_move(board, 0), Err(IllegalMove::Occupied)));
+        assert!(matches!(
+            check_move(board, 40),
+            Err(IllegalMove::WrongZone(0))
+        ));
+        let (board, _) = position("xxx6/9/9/oo7/9/9/9/9/9 any o");
+        assert!(matches!(check_move(board, 4), Err(IllegalMove::ClosedZone)));
+        assert!(check_move(board, 40).is_ok());
+        let (board, _) = position("xxxxxxxxx/9/9/oooooo3/9/9/9/9/9 any o");
+        assert!(matches!(check_move(board, 40), Err(IllegalMove::GameOver)));
+    }
+
+    #[test]
+    fn legal_moves_are_those_generated() {
+        let _rules = TestRules::standard();
+        let mut board = (0, 0, ZONE_ANY << 54);
+        let mut side = false;
+        for mv in [40, 36, 4, 42, 58, 44, 76, 37, 13] {
+            let generated: Vec<_> = generate_moves(board).collect();
+            for candidate in 0..81 {
+                assert_eq!(
+                    check_move(board, candidate).is_ok(),
+                    generated.contains(&candidate)
+                );
+            }
+            assert!(generated.contains(&mv));
+            board = play_move(board, mv, side);
+            side = !side;
+        }
+    }
+
+    #[test]
+    fn impossible_wins_are_rejected() {
+        let _rules = TestRules::standard();
+        let (board, side) = position("xxx6/ooo6/9/9/9/9/9/9/9 any x");
+        assert!(matches!(
+            validate_position(board, side),
+            Err(PositionError::SubBoard(0))
+        ));
+        let (board, side) = position("xxxxxxxxx/9/9/ooooooooo/9/9/9/9/9 any x");
+        assert!(matches!(
+            validate_position(board, side),
+            Err(PositionError::BothWon)
+        ));
+    }
+
+    #[test]
+    fn the_zone_to_play_in_must_be_open() {
+        let _rules = TestRules::standard();
+        let (board, side) = position("xxx6/9/9/oo7/9/9/9/9/9 nw o");
+        assert!(matches!(
+            validate_position(board, side),
+            Err(PositionError::ClosedZone)
+        ));
+        assert!(validate_position(set_zone(board, 3), side).is_ok());
+
+        // Being sent to a full zone is read as being free to play in any zone.
+        let (board, side) = position("xox6/xox6/oxo6/9/9/9/9/9/9 nw o");
+        assert_eq!((board.2 >> 54) & 0b1111, ZONE_ANY);
+        assert!(validate_position(board, side).is_ok());
+        assert!(matches!(
+            validate_position(set_zone(board, 0), side),
+            Err(PositionError::ClosedZone)
+        ));
+    }
+
+    #[test]
+    fn mate_search_finds_the_quickest_win() {
+        let _rules = TestRules::standard();
+        let (board, side) = position("xxxxxxxx1/9/9/oo1oo1oo1/9/9/oo7/9/9 ne x");
+        for moves in 1..=3 {
+            let mut control = SearchControl::new(None);
+            match mate_search(board, side, moves, &mut control) {
+                MateResult::Found(plies, line) => {
+                    assert_eq!(plies, 1);
+                    assert_eq!(line[0], 20);
+                    assert_eq!(line[1], NULL_MOVE);
+                }
+                _ => panic!("no mate found within {} moves", moves),
+            }
+        }
+    }
+
+    #[test]
+    fn mate_search_reports_no_mate_or_a_stop() {
+        let _rules = TestRules::standard();
+        let empty = (0, 0, ZONE_ANY << 54);
+        let mut control = SearchControl::new(None);
+        assert!(matches!(
+            mate_search(empty, false, 2, &mut control),
+            MateResult::NoMate
+        ));
+        let mut control = SearchControl::new(None).with_node_limit(1);
+        assert!(matches!(
+            mate_search(empty, false, 6, &mut control),
+            MateResult::Stopped
+        ));
+    }
+
+    #[test]
+    fn won_zones_follow_the_rules_in_force() {
+        let _rules = TestRules::standard();
+        let (board, side) = position("xxx6/9/9/oo7/9/9/9/9/9 c o");
+        let sent = play_move(board, 36, side);
+        let in_won_zone = |board| generate_moves(board).any(|mv| mv < 9);
+        assert_eq!((sent.2 >> 54) & 0b1111, ZONE_ANY);
+        assert!(!in_won_zone(sent));
+
+        set_won_zones(WonZones::Open);
+        let sent = play_move(board, 36, side);
+        assert_eq!((sent.2 >> 54) & 0b1111, 0);
+        assert_eq!(generate_moves(sent).collect::<Vec<_>>(), [3, 4, 5, 6, 7, 8]);
+
+        set_won_zones(WonZones::Free);
+        let sent = play_move(board, 36, side);
+        assert_eq!((sent.2 >> 54) & 0b1111, ZONE_ANY);
+        assert!(in_won_zone(sent));
+    }
+
+    #[test]
+    fn a_won_zone_stays_with_its_first_winner() {
+        let _rules = TestRules::standard();
+        set_won_zones(WonZones::Open);
+        let (board, side) = position("xxx6/oo7/9/x8/9/9/9/9/9 nw o");
+        let board = play_move(board, 5, side);
+        assert_eq!((board.2 >> 36) & 0x3ffff, 1);
+    }
+
+    #[test]
+    fn a_line_of_zones_loses_under_misere() {
+        let _rules = TestRules::standard();
+        let (board, _) = position("xxxxxxxxx/9/9/oo1oo1oo1/9/9/oo7/9/9 any o");
+        assert_eq!(winner(board), Some(false));
+        set_variant(Variant::Misere);
+        assert_eq!(winner(board), Some(true));
+
+        // Completing the line that wins under the standard rules is no win at all.
+        let (board, side) = position("xxxxxxxx1/9/9/oo1oo1oo1/9/9/oo7/9/9 ne x");
+        let mut control = SearchControl::new(None);
+        assert!(matches!(
+            mate_search(board, side, 1, &mut control),
+            MateResult::NoMate
+        ));
+    }
+
+    #[test]
+    fn more_zones_win_a_full_grid_under_majority() {
+        let _rules = TestRules::standard();
+        let (ahead, _) = position("xxx6/9/9/3xxxooo/9/9/9/9/9 any o");
+        let (level, _) = position("xxx6/9/9/6ooo/9/9/9/9/9 any o");
+        assert_eq!(full_grid_winner(ahead), None);
+        set_variant(Variant::Majority);
+        assert_eq!(full_grid_winner(ahead), Some(false));
+        assert_eq!(full_grid_winner(level), None);
+        let (behind, _) = position("ooo6/9/9/3oooxxx/9/9/9/9/9 any x");
+        assert_eq!(full_grid_winner(behind), Some(true));
+    }
+
+    #[test]
+    fn the_first_move_follows_the_first_move_rule() {
+        let _rules = TestRules::standard();
+        let empty = (0, 0, ZONE_ANY << 54);
+        assert_eq!(generate_moves(empty).count(), 81);
+
+        set_first_move(FirstMove::NoCenter);
+        assert!(generate_moves(empty).eq((0..81).filter(|&mv| mv != 40)));
+        assert!(matches!(check_move(empty, 40), Err(IllegalMove::FirstMove)));
+        // A position set up with a zone to play in is not the start of a game.
+        assert!(check_move(set_zone(empty, 4), 40).is_ok());
+
+        set_first_move(FirstMove::Zone(4));
+        assert!(generate_moves(empty).eq(36..45));
+        assert!(matches!(check_move(empty, 0), Err(IllegalMove::FirstMove)));
+        assert_eq!(generate_moves(play_move(empty, 36, false)).count(), 9);
+    }
+
+    #[test]
+    fn being_sent_to_a_full_zone_frees_the_player() {
+        let _rules = TestRules::standard();
+        let (board, side) = position("xox6/xox6/ox7/9/9/9/9/9/9 nw o");
+        assert!(validate_position(board, side).is_ok());
+        let filled = play_move(board, 8, side);
+        assert_eq!((filled.2 >> 54) & 0b1111, 8);
+        let sent = play_move(filled, 72, !side);
+        assert_eq!((sent.2 >> 54) & 0b1111, ZONE_ANY);
+        assert!(generate_moves(sent).eq((9..81).filter(|&mv| mv != 72)));
+        assert_eq!(
+            position(&board_string(set_zone(sent, 0), side)),
+            (sent, side)
+        );
     }
 }
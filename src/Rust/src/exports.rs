@@ -0,0 +1,163 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use crate::engine::*;
+use crate::json::search_json;
+use crate::notation::*;
+use crate::timeman::TimeManager;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB};
+
+/**
+ * Bindings for running the engine in a browser, as a WebAssembly module built for `wasm32-unknown-unknown`,
+ * and from Python, through the shared library built with the `python` feature.
+ * The functions are exported directly, without `wasm-bindgen` or `pyo3`: `wasm/ut3b2l.js` wraps them
+ * in a JavaScript API, and `python/ut3b2l.py` in a Python API using `ctypes`.
+ * Strings are passed in by writing them to memory given out by `alloc` (or any memory, from Python),
+ * and passed out through a buffer whose location is read with `output_ptr` and `output_len`
+ * after each call that produces a string.
+ * The module holds a single game per thread, as the protocol does,
+ * made up of the current position and a transposition table.
+ */
+struct State {
+    board: Board,
+    side: bool,
+    table: Arc<TranspositionTable>,
+    output: String,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State {
+        board: (0, 0, ZONE_ANY << 54),
+        side: false,
+        table: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB, Replacement::Aging)),
+        output: String::new(),
+    });
+}
+
+// Reads a string written by the host into memory from `alloc`.
+unsafe fn read_input(ptr: *const u8, len: usize) -> String {
+    String::from_utf8_lossy(std::slice::from_raw_parts(ptr, len)).into_owned()
+}
+
+// Replaces the output buffer, returning the length of the new output.
+fn set_output(state: &mut State, output: String) -> usize {
+    state.output = output;
+    state.output.len()
+}
+
+// Reserves `len` bytes for the host to write a string argument into.
+#[no_mangle]
+pub extern "C" fn alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+}
+
+/**
+ * # Safety
+ * `ptr` must have been returned by `alloc` with the same `len`, and not freed already.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn dealloc(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+// The location of the output of the last call that produced a string.
+#[no_mangle]
+pub extern "C" fn output_ptr() -> *const u8 {
+    STATE.with_borrow(|state| state.output.as_ptr())
+}
+
+#[no_mangle]
+pub extern "C" fn output_len() -> usize {
+    STATE.with_borrow(|state| state.output.len())
+}
+
+/**
+ * Starts a new game from a position string, or from the empty board if the string is empty.
+ * Returns whether the position was valid, keeping the previous game otherwise.
+ *
+ * # Safety
+ * `ptr` must point to `len` readable bytes.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn new_game(ptr: *const u8, len: usize) -> bool {
+    let position = read_input(ptr, len);
+    let parsed = if position.trim().is_empty() {
+        Some(((0, 0, ZONE_ANY << 54), false))
+    } else {
+        board_from_string(&position)
+    };
+    match parsed {
+        Some((board, side)) if validate_position(board, side).is_ok() => {
+            STATE.with_borrow_mut(|state| {
+                state.board = board;
+                state.side = side;
+                state.table.clear();
+            });
+            true
+        }
+        _ => false,
+    }
+}
+
+/**
+ * Plays a move in either notation for the side to move, returning whether it was legal.
+ *
+ * # Safety
+ * `ptr` must point to `len` readable bytes.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn play(ptr: *const u8, len: usize) -> bool {
+    let Some(mv) = move_from_string(read_input(ptr, len).trim()) else {
+        return false;
+    };
+    STATE.with_borrow_mut(|state| {
+        if check_move(state.board, mv).is_err() {
+            return false;
+        }
+        state.board = play_move(state.board, mv, state.side);
+        state.side = !state.side;
+        true
+    })
+}
+
+// Outputs the legal moves of the position, separated by spaces, returning the length of the output.
+#[no_mangle]
+pub extern "C" fn legal_moves() -> usize {
+    STATE.with_borrow_mut(|state| {
+        let moves: Vec<_> = generate_moves(state.board)
+            .map(|mv| move_string(mv, Notation::Zone))
+            .collect();
+        set_output(state, moves.join(" "))
+    })
+}
+
+// Outputs the position string of the current position, in the format accepted by `new_game`.
+#[no_mangle]
+pub extern "C" fn board() -> usize {
+    STATE.with_borrow_mut(|state| set_output(state, board_string(state.board, state.side)))
+}
+
+/**
+ * Searches the current position, either to `depth` plies, or for `movetime_ms` milliseconds if `depth` is 0,
+ * and outputs the result as a JSON object, such as `{"move":"c/c","depth":4,"eval":"+16","pv":["c/c","c/nw"]}`.
+ * The move is `null` if the game is over. The position is left unchanged.
+ * Returns the length of the output.
+ */
+#[no_mangle]
+pub extern "C" fn search(depth: usize, movetime_ms: u32) -> usize {
+    STATE.with_borrow_mut(|state| {
+        let (board, side) = (state.board, state.side);
+        state.table.new_search();
+        let mut control = SearchControl::new(None).with_table(state.table.clone());
+        let (depth, eval, line) = if depth == 0 {
+            let time = TimeManager::new(movetime_ms as u64);
+            iterative_deepening(board, side, MAX_PLY, Some(&time), &mut control)
+        } else {
+            iterative_deepening(board, side, depth.min(MAX_PLY), None, &mut control)
+        };
+        set_output(state, search_json(depth, eval, &line).to_string())
+    })
+}
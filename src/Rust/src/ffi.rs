@@ -0,0 +1,235 @@
+use std::ffi::{c_char, c_int, c_uint, c_void, CStr, CString};
+use std::ptr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::engine::*;
+use crate::notation::*;
+use crate::timeman::TimeManager;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB, MAX_HASH_MB};
+
+/**
+ * The C API of the engine, for embedding it in programs written in other languages.
+ * It is declared in `include/ut3b2l.h`, which documents each function for C callers,
+ * and is exported from the shared library when built with the `ffi` feature.
+ * Each engine handle holds its own game and transposition table, so several can be used at once,
+ * though a single handle must not be used by two threads at the same time.
+ * All strings are NUL-terminated UTF-8, and strings returned by the engine are freed with `ut3_string_free`.
+ */
+pub struct Engine {
+    board: Board,
+    side: bool,
+    table: Arc<TranspositionTable>,
+}
+
+// Called with each `info` line of a search, together with the pointer given alongside it.
+pub type InfoCallback = Option<extern "C" fn(line: *const c_char, user_data: *mut c_void)>;
+
+// The return codes of the functions that can fail.
+pub const UT3_OK: c_int = 0;
+pub const UT3_INVALID: c_int = -1;
+
+// Reads a string argument, which is invalid if it is null or not UTF-8.
+unsafe fn read_string<'a>(string: *const c_char) -> Option<&'a str> {
+    if string.is_null() {
+        return None;
+    }
+    CStr::from_ptr(string).to_str().ok()
+}
+
+// Hands a string to the caller, who frees it with `ut3_string_free`.
+fn return_string(string: String) -> *mut c_char {
+    CString::new(string).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/**
+ * Creates an engine at the start of a new game, with a transposition table of the given size in megabytes,
+ * or of the default size if 0 is given. Returns null if the size is too large.
+ */
+#[no_mangle]
+pub extern "C" fn ut3_engine_new(hash_mb: usize) -> *mut Engine {
+    let hash_mb = if hash_mb == 0 {
+        DEFAULT_HASH_MB
+    } else {
+        hash_mb
+    };
+    if hash_mb > MAX_HASH_MB {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(Engine {
+        board: (0, 0, ZONE_ANY << 54),
+        side: false,
+        table: Arc::new(TranspositionTable::new(hash_mb, Replacement::Aging)),
+    }))
+}
+
+/**
+ * # Safety
+ * `engine` must have been returned by `ut3_engine_new` and not freed already, or be null.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ut3_engine_free(engine: *mut Engine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/**
+ * Starts a new game from a position string, as taken by `newgame`.
+ * Returns `UT3_INVALID`, leaving the game unchanged, if the position cannot be read or is not valid.
+ *
+ * # Safety
+ * `engine` must be a live handle, and `position` a NUL-terminated string or null.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ut3_engine_set_position(
+    engine: *mut Engine,
+    position: *const c_char,
+) -> c_int {
+    let engine = &mut *engine;
+    match read_string(position).and_then(board_from_string) {
+        Some((board, side)) if validate_position(board, side).is_ok() => {
+            engine.board = board;
+            engine.side = side;
+            engine.table.clear();
+            UT3_OK
+        }
+        _ => UT3_INVALID,
+    }
+}
+
+/**
+ * Returns the position string of the current position.
+ *
+ * # Safety
+ * `engine` must be a live handle.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ut3_engine_position(engine: *const Engine) -> *mut c_char {
+    let engine = &*engine;
+    return_string(board_string(engine.board, engine.side))
+}
+
+/**
+ * Plays a move in either notation for the side to move.
+ * Returns `UT3_INVALID`, leaving the game unchanged, if the move cannot be read or is illegal.
+ *
+ * # Safety
+ * `engine` must be a live handle, and `mv` a NUL-terminated string or null.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ut3_engine_play(engine: *mut Engine, mv: *const c_char) -> c_int {
+    let engine = &mut *engine;
+    match read_string(mv).and_then(move_from_string) {
+        Some(mv) if check_move(engine.board, mv).is_ok() => {
+            engine.board = play_move(engine.board, mv, engine.side);
+            engine.side = !engine.side;
+            UT3_OK
+        }
+        _ => UT3_INVALID,
+    }
+}
+
+/**
+ * Returns the legal moves of the position in the `zone/square` notation, separated by spaces.
+ * The string is empty once the game is over.
+ *
+ * # Safety
+ * `engine` must be a live handle.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ut3_engine_legal_moves(engine: *const Engine) -> *mut c_char {
+    let moves: Vec<_> = generate_moves((*engine).board)
+        .map(|mv| move_string(mv, Notation::Zone))
+        .collect();
+    return_string(moves.join(" "))
+}
+
+// The user data of a callback, which is only ever used on the thread that started the search.
+struct UserData(*mut c_void);
+unsafe impl Send for UserData {}
+
+impl UserData {
+    // Taking the whole value, so that the closure holding it captures it rather than the pointer alone.
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/**
+ * Searches the current position to `depth` plies, or for `movetime_ms` milliseconds if `depth` is 0,
+ * without changing the position. After each completed iteration, `callback` (if not null) is called
+ * with an `info` line in the format output by `go`, and `user_data`.
+ * The best move is written to `best_move`, to be freed by the caller, or null if the game is over.
+ * Returns `UT3_INVALID` if neither limit is given, or the depth is greater than the engine supports.
+ *
+ * # Safety
+ * `engine` must be a live handle, and `best_move` a valid pointer to write to.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ut3_engine_search(
+    engine: *mut Engine,
+    depth: c_uint,
+    movetime_ms: c_uint,
+    callback: InfoCallback,
+    user_data: *mut c_void,
+    best_move: *mut *mut c_char,
+) -> c_int {
+    let engine = &mut *engine;
+    if (depth == 0 && movetime_ms == 0) || depth as usize > MAX_PLY {
+        return UT3_INVALID;
+    }
+
+    let start = Instant::now();
+    let mut control = SearchControl::new(None).with_table(engine.table.clone());
+    if let Some(callback) = callback {
+        let user_data = UserData(user_data);
+        control = control.with_iteration_callback(Box::new(move |depth, eval, line| {
+            let info = format!(
+                "info depth {} pv {} eval {} time {}",
+                depth,
+                pv_string(line, Notation::Zone),
+                eval_string(eval, depth),
+                start.elapsed().as_millis()
+            );
+            let info = CString::new(info).unwrap();
+            callback(info.as_ptr(), user_data.get());
+        }));
+    }
+
+    engine.table.new_search();
+    let (_, _, line) = if depth == 0 {
+        let time = TimeManager::new(movetime_ms as u64);
+        iterative_deepening(
+            engine.board,
+            engine.side,
+            MAX_PLY,
+            Some(&time),
+            &mut control,
+        )
+    } else {
+        iterative_deepening(
+            engine.board,
+            engine.side,
+            depth as usize,
+            None,
+            &mut control,
+        )
+    };
+    *best_move = match line[0] {
+        NULL_MOVE => ptr::null_mut(),
+        mv => return_string(move_string(mv, Notation::Zone)),
+    };
+    UT3_OK
+}
+
+/**
+ * # Safety
+ * `string` must have been returned by the engine and not freed already, or be null.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn ut3_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
@@ -0,0 +1,315 @@
+use std::time::Instant;
+
+use crate::engine::{OUTCOME_DRAW, OUTCOME_LOSS, OUTCOME_WIN};
+use crate::notation::eval_string;
+
+// The sizes of grid supported, where a grid of size `n` is an `n` by `n` grid of zones of `n` by `n` cells.
+pub const MIN_SIZE: usize = 3;
+pub const MAX_SIZE: usize = 5;
+
+// Weights of a line of zones, or of cells within a zone, held by only one player, for each cell of it they hold.
+const LARGE_LINE: i32 = 20;
+const SMALL_LINE: i32 = 1;
+
+// The state of a zone: still being played in, won by a player, or completely filled without a winner.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ZoneState {
+    Open,
+    Won(bool),
+    Full,
+}
+
+/**
+ * A board of any size from `MIN_SIZE` to `MAX_SIZE`, for experimenting with larger boards than the usual one.
+ * The usual board, of size 3, is far faster with the bitboards of `engine`, which this does not replace.
+ * The rules are those of the usual game, with lines running the whole width of a grid:
+ * a zone is won with a row, column or diagonal of its cells, the game with a row, column or diagonal of zones,
+ * and the square of each move sends the opponent to the zone in the same place, unless it is won or full.
+ * Cells are indexed by `n * n * zone + square`, with zones and squares numbered in reading order from 0.
+ */
+#[derive(Clone)]
+pub struct GridBoard {
+    pub size: usize,
+    pub cells: Vec<Option<bool>>,
+    pub zones: Vec<ZoneState>,
+    // The zone to be played in, or `None` if any zone may be played in.
+    pub zone: Option<usize>,
+    pub side: bool,
+    pub winner: Option<bool>,
+}
+
+// Returns the lines of an `n` by `n` grid, as the indices of their squares: the rows, the columns and the two diagonals.
+pub fn lines(size: usize) -> Vec<Vec<usize>> {
+    let mut lines = Vec::new();
+    for i in 0..size {
+        lines.push((0..size).map(|j| size * i + j).collect());
+        lines.push((0..size).map(|j| size * j + i).collect());
+    }
+    lines.push((0..size).map(|i| (size + 1) * i).collect());
+    lines.push((0..size).map(|i| (size - 1) * (i + 1)).collect());
+    lines
+}
+
+// Returns whether the square at `at` of an `n` by `n` grid completes a line of squares that `owned` holds.
+fn completes_line(size: usize, at: usize, owned: impl Fn(usize) -> bool) -> bool {
+    let (row, col) = (at / size, at % size);
+    (0..size).all(|j| owned(size * row + j))
+        || (0..size).all(|j| owned(size * j + col))
+        || (row == col && (0..size).all(|i| owned((size + 1) * i)))
+        || (row + col == size - 1 && (0..size).all(|i| owned((size - 1) * (i + 1))))
+}
+
+impl GridBoard {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            cells: vec![None; size.pow(4)],
+            zones: vec![ZoneState::Open; size * size],
+            zone: None,
+            side: false,
+            winner: None,
+        }
+    }
+
+    // The result of the game once it is over: the winner, or `None` for a draw.
+    pub fn result(&self) -> Option<Option<bool>> {
+        if self.winner.is_some() {
+            Some(self.winner)
+        } else if self.zones.iter().all(|&state| state != ZoneState::Open) {
+            Some(None)
+        } else {
+            None
+        }
+    }
+
+    // The legal moves, in order of their cells.
+    pub fn moves(&self) -> Vec<usize> {
+        if self.result().is_some() {
+            return Vec::new();
+        }
+        let area = self.size * self.size;
+        let zones: Vec<usize> = match self.zone {
+            Some(zone) => vec![zone],
+            None => (0..area)
+                .filter(|&zone| self.zones[zone] == ZoneState::Open)
+                .collect(),
+        };
+        zones
+            .into_iter()
+            .flat_map(|zone| area * zone..area * (zone + 1))
+            .filter(|&mv| self.cells[mv].is_none())
+            .collect()
+    }
+
+    // Returns the board after the side to move plays the given move, which must be legal.
+    pub fn play(&self, mv: usize) -> Self {
+        let mut board = self.clone();
+        let area = self.size * self.size;
+        let (zone, square) = (mv / area, mv % area);
+        board.cells[mv] = Some(self.side);
+
+        let cells = &board.cells[area * zone..area * (zone + 1)];
+        if completes_line(self.size, square, |i| cells[i] == Some(self.side)) {
+            board.zones[zone] = ZoneState::Won(self.side);
+            if completes_line(self.size, zone, |i| {
+                board.zones[i] == ZoneState::Won(self.side)
+            }) {
+                board.winner = Some(self.side);
+            }
+        } else if cells.iter().all(Option::is_some) {
+            board.zones[zone] = ZoneState::Full;
+        }
+
+        board.zone = Some(square).filter(|&square| board.zones[square] == ZoneState::Open);
+        board.side = !self.side;
+        board
+    }
+
+    /**
+     * A heuristic evaluation from the perspective of the side to move, in the manner of `engine::evaluate`:
+     * every line of zones, and every line of cells within an open zone, that only one player holds any of
+     * counts for that player by the square of the number they hold.
+     */
+    pub fn evaluate(&self, lines: &[Vec<usize>]) -> i32 {
+        let area = self.size * self.size;
+        let score = |own: usize, other: usize| match (own, other) {
+            (own, 0) => (own * own) as i32,
+            (0, other) => -((other * other) as i32),
+            _ => 0,
+        };
+        let mut eval = 0;
+        for line in lines {
+            let count = |side| {
+                line.iter()
+                    .filter(|&&zone| self.zones[zone] == ZoneState::Won(side))
+                    .count()
+            };
+            eval += LARGE_LINE * score(count(false), count(true));
+        }
+        for zone in (0..area).filter(|&zone| self.zones[zone] == ZoneState::Open) {
+            for line in lines {
+                let count = |side| {
+                    line.iter()
+                        .filter(|&&square| self.cells[area * zone + square] == Some(side))
+                        .count()
+                };
+                eval += SMALL_LINE * score(count(false), count(true));
+            }
+        }
+        if self.side {
+            -eval
+        } else {
+            eval
+        }
+    }
+}
+
+/**
+ * Searches a board to the given depth with alpha-beta pruning, returning the evaluation for the side to move,
+ * where a forced win in `n` plies is `OUTCOME_WIN - n` as in the usual search, and the best move if there is one.
+ * The number of nodes searched is added to `nodes`.
+ */
+pub fn search(
+    board: &GridBoard,
+    depth: usize,
+    mut alpha: i32,
+    beta: i32,
+    ply: usize,
+    lines: &[Vec<usize>],
+    nodes: &mut u64,
+) -> (i32, Option<usize>) {
+    *nodes += 1;
+    if let Some(result) = board.result() {
+        // The player who has just moved is the only one who can have won.
+        return match result {
+            Some(_) => (OUTCOME_LOSS + ply as i32, None),
+            None => (OUTCOME_DRAW, None),
+        };
+    }
+    if depth == 0 {
+        return (board.evaluate(lines), None);
+    }
+    let mut best_move = None;
+    for mv in board.moves() {
+        let eval = -search(
+            &board.play(mv),
+            depth - 1,
+            -beta,
+            -alpha,
+            ply + 1,
+            lines,
+            nodes,
+        )
+        .0;
+        if best_move.is_none() || eval > alpha {
+            alpha = alpha.max(eval);
+            best_move = Some(mv);
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+    (alpha, best_move)
+}
+
+// Counts the leaf nodes of the tree of legal moves to the given depth, as `perft` does for the usual board.
+pub fn perft(board: &GridBoard, depth: usize) -> u64 {
+    let moves = board.moves();
+    match depth {
+        0 => 1,
+        1 => moves.len() as u64,
+        _ => moves
+            .into_iter()
+            .map(|mv| perft(&board.play(mv), depth - 1))
+            .sum(),
+    }
+}
+
+// Writes a move as its zone and square, numbered from 0, as in `4/12`.
+pub fn move_string(board: &GridBoard, mv: usize) -> String {
+    let area = board.size * board.size;
+    format!("{}/{}", mv / area, mv % area)
+}
+
+/**
+ * Runs the larger boards from the command line, as `grid [--size <size>] perft <depth>`,
+ * counting the moves to each depth up to the given one, or `grid [--size <size>] autoplay <depth>`,
+ * playing a game against itself with a search of the given depth for each move.
+ * The size is 4 by default.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (size, rest) = match args {
+        [flag, size, rest @ ..] if flag == "--size" => (
+            size.parse()
+                .ok()
+                .filter(|size| (MIN_SIZE..=MAX_SIZE).contains(size))
+                .ok_or_else(|| format!("size must be from {} to {}", MIN_SIZE, MAX_SIZE))?,
+            rest,
+        ),
+        rest => (4, rest),
+    };
+    let usage = "usage: grid [--size <size>] perft <depth> | grid [--size <size>] autoplay <depth>";
+    let [command, depth] = rest else {
+        return Err(usage.to_string());
+    };
+    let depth: usize = depth
+        .parse()
+        .ok()
+        .filter(|&depth| depth > 0)
+        .ok_or_else(|| format!("invalid depth {}", depth))?;
+    let mut board = GridBoard::new(size);
+    match command.as_str() {
+        "perft" => {
+            for depth in 1..=depth {
+                let start = Instant::now();
+                let nodes = perft(&board, depth);
+                println!(
+                    "grid perft depth {} nodes {} time {}",
+                    depth,
+                    nodes,
+                    start.elapsed().as_millis()
+                );
+            }
+        }
+        "autoplay" => {
+            let lines = lines(size);
+            let mut ply = 0;
+            while board.result().is_none() {
+                let (mut nodes, start) = (0, Instant::now());
+                let (eval, mv) = search(
+                    &board,
+                    depth,
+                    OUTCOME_LOSS,
+                    OUTCOME_WIN,
+                    0,
+                    &lines,
+                    &mut nodes,
+                );
+                let Some(mv) = mv else {
+                    break;
+                };
+                println!(
+                    "grid ply {} side {} move {} eval {} nodes {} time {}",
+                    ply,
+                    if board.side { "o" } else { "x" },
+                    move_string(&board, mv),
+                    eval_string(eval, depth),
+                    nodes,
+                    start.elapsed().as_millis()
+                );
+                board = board.play(mv);
+                ply += 1;
+            }
+            println!(
+                "grid result {}",
+                match board.result() {
+                    Some(Some(false)) => "1-0",
+                    Some(Some(true)) => "0-1",
+                    _ => "1/2-1/2",
+                }
+            );
+        }
+        _ => return Err(usage.to_string()),
+    }
+    Ok(())
+}
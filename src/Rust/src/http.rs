@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::engine::*;
+use crate::metrics::Metrics;
+use crate::timeman::TimeManager;
+use crate::tt::{Replacement, TranspositionTable};
+use crate::{illegal_move_string, position_error_string};
+use ut3b2l::json::{search_json, Json};
+use ut3b2l::notation::*;
+
+// The size of the transposition table of each game, kept small since many games may be open at once.
+const GAME_HASH_MB: usize = 4;
+
+// The most games that may be open at once, after which new games are refused until one is deleted.
+const MAX_GAMES: usize = 256;
+
+// The largest request body accepted, well beyond any request the API takes.
+const MAX_BODY: usize = 1 << 16;
+
+// The longest a search may run, in milliseconds, which also stops searches to a depth that would take longer.
+const MAX_MOVETIME: u64 = 60_000;
+
+// The most searches that may run at once, and connections that may be served at once,
+// beyond which requests are refused, so that no number of requests can take up every thread or core.
+const MAX_SEARCHES: usize = 8;
+const MAX_CONNECTIONS: usize = 64;
+
+// How long a connection may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The position of a game held by the server.
+#[derive(Clone, Copy)]
+struct Game {
+    board: Board,
+    side: bool,
+}
+
+// A game together with its own transposition table, so that searches in it build on each other.
+// The table is kept outside the lock, so that it can be inspected while the game is being searched.
+struct OpenGame {
+    game: Mutex<Game>,
+    table: Arc<TranspositionTable>,
+}
+
+// The open games by their identifiers, each locked separately, and the number of searches running.
+#[derive(Default)]
+struct Games {
+    games: HashMap<u64, Arc<OpenGame>>,
+    next_id: u64,
+    searches: usize,
+}
+
+// A search counted against `MAX_SEARCHES` for as long as it is held.
+struct SearchSlot<'a>(&'a Mutex<Games>);
+
+impl<'a> SearchSlot<'a> {
+    fn take(games: &'a Mutex<Games>) -> Option<Self> {
+        let mut open = games.lock().unwrap();
+        if open.searches >= MAX_SEARCHES {
+            return None;
+        }
+        open.searches += 1;
+        Some(Self(games))
+    }
+}
+
+impl Drop for SearchSlot<'_> {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().searches -= 1;
+    }
+}
+
+// A response, made up of its status code and JSON body, or no body for `204 No Content`.
+type Response = (u16, Option<Json>);
+
+fn error(status: u16, message: &str) -> Response {
+    (
+        status,
+        Some(Json::object(vec![(
+            "error",
+            Json::String(message.to_string()),
+        )])),
+    )
+}
+
+// The state of a game as returned by most endpoints.
+fn game_json(id: u64, game: &Game) -> Json {
+    Json::object(vec![
+        ("id", Json::Number(id as i64)),
+        (
+            "position",
+            Json::String(board_string(game.board, game.side)),
+        ),
+        (
+            "moves",
+            Json::Array(
+                generate_moves(game.board)
+                    .map(|mv| Json::String(move_string(mv, Notation::Zone)))
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+// Reads a request, returning its method, path and body, or `None` if it is malformed.
+fn read_request(reader: &mut impl BufRead) -> Option<(String, String, String)> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut words = line.split_whitespace();
+    let method = words.next()?.to_string();
+    let path = words.next()?.to_string();
+
+    let mut length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok()?;
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return None;
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    Some((method, path, String::from_utf8(body).ok()?))
+}
+
+// Parses a request body, where an empty body is taken as an empty object.
+fn parse_body(body: &str) -> Option<Json> {
+    if body.trim().is_empty() {
+        Some(Json::Object(Vec::new()))
+    } else {
+        Json::parse(body).filter(|json| matches!(json, Json::Object(_)))
+    }
+}
+
+// `POST /game` starts a game from the given `position`, or from the empty board if none is given.
+fn new_game(games: &Mutex<Games>, body: &Json) -> Response {
+    let (board, side) = match body.get("position") {
+        None => ((0, 0, ZONE_ANY << 54), false),
+        Some(position) => match position.as_str().and_then(board_from_string) {
+            Some(parsed) => parsed,
+            None => return error(400, "invalid position"),
+        },
+    };
+    if let Err(position_error) = validate_position(board, side) {
+        return error(
+            400,
+            &format!("invalid position {}", position_error_string(position_error)),
+        );
+    }
+    let mut games = games.lock().unwrap();
+    if games.games.len() >= MAX_GAMES {
+        return error(503, "too many games");
+    }
+    let game = Game { board, side };
+    let id = games.next_id;
+    games.next_id += 1;
+    let json = game_json(id, &game);
+    games.games.insert(
+        id,
+        Arc::new(OpenGame {
+            game: Mutex::new(game),
+            table: Arc::new(TranspositionTable::new(GAME_HASH_MB, Replacement::Aging)),
+        }),
+    );
+    (201, Some(json))
+}
+
+// `POST /game/{id}/move` plays the given `move` in either notation.
+fn play(id: u64, game: &mut Game, body: &Json) -> Response {
+    let Some(mv) = body
+        .get("move")
+        .and_then(Json::as_str)
+        .and_then(move_from_string)
+    else {
+        return error(400, "invalid move");
+    };
+    if let Err(illegal) = check_move(game.board, mv) {
+        return error(400, &format!("illegal {}", illegal_move_string(illegal)));
+    }
+    game.board = play_move(game.board, mv, game.side);
+    game.side = !game.side;
+    (200, Some(game_json(id, game)))
+}
+
+// `POST /game/{id}/search` searches to the given `depth`, or for the given `movetime` in milliseconds,
+// for at most `MAX_MOVETIME` either way, leaving the game unchanged.
+fn search(
+    game: &Game,
+    table: &Arc<TranspositionTable>,
+    body: &Json,
+    metrics: &Metrics,
+) -> Response {
+    let depth = body.get("depth").map(Json::as_i64);
+    let movetime = body.get("movetime").map(Json::as_i64);
+    let (depth, movetime) = match (depth, movetime) {
+        (Some(Some(depth)), None) if depth > 0 && depth as usize <= MAX_PLY => {
+            (depth as usize, MAX_MOVETIME)
+        }
+        (None, Some(Some(movetime))) if movetime > 0 && movetime as u64 <= MAX_MOVETIME => {
+            (MAX_PLY, movetime as u64)
+        }
+        _ => return error(400, "invalid limit"),
+    };
+    metrics.search_started();
+    table.new_search();
+    let mut control = SearchControl::new(None).with_table(table.clone());
+    let start = Instant::now();
+    let time = TimeManager::new(movetime);
+    let search = iterative_deepening(game.board, game.side, depth, Some(&time), &mut control);
+    let (depth, eval, line) = search;
+    metrics.search_completed(
+        depth,
+        Some(control.nodes),
+        start.elapsed().as_millis() as u64,
+    );
+    (200, Some(search_json(depth, eval, &line)))
+}
+
+// Routes a request to its endpoint.
+fn respond(
+    games: &Mutex<Games>,
+    metrics: &Metrics,
+    method: &str,
+    path: &str,
+    body: &str,
+) -> Response {
+    let Some(body) = parse_body(body) else {
+        return error(400, "invalid json");
+    };
+    let segments: Vec<_> = path.trim_matches('/').split('/').collect();
+    if segments == ["game"] {
+        return match method {
+            "POST" => {
+                let response = new_game(games, &body);
+                if response.0 == 201 {
+                    metrics.session_opened();
+                }
+                response
+            }
+            _ => error(405, "method not allowed"),
+        };
+    }
+    if segments.len() < 2 || segments.len() > 3 || segments[0] != "game" {
+        return error(404, "not found");
+    }
+    let Ok(id) = segments[1].parse::<u64>() else {
+        return error(404, "not found");
+    };
+    if segments.len() == 2 && method == "DELETE" {
+        return match games.lock().unwrap().games.remove(&id) {
+            Some(_) => {
+                metrics.session_closed();
+                (204, None)
+            }
+            None => error(404, "no game"),
+        };
+    }
+    let Some(open) = games.lock().unwrap().games.get(&id).cloned() else {
+        return error(404, "no game");
+    };
+    let mut game = open.game.lock().unwrap();
+    match (method, segments.get(2).copied()) {
+        ("GET", None | Some("board")) => (200, Some(game_json(id, &game))),
+        ("POST", Some("move")) => play(id, &mut game, &body),
+        // The search is of a copy of the game, so other requests for the game are not held up by it.
+        ("POST", Some("search")) => {
+            let position = *game;
+            drop(game);
+            match SearchSlot::take(games) {
+                Some(_slot) => search(&position, &open.table, &body, metrics),
+                None => error(503, "too many searches"),
+            }
+        }
+        (_, None | Some("board" | "move" | "search")) => error(405, "method not allowed"),
+        _ => error(404, "not found"),
+    }
+}
+
+// The board page served at `/`, which uses the API for everything it does.
+const PAGE: &str = include_str!("../web/index.html");
+
+// The metrics of the server, where each open game counts as a session.
+fn metrics_text(games: &Mutex<Games>, metrics: &Metrics) -> String {
+    let tables: Vec<_> = {
+        let games = games.lock().unwrap();
+        games
+            .games
+            .values()
+            .map(|open| open.table.clone())
+            .collect()
+    };
+    let occupancy =
+        tables.iter().map(|table| table.occupancy()).sum::<f64>() / tables.len().max(1) as f64;
+    metrics.render(true, Some(occupancy))
+}
+
+// Handles a single request on its own connection, which is closed after the response.
+fn serve_connection(
+    stream: TcpStream,
+    games: &Mutex<Games>,
+    metrics: &Metrics,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (status, content_type, body) = match read_request(&mut reader) {
+        Some((method, path, _)) if method == "GET" && path == "/" => {
+            (200, "text/html; charset=utf-8", PAGE.to_string())
+        }
+        Some((method, path, _)) if method == "GET" && path == "/metrics" => (
+            200,
+            "text/plain; version=0.0.4",
+            metrics_text(games, metrics),
+        ),
+        request => {
+            let (status, json) = match request {
+                Some((method, path, body)) => respond(games, metrics, &method, &path, &body),
+                None => error(400, "invalid request"),
+            };
+            let body = json.map_or(String::new(), |json| json.to_string());
+            (status, "application/json", body)
+        }
+    };
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/**
+ * Serves the HTTP API on the given address, for web apps and tests that do not keep a connection open,
+ * together with a page at `/` for playing and analysing games in a browser and metrics at `/metrics`.
+ * Games are kept in memory by identifier until deleted, and each request is answered on its own connection.
+ * Requests are handled on separate threads, so a long search in one game does not hold up the others.
+ */
+pub fn serve(address: &str) {
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("cannot listen on {}: {}", address, error);
+            return;
+        }
+    };
+    eprintln!("listening on {}", address);
+    let games = Arc::new(Mutex::new(Games::default()));
+    let metrics = Arc::new(Metrics::default());
+    let connections = Arc::new(AtomicUsize::new(0));
+    for mut stream in listener.incoming().flatten() {
+        if connections.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::Relaxed);
+            stream
+                .write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .ok();
+            continue;
+        }
+        let games = games.clone();
+        let metrics = metrics.clone();
+        let connections = connections.clone();
+        thread::spawn(move || {
+            serve_connection(stream, &games, &metrics).ok();
+            connections.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set once the process has been asked to terminate, and never cleared.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Whether an interrupt has been received.
+// Searches check this periodically and stop as soon as it is set.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/**
+ * Installs a handler for `SIGINT`, so that an interrupt stops any running search
+ * and lets the main loop exit cleanly instead of terminating the process mid-write.
+ * The handler only sets an atomic flag, which is all that is safe to do inside a signal handler.
+ */
+#[cfg(unix)]
+pub fn install_handler() {
+    const SIGINT: i32 = 2;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+
+    extern "C" fn handle(_: i32) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+
+    unsafe {
+        signal(SIGINT, handle);
+    }
+}
+
+// On other platforms, interrupts keep their default behaviour of ending the process.
+#[cfg(not(unix))]
+pub fn install_handler() {}
@@ -0,0 +1,279 @@
+use std::fmt;
+
+use crate::engine::*;
+use crate::notation::*;
+
+/**
+ * A JSON value, as read from and written to the JSON interfaces of the engine.
+ * Numbers are always integers, since nothing the engine reads or writes is fractional,
+ * and objects keep their keys in order, so that output is written in the order it was built.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    // Parses a complete JSON document, returning `None` if it is malformed.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            index: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.index == parser.chars.len() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    // Builds an object from keys and values.
+    pub fn object(fields: Vec<(&str, Json)>) -> Self {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    // The value of a key of an object, or `None` if it is absent or this is not an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(string) => write_string(f, string),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// Writes a string with quotes, escaping the characters that JSON requires to be escaped.
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .get(self.index)
+            .is_some_and(|c| c.is_whitespace())
+        {
+            self.index += 1;
+        }
+    }
+
+    // Consumes the given character, after any whitespace, if it comes next.
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.get(self.index) == Some(&expected) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Consumes the given word if it comes next.
+    fn eat_word(&mut self, word: &str) -> bool {
+        let end = self.index + word.len();
+        if end <= self.chars.len() && self.chars[self.index..end].iter().copied().eq(word.chars()) {
+            self.index = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        let next = *self.chars.get(self.index)?;
+        match next {
+            '{' => {
+                self.index += 1;
+                let mut fields = Vec::new();
+                if self.eat('}') {
+                    return Some(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    if !self.eat(':') {
+                        return None;
+                    }
+                    fields.push((key, self.value()?));
+                    if self.eat('}') {
+                        return Some(Json::Object(fields));
+                    }
+                    if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            '[' => {
+                self.index += 1;
+                let mut values = Vec::new();
+                if self.eat(']') {
+                    return Some(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    if self.eat(']') {
+                        return Some(Json::Array(values));
+                    }
+                    if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            '"' => self.string().map(Json::String),
+            '-' | '0'..='9' => {
+                let start = self.index;
+                self.index += 1;
+                while self
+                    .chars
+                    .get(self.index)
+                    .is_some_and(|c| c.is_ascii_digit())
+                {
+                    self.index += 1;
+                }
+                let number: String = self.chars[start..self.index].iter().collect();
+                number.parse().ok().map(Json::Number)
+            }
+            _ if self.eat_word("null") => Some(Json::Null),
+            _ if self.eat_word("true") => Some(Json::Bool(true)),
+            _ if self.eat_word("false") => Some(Json::Bool(false)),
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.chars.get(self.index) != Some(&'"') {
+            return None;
+        }
+        self.index += 1;
+        let mut string = String::new();
+        loop {
+            let c = *self.chars.get(self.index)?;
+            self.index += 1;
+            match c {
+                '"' => return Some(string),
+                '\\' => {
+                    let escape = *self.chars.get(self.index)?;
+                    self.index += 1;
+                    string.push(match escape {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => {
+                            let digits: String =
+                                self.chars.get(self.index..self.index + 4)?.iter().collect();
+                            self.index += 4;
+                            char::from_u32(u32::from_str_radix(&digits, 16).ok()?)?
+                        }
+                        c => c,
+                    });
+                }
+                c => string.push(c),
+            }
+        }
+    }
+}
+
+// A move in the `zone/square` notation, or `null` for the null move.
+pub fn move_json(mv: Move) -> Json {
+    match mv {
+        NULL_MOVE => Json::Null,
+        mv => Json::String(move_string(mv, Notation::Zone)),
+    }
+}
+
+// The result of a search, such as `{"move":"c/c","depth":4,"eval":"+16","pv":["c/c","c/nw"]}`,
+// where the move is `null` if the game is over.
+pub fn search_json(depth: usize, eval: i32, line: &[Move; MAX_PLY]) -> Json {
+    Json::object(vec![
+        ("move", move_json(line[0])),
+        ("depth", Json::Number(depth as i64)),
+        ("eval", Json::String(eval_string(eval, depth))),
+        (
+            "pv",
+            Json::Array(
+                line.iter()
+                    .take_while(|&&mv| mv != NULL_MOVE)
+                    .map(|&mv| move_json(mv))
+                    .collect(),
+            ),
+        ),
+    ])
+}
@@ -0,0 +1,32 @@
+// The engine itself, without the command protocol, so that it can be used by other programs
+// such as the benchmarks in `benches/`.
+pub mod adjudicate;
+pub mod book;
+pub mod clock;
+pub mod engine;
+pub mod grid;
+pub mod interrupt;
+pub mod json;
+pub mod notation;
+pub mod protocol;
+pub mod random;
+pub mod rules;
+pub mod skill;
+pub mod stats;
+pub mod symmetry;
+pub mod timeman;
+pub mod tt;
+pub mod zobrist;
+
+// The exports of the WebAssembly build and the Python bindings,
+// which only exist when targeting WebAssembly or with the `python` feature.
+#[cfg(any(target_arch = "wasm32", feature = "python"))]
+pub mod exports;
+
+// The session manager for chat bots, which only exists with the `bot` feature.
+#[cfg(feature = "bot")]
+pub mod bot;
+
+// The C API, which only exists with the `ffi` feature.
+#[cfg(feature = "ffi")]
+pub mod ffi;
@@ -1,376 +1,1340 @@
-use std::io::stdin;
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::fs::{read, write};
+use std::io::{stdin, stdout, ErrorKind, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::adjudicate::{AdjudicationSettings, Adjudicator, Verdict};
+use crate::editor::{Editor, EditorAction};
 use crate::engine::*;
+use crate::interrupt::{install_handler, interrupted};
+use crate::playvs::PlayVs;
+use crate::rules::{FirstMove, Variant, WonZones};
+use crate::skill::{Skill, MAX_SKILL, MIN_SKILL};
+use crate::timeman::TimeManager;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB, MAX_HASH_MB};
 
-pub mod engine;
-
-// Arrays to readily convert integers in the 0-8 range to the
-// name of their corresponding zone.
-const ZONE_ARRAY_UPPER: [&str; 9] = ["NW", "N", "NE", "W", "C", "E", "SW", "S", "SE"];
-const ZONE_ARRAY_LOWER: [&str; 9] = ["nw", "n", "ne", "w", "c", "e", "sw", "s", "se"];
-
-// Used to output an ASCII art representation of the board.
-fn print_board(board: Board) {
-    // Destructure and retrieve values from board.
-    let (us, them, share) = board;
-    let zone = (share >> 54) & 0b1111;
-
-    // Map each of the bits to the coresponding string representations.
-    // That is, "X" for Player X, "O" for Player O, and "." for non-occupied.
-    let small = (0..63)
-        .map(|i| {
-            if ((us >> i) & 1) == 1 {
-                "X".to_string()
-            } else if ((them >> i) & 1) == 1 {
-                "O".to_string()
-            } else {
-                ".".to_string()
-            }
+// The engine is shared with the library, while the modules below only concern the command protocol.
+use ut3b2l::book::Book;
+use ut3b2l::notation::*;
+use ut3b2l::protocol::{self, JsonLines, Native, Protocol, Ugi};
+use ut3b2l::random::Random;
+use ut3b2l::stats::{self, Counter};
+use ut3b2l::{adjudicate, engine, grid, interrupt, rules, skill, symmetry, timeman, tt, zobrist};
+
+pub mod annotate;
+pub mod arena;
+pub mod bench;
+pub mod cluster;
+pub mod codingame;
+pub mod dataset;
+pub mod db;
+pub mod display;
+pub mod editor;
+pub mod http;
+pub mod metrics;
+pub mod openings;
+pub mod perft;
+pub mod playvs;
+pub mod puzzles;
+pub mod relay;
+pub mod repertoire;
+pub mod selfplay;
+pub mod socket;
+pub mod sprt;
+pub mod testsuite;
+pub mod tournament;
+pub mod train;
+pub mod tui;
+pub mod verify;
+pub mod websocket;
+
+// How often the main loop checks for an interrupt while waiting for a command.
+const INTERRUPT_POLL: Duration = Duration::from_millis(50);
+
+// A subcommand given as the first argument, such as `ut3b2l match ...`, which runs on its own
+// and returns a description of anything that stopped it.
+type Subcommand = fn(&[String]) -> Result<(), String>;
+
+const SUBCOMMANDS: [(&str, Subcommand); 12] = [
+    ("bench", bench::run),
+    ("cluster", cluster::run),
+    ("db", db::run),
+    ("grid", grid::run),
+    ("match", arena::run),
+    ("openings", openings::run),
+    ("relay", relay::run),
+    ("repertoire", repertoire::run),
+    ("selfplay", selfplay::run),
+    ("sprt", sprt::run),
+    ("tournament", tournament::run),
+    ("train", train::run),
+];
+
+// The address served by `--web` when none is given.
+const WEB_ADDRESS: &str = "127.0.0.1:8080";
+
+// The formats that `info` lines can be output in, selected with `setoption InfoFormat`.
+// `Text` separates keys and values with spaces, as in `info depth 4 pv nw/nw nw/ne`,
+// while `KeyValue` writes each field as `key=value`, as in `info depth=4 pv=nw/nw,nw/ne`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum InfoFormat {
+    Text,
+    KeyValue,
+}
+
+// Outputs an `info` line made up of the given fields, each a key followed by its value.
+// In the `KeyValue` format, the words of a value are joined by commas so that it contains no spaces.
+fn print_info(format: InfoFormat, fields: &[(&str, String)]) {
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|(key, value)| match format {
+            InfoFormat::Text => format!("{} {}", key, value),
+            InfoFormat::KeyValue => format!(
+                "{}={}",
+                key,
+                value.split_whitespace().collect::<Vec<_>>().join(",")
+            ),
         })
-        .chain((0..18).map(|i| {
-            if ((share >> i) & 1) == 1 {
-                "X".to_string()
-            } else if ((share >> (i + 18)) & 1) == 1 {
-                "O".to_string()
-            } else {
-                ".".to_string()
-            }
-        }))
-        .collect::<Vec<_>>();
-
-    // Similar mapping for large grid.
-    let large = (0..9)
-        .map(|i| {
-            if ((share >> (i + 36)) & 1) == 1 {
-                "X".to_string()
-            } else if ((share >> (i + 45)) & 1) == 1 {
-                "O".to_string()
-            } else {
-                ".".to_string()
+        .collect();
+    println!("info {}", fields.join(" "));
+}
+
+// The default depth searched by the `hint` command.
+const HINT_DEPTH: usize = 6;
+
+// The default depth and number of moves shown by the `explain` command.
+const EXPLAIN_DEPTH: usize = 4;
+const EXPLAIN_COUNT: usize = 3;
+
+// The evaluation, depth and time in milliseconds of the search that chose a move.
+type SearchRecord = (i32, usize, u128);
+
+// How far a search started by `go` may go.
+enum SearchLimit {
+    Depth(usize),
+    MoveTime(TimeManager),
+    Mate(usize),
+}
+
+// The depth, evaluation and principal variation found by a search,
+// or why a search for a forced win found none, as `none` or `stopped`.
+type SearchOutcome = Result<(usize, i32, [u64; MAX_PLY]), &'static str>;
+
+// Everything the main loop waits on, from either the input thread or the search thread.
+// The outcome of a search is boxed, since the principal variation is much larger than a command.
+enum Event {
+    Command(String),
+    InputClosed,
+    SearchDone(Box<SearchOutcome>),
+}
+
+// A search running on its own thread, together with the flag that stops it early.
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    start: Instant,
+}
+
+// The commands that are answered immediately while a search is running, unless others are queued before them.
+// All others are queued, and handled in order once the search has finished,
+// apart from `q` and `quit`, which stop the search and exit once it has finished.
+const SEARCH_COMMANDS: [&str; 5] = ["stop", "isready", "d", "gamepos", "sidetomove"];
+
+// Whether a line of input is one of the commands answered immediately while a search is running.
+fn is_search_command(line: &str) -> bool {
+    line.split_whitespace()
+        .next()
+        .is_some_and(|word| SEARCH_COMMANDS.contains(&word))
+}
+
+// Starts a search on a new thread, which sends its outcome to the main loop once finished.
+// The transposition table is kept from search to search, with its entries aging by one search.
+fn start_search(
+    board: Board,
+    side: bool,
+    limit: SearchLimit,
+    table: &Arc<TranspositionTable>,
+    iir: IirSettings,
+    mut skill: Skill,
+    events: Sender<Event>,
+) -> RunningSearch {
+    let stop = Arc::new(AtomicBool::new(false));
+    table.new_search();
+    let mut control = SearchControl::new(None)
+        .with_stop_request(stop.clone())
+        .with_table(table.clone())
+        .with_iir(iir);
+    thread::spawn(move || {
+        let outcome = match limit {
+            // Below full strength, the depth is capped, and a move time gives the deepest search the level allows.
+            SearchLimit::Depth(depth) if !skill.is_full_strength() => {
+                Ok(skill.search(board, side, depth, None, &mut control))
             }
-        })
-        .collect::<Vec<_>>();
-
-    // After organising occupancies into Vec, iterate through and print.
-    println!("---+---+---");
-    for i in (0..81).step_by(27) {
-        for j in (0..9).step_by(3) {
-            println!(
-                "{}",
-                (0..27)
-                    .step_by(9)
-                    .map(|k| small[i + j + k..i + j + k + 3].join(""))
-                    .collect::<Vec<_>>()
-                    .join("|")
-            );
-        }
-        println!("---+---+---");
-    }
-    for i in (0..9).step_by(3) {
-        println!("{}", large[i..i + 3].join(""));
+            SearchLimit::MoveTime(time) if !skill.is_full_strength() => {
+                Ok(skill.search(board, side, MAX_PLY, Some(&time), &mut control))
+            }
+            // A fixed depth is still searched iteratively, so that a stopped search has a result.
+            SearchLimit::Depth(depth) => {
+                Ok(iterative_deepening(board, side, depth, None, &mut control))
+            }
+            SearchLimit::MoveTime(time) => Ok(iterative_deepening(
+                board,
+                side,
+                MAX_PLY,
+                Some(&time),
+                &mut control,
+            )),
+            SearchLimit::Mate(moves) => match mate_search(board, side, moves, &mut control) {
+                MateResult::Found(plies, line) => Ok((plies, OUTCOME_WIN - plies as i32, *line)),
+                MateResult::NoMate => Err("none"),
+                MateResult::Stopped => Err("stopped"),
+            },
+        };
+        events.send(Event::SearchDone(Box::new(outcome))).ok();
+    });
+    RunningSearch {
+        stop,
+        start: Instant::now(),
     }
-    println!(
-        "ZONE: {}",
-        if zone == ZONE_ANY {
-            "ANY"
-        } else {
-            ZONE_ARRAY_UPPER[zone as usize]
+}
+
+// The default depth searched for each move by the `autoplay` command.
+const AUTOPLAY_DEPTH: usize = 6;
+
+/**
+ * Searches a position on the calling thread, as `go` would, to the given depth or for the given time in milliseconds,
+ * at the given skill level, for the commands that play moves of their own. Returns the principal variation with the search that found it.
+ */
+fn search_now(
+    board: Board,
+    side: bool,
+    depth: usize,
+    movetime: Option<u64>,
+    table: &Arc<TranspositionTable>,
+    iir: IirSettings,
+    skill: &mut Skill,
+) -> ([Move; MAX_PLY], SearchRecord) {
+    let start = Instant::now();
+    table.new_search();
+    let mut control = SearchControl::new(None)
+        .with_table(table.clone())
+        .with_iir(iir);
+    let time = movetime.map(TimeManager::new);
+    let (depth, eval, line) = if skill.is_full_strength() {
+        iterative_deepening(board, side, depth, time.as_ref(), &mut control)
+    } else {
+        skill.search(board, side, depth, time.as_ref(), &mut control)
+    };
+    (line, (eval, depth, start.elapsed().as_millis()))
+}
+
+/**
+ * Plays both sides from the last position of the game history until the game ends, adding each move to the history
+ * as `go` does, and outputs each move as it is played, followed by the result.
+ * Each move is searched to the given depth, or for the given time in milliseconds.
+ * An interrupt ends the game early, without playing the move being searched.
+ */
+fn autoplay(
+    history: &mut Vec<(Board, Move, bool, Option<SearchRecord>)>,
+    depth: usize,
+    movetime: Option<u64>,
+    table: &Arc<TranspositionTable>,
+    iir: IirSettings,
+    skill: &mut Skill,
+    notation: Notation,
+) {
+    loop {
+        let (board, _, side, _) = *history.last().unwrap();
+        if let Some(result) = arena::game_result(board) {
+            println!("autoplay result {}", result.score_string());
+            return;
+        }
+        let (line, search) = search_now(board, side, depth, movetime, table, iir, skill);
+        if interrupted() {
+            return;
         }
-    );
+        let (eval, depth, duration) = search;
+        println!(
+            "autoplay ply {} {} move {} eval {} depth {} time {}",
+            history.len(),
+            player_string(side),
+            move_string(line[0], notation),
+            eval_string(eval, depth),
+            depth,
+            duration
+        );
+        history.push((
+            play_move(board, line[0], side),
+            line[0],
+            !side,
+            Some(search),
+        ));
+    }
 }
 
-// Converts a `u64` move representation to a string.
-fn move_string(mv: Move) -> String {
-    format!(
-        "{0}/{1}",
-        ZONE_ARRAY_LOWER[(mv / 9) as usize],
-        ZONE_ARRAY_LOWER[(mv % 9) as usize]
-    )
+// Produces a CSV table with a row for every move in the game history,
+// with the result of the search that chose the move if the engine played it.
+// The `eval` column is from the perspective of the side that moved, as in the output of `go`,
+// while the `score` column is always from the perspective of Player X, for plotting.
+fn eval_graph_csv(
+    history: &[(Board, Move, bool, Option<SearchRecord>)],
+    notation: Notation,
+) -> String {
+    let mut csv = "ply,move,side,eval,score,depth,time\n".to_string();
+    for (ply, (_, mv, side, search)) in history.iter().enumerate().skip(1) {
+        // The side stored with each entry is the side to move after the move was made.
+        let mover = !side;
+        csv += &format!(
+            "{},{},{},",
+            ply,
+            if *mv == NULL_MOVE {
+                "null".to_string()
+            } else {
+                move_string(*mv, notation)
+            },
+            player_string(mover)
+        );
+        csv += &match search {
+            Some((eval, depth, time)) => format!(
+                "{},{},{},{}\n",
+                eval_string(*eval, *depth),
+                if mover { -eval } else { *eval },
+                depth,
+                time
+            ),
+            None => ",,,\n".to_string(),
+        };
+    }
+    csv
 }
 
-// Returns the internal move representation from its string representation.
-fn move_from_string(move_string: &str) -> Option<Move> {
-    let zone_and_square: Vec<_> = move_string.split('/').collect();
-    if zone_and_square.len() != 2 {
-        return None;
+// Describes why a position was rejected, for use in command responses.
+fn position_error_string(error: PositionError) -> String {
+    match error {
+        PositionError::PieceCount => "count".to_string(),
+        PositionError::SubBoard(zone) => format!("subboard {}", ZONE_ARRAY_LOWER[zone as usize]),
+        PositionError::BothWon => "bothwon".to_string(),
+        PositionError::ClosedZone => "zone".to_string(),
     }
-    let zone = ZONE_ARRAY_LOWER
-        .iter()
-        .position(|&z| z == zone_and_square[0]);
-    let square = ZONE_ARRAY_LOWER
-        .iter()
-        .position(|&s| s == zone_and_square[1]);
-    if let (Some(z), Some(s)) = (zone, square) {
-        Some(9 * z as u64 + s as u64)
-    } else {
-        None
+}
+
+// Describes why a move was rejected, for use in command responses.
+fn illegal_move_string(error: IllegalMove) -> String {
+    match error {
+        IllegalMove::GameOver => "gameover".to_string(),
+        IllegalMove::WrongZone(zone) => format!("zone {}", ZONE_ARRAY_LOWER[zone as usize]),
+        IllegalMove::ClosedZone => "closed".to_string(),
+        IllegalMove::Occupied => "occupied".to_string(),
+        IllegalMove::FirstMove => "firstmove".to_string(),
     }
 }
 
-// Converts a `i32` heuristic evaluation value to a string.
-fn eval_string(eval: i32, max_depth: usize) -> String {
-    if eval <= OUTCOME_LOSS + max_depth as i32 {
-        format!("L{0}", eval - OUTCOME_LOSS)
-    } else if eval >= OUTCOME_WIN - max_depth as i32 {
-        format!("W{0}", OUTCOME_WIN - eval)
-    } else if eval == OUTCOME_DRAW {
-        "D0".to_string()
-    } else {
-        format!("{:+0}", eval)
+// Describes why a handicap was rejected, for use in command responses.
+fn handicap_error_string(error: HandicapError) -> String {
+    match error {
+        HandicapError::Count => "count".to_string(),
+        HandicapError::Occupied(mv) => format!("occupied {}", move_string(mv, Notation::Zone)),
+        HandicapError::Line(zone) => format!("line {}", ZONE_ARRAY_LOWER[zone as usize]),
     }
 }
 
-// Compressed inline string representation for compact passing of Board setups.
-fn board_string(board: Board) -> String {
-    let (us, them, share) = board;
-    let zone = (share >> 54) & 0b1111;
-    let cells = (0..81).step_by(27).flat_map(move |i| {
-        (0..9).step_by(3).map(move |j| {
-            (0..27)
-                .step_by(9)
-                .flat_map(move |k| i + j + k..i + j + k + 3)
-        })
-    });
-    format!(
-        "{} {}",
-        cells
-            .map(|v| v
-                .map(|i| {
-                    if i > 62 {
-                        if ((share >> (i - 63)) & 1) == 1 {
-                            "x".to_string()
-                        } else if ((share >> (i - 45)) & 1) == 1 {
-                            "o".to_string()
-                        } else {
-                            ".".to_string()
-                        }
-                    } else if ((us >> i) & 1) == 1 {
-                        "x".to_string()
-                    } else if ((them >> i) & 1) == 1 {
-                        "o".to_string()
-                    } else {
-                        ".".to_string()
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join(""))
-            .collect::<Vec<_>>()
-            .join("/")
-            .replace(".........", "9")
-            .replace("........", "8")
-            .replace(".......", "7")
-            .replace("......", "6")
-            .replace(".....", "5")
-            .replace("....", "4")
-            .replace("...", "3")
-            .replace("..", "2")
-            .replace('.', "1"),
-        if zone == ZONE_ANY {
-            "any"
-        } else {
-            ZONE_ARRAY_LOWER[zone as usize]
-        }
-    )
+/**
+ * Applies the pie rule to the game history, if it is in force and only the first move of a game
+ * from the empty board has been made, returning whether it did.
+ * The second player takes over the first move, which becomes theirs, and the first player moves again,
+ * so the history is rewritten as the game in which the second player made that move,
+ * keeping the board consistent with the side to move and every command that replays the history.
+ * The rewritten game starts with the second player to move, so it cannot be swapped again.
+ */
+fn swap_first_move(history: &mut Vec<(Board, Move, bool, Option<SearchRecord>)>) -> bool {
+    let &[(start, _, false, _), (_, mv, true, search)] = history.as_slice() else {
+        return false;
+    };
+    if !rules::pie() || mv == NULL_MOVE || start != (0, 0, ZONE_ANY << 54) {
+        return false;
+    }
+    *history = vec![
+        (start, NULL_MOVE, true, None),
+        (play_move(start, mv, true), mv, false, search),
+    ];
+    true
 }
 
-// Returns an internal board representation from its string representation.
-fn board_from_string(board_string: &str) -> Option<Board> {
-    let (mut us, mut them, mut share) = (0u64, 0u64, 0u64);
-    let decompressed_string = board_string
-        .replace('1', ".")
-        .replace('2', "..")
-        .replace('3', "...")
-        .replace('4', "....")
-        .replace('5', ".....")
-        .replace('6', "......")
-        .replace('7', ".......")
-        .replace('8', "........")
-        .replace('9', ".........");
-    let cell_and_zone: Vec<_> = decompressed_string.split_whitespace().collect();
-    if cell_and_zone.len() != 2 {
-        return None;
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some((_, run)) = SUBCOMMANDS
+        .iter()
+        .find(|(name, _)| args.get(1).map(String::as_str) == Some(*name))
+    {
+        if let Err(error) = run(&args[2..]) {
+            eprintln!("{}", error);
+        }
+        return;
     }
-    let (cell, zone) = (cell_and_zone[0], cell_and_zone[1]);
-    if let Some(z) = ZONE_ARRAY_LOWER.iter().position(|&z| z == zone) {
-        share |= (z as u64) << 54;
-    } else if zone == "any" {
-        share |= ZONE_ANY << 54;
-    } else {
-        return None;
+
+    // The CodinGame protocol and the terminal interface replace the usual command protocol entirely.
+    if std::env::args().any(|arg| arg == "--codingame") {
+        codingame::run();
+        return;
     }
-    let rows: Vec<_> = cell.split('/').collect();
-    if rows.len() != 9 {
-        return None;
+    // As a WebSocket server, each connection runs the command protocol in its own process,
+    // or the protocol given with `--protocol`, unless the connection asks for another.
+    if let Some(i) = args.iter().position(|arg| arg == "--serve-ws") {
+        let protocol = match args.iter().position(|arg| arg == "--protocol") {
+            Some(j) => args.get(j + 1).map_or("", String::as_str),
+            None => "native",
+        };
+        if !websocket::PROTOCOLS.contains(&protocol) {
+            eprintln!("invalid protocol");
+            return;
+        }
+        match args.get(i + 1) {
+            Some(address) => websocket::serve(address, protocol),
+            None => eprintln!("invalid address"),
+        }
+        return;
     }
-    if rows.iter().any(|row| row.len() != 9) {
-        return None;
+
+    // Another frontend protocol is spoken by running the engine as a child process behind an adapter.
+    if let Some(i) = args.iter().position(|arg| arg == "--protocol") {
+        let mut protocol: Box<dyn Protocol> = match args.get(i + 1).map(String::as_str) {
+            Some("native") => Box::new(Native),
+            Some("ugi") => Box::new(Ugi::default()),
+            Some("json") => Box::new(JsonLines),
+            _ => {
+                eprintln!("invalid protocol");
+                return;
+            }
+        };
+        let result = std::env::current_exe()
+            .and_then(|engine| protocol::run(&mut *protocol, &engine.to_string_lossy()));
+        if let Err(error) = result {
+            eprintln!("cannot run the engine: {}", error);
+        }
+        return;
     }
-    decompressed_string
-        .replace('/', "")
-        .chars()
-        .zip((0..81).step_by(27).flat_map(move |i| {
-            (0..9).step_by(3).flat_map(move |j| {
-                (0..27)
-                    .step_by(9)
-                    .flat_map(move |k| i + j + k..i + j + k + 3)
-            })
-        }))
-        .for_each(|(c, i)| {
-            if i > 62 {
-                if c == 'x' {
-                    share |= 1 << (i - 63);
-                } else if c == 'o' {
-                    share |= 1 << (i - 45);
-                }
-            } else if c == 'x' {
-                us |= 1 << i;
-            } else if c == 'o' {
-                them |= 1 << i;
-            }
-        });
-    let first_seven_us = us;
-    let first_seven_them = them;
-    for i in 0..7 {
-        if line_presence(first_seven_us >> (9 * i)) {
-            share |= 1 << (36 + i);
-        } else if line_presence(first_seven_them >> (9 * i)) {
-            share |= 1 << (45 + i);
+
+    // Messages typed on standard input are handled as if posted in a single channel, to try out a chat bot.
+    #[cfg(feature = "bot")]
+    if std::env::args().any(|arg| arg == "--bot-console") {
+        let mut sessions = ut3b2l::bot::SessionManager::new();
+        for line in stdin().lines().map_while(Result::ok) {
+            if let Some(reply) = sessions.handle(0, &line) {
+                println!("{}", reply);
+            }
         }
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--tui") {
+        tui::run();
+        return;
     }
-    let last_two_us = share;
-    let last_two_them = share >> 18;
-    for i in 7..9 {
-        if line_presence(last_two_us >> (9 * i - 63)) {
-            share |= 1 << (36 + i);
-        } else if line_presence(last_two_them >> (9 * i - 63)) {
-            share |= 1 << (45 + i);
+
+    // As an HTTP server, games are held in this process and driven by the JSON API,
+    // as a local socket server, a single engine process is shared by the clients that attach to it in turn,
+    // and as a worker, each connection runs the command protocol over plain TCP for a coordinator.
+    for (flag, serve) in [
+        ("--serve-http", http::serve as fn(&str)),
+        ("--serve-socket", socket::serve),
+        ("--worker", cluster::serve_worker),
+    ] {
+        if let Some(i) = args.iter().position(|arg| arg == flag) {
+            match args.get(i + 1) {
+                Some(address) => serve(address),
+                None => eprintln!("invalid address"),
+            }
+            return;
         }
     }
-    Some((us, them, share))
-}
 
-fn main() {
-    let mut history: Vec<(Board, Move)> = Vec::new();
+    // The web interface is the HTTP server on a local address, unless another is given.
+    if let Some(i) = args.iter().position(|arg| arg == "--web") {
+        let address = args.get(i + 1).map_or(WEB_ADDRESS, |address| address);
+        eprintln!("open http://{}/ in a browser", address);
+        http::serve(address);
+        return;
+    }
+
+    // The number of threads used by `perft` defaults to the number of available cores,
+    // and can be overridden with `--threads <count>`.
+    let threads = match args.iter().position(|arg| arg == "--threads") {
+        Some(i) => match args.get(i + 1).map(|count| count.parse::<usize>()) {
+            Some(Ok(count)) if count > 0 => count,
+            _ => {
+                eprintln!("invalid threads");
+                return;
+            }
+        },
+        None => thread::available_parallelism().map_or(1, |count| count.get()),
+    };
 
-    history.push(((0, 0, ZONE_ANY << 54), NULL_MOVE));
+    // Each entry holds a position, the move that led to it, the side to move in it,
+    // and the result of the search that chose the move, if the engine played it.
+    let mut history: Vec<(Board, Move, bool, Option<SearchRecord>)> = Vec::new();
+
+    history.push(((0, 0, ZONE_ANY << 54), NULL_MOVE, false, None));
 
     let mut command_string: String;
     let mut command: Vec<String>;
 
+    // Engine options, changed with the `setoption` command.
+    let mut notation = Notation::Zone;
+    let mut info_format = InfoFormat::Text;
+    let mut color = stdout().is_terminal();
+    let mut adjudicator = Adjudicator::new(AdjudicationSettings::default());
+    let mut hash_mb = DEFAULT_HASH_MB;
+    let mut replacement = Replacement::Aging;
+    let mut table = Arc::new(TranspositionTable::new(hash_mb, replacement));
+    let mut iir = IirSettings::default();
+    let mut skill = Skill::new(MAX_SKILL, Random::clock_seed());
+
+    // While a position is being edited, all commands are handled by the editor,
+    // and during a game against the engine, by the game.
+    let mut editor: Option<Editor> = None;
+    let mut playvs: Option<PlayVs> = None;
+
+    install_handler();
+
+    // Commands are read on a separate thread, so that the main loop can notice an interrupt
+    // or a finished search while waiting for input.
+    let (sender, receiver) = channel();
+    let input_sender = sender.clone();
+    thread::spawn(move || {
+        for line in stdin().lines() {
+            match line {
+                Ok(line) => {
+                    if input_sender.send(Event::Command(line)).is_err() {
+                        return;
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::InvalidData => continue,
+                Err(_) => break,
+            }
+        }
+        input_sender.send(Event::InputClosed).ok();
+    });
+
+    let mut search: Option<RunningSearch> = None;
+    let mut pending: VecDeque<String> = VecDeque::new();
+    // Once input has closed, the loop ends after the queued commands and their searches have finished.
+    let mut input_closed = false;
+    // Set by `q` during a search, after which the loop ends once the search has finished.
+    let mut quitting = false;
+
     println!("ready");
 
     loop {
-        command_string = String::new();
-        match stdin().read_line(&mut command_string) {
-            Ok(_) => {
-                command = command_string
-                    .split_whitespace()
-                    .map(|s| s.to_string())
-                    .collect()
-            }
-            Err(_) => continue,
+        // Once interrupted or quitting, any commands still waiting to be handled are discarded.
+        if interrupted() || (quitting && search.is_none()) {
+            break;
         }
+        // During a search, only the commands answered immediately are taken from the front of the queue,
+        // so a `stop` queued behind a `go` stops the search that `go` starts.
+        let queued = match search {
+            Some(_) if !pending.front().is_some_and(|line| is_search_command(line)) => None,
+            _ => pending.pop_front(),
+        };
+        let from_queue = queued.is_some();
+        command_string = match queued {
+            Some(line) => line,
+            None if input_closed && search.is_none() => break,
+            None => match receiver.recv_timeout(INTERRUPT_POLL) {
+                Ok(Event::Command(line)) => line,
+                Ok(Event::InputClosed) => {
+                    input_closed = true;
+                    continue;
+                }
+                Ok(Event::SearchDone(outcome)) => {
+                    let start = search.take().unwrap().start;
+                    // An interrupted search is incomplete, so its result is not reported.
+                    if interrupted() {
+                        break;
+                    }
+                    let duration = start.elapsed().as_millis();
+                    let (depth, eval, line) = match *outcome {
+                        Ok(found) => found,
+                        Err(reason) => {
+                            print_info(
+                                info_format,
+                                &[("mate", reason.to_string()), ("time", duration.to_string())],
+                            );
+                            continue;
+                        }
+                    };
+                    let (board, _, current_player, _) = *history.last().unwrap();
+                    print_info(
+                        info_format,
+                        &[
+                            ("depth", depth.to_string()),
+                            ("pv", pv_string(&line, notation)),
+                            ("eval", eval_string(eval, depth)),
+                            ("time", duration.to_string()),
+                        ],
+                    );
+                    // A finished game has no move to play, so only the result is reported.
+                    if line[0] == NULL_MOVE {
+                        continue;
+                    }
+                    history.push((
+                        play_move(board, line[0], current_player),
+                        line[0],
+                        !current_player,
+                        Some((eval, depth, duration)),
+                    ));
+                    let verdict = match adjudicator.record(current_player, eval) {
+                        Some(Verdict::Resign(player)) => Some(("resign", player)),
+                        Some(Verdict::Win(player)) => Some(("win", player)),
+                        None => None,
+                    };
+                    if let Some((kind, player)) = verdict {
+                        print_info(
+                            info_format,
+                            &[("adjudicate", format!("{} {}", kind, player_string(player)))],
+                        );
+                    }
+                    continue;
+                }
+                Err(_) => continue,
+            },
+        };
+        command = command_string
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect();
         if command.is_empty() {
             continue;
         }
+        if let Some(running) = &search {
+            if command[0] == "q" || command[0] == "quit" {
+                quitting = true;
+                running.stop.store(true, Ordering::Relaxed);
+                continue;
+            }
+            // Commands answered immediately still wait behind queued ones, so every command is handled in order.
+            if !SEARCH_COMMANDS.contains(&(&command[0] as &str))
+                || !from_queue && !pending.is_empty()
+            {
+                pending.push_back(command_string);
+                continue;
+            }
+        }
+        if let Some(game) = &mut playvs {
+            if game.execute(&command, &mut history, &table, iir, &mut skill) {
+                adjudicator.reset();
+                playvs = None;
+            }
+            continue;
+        }
+        if let Some(current_editor) = &mut editor {
+            match current_editor.execute(&command) {
+                EditorAction::Continue => {}
+                EditorAction::Commit(new_board, side) => {
+                    history.clear();
+                    history.push((new_board, NULL_MOVE, side, None));
+                    adjudicator.reset();
+                    table.clear();
+                    editor = None;
+                }
+                EditorAction::Cancel => editor = None,
+                EditorAction::Quit => break,
+            }
+            continue;
+        }
         match &command[0] as &str {
             "newgame" => {
                 if command.len() < 3 {
                     println!("newgame invalid args");
                     continue;
                 }
-                if let Some(new_board) =
-                    board_from_string(&format!("{} {}", &command[1], &command[2]))
+                // The side to move is the optional third field of the position string.
+                if let Some((new_board, side)) =
+                    board_from_string(&command[1..command.len().min(4)].join(" "))
                 {
-                    history.clear();
-                    history.push((new_board, NULL_MOVE));
-                    println!("newgame ok");
+                    match validate_position(new_board, side) {
+                        Ok(()) => {
+                            history.clear();
+                            history.push((new_board, NULL_MOVE, side, None));
+                            adjudicator.reset();
+                            table.clear();
+                            println!("newgame ok");
+                        }
+                        Err(error) => println!("newgame invalid {}", position_error_string(error)),
+                    }
                 } else {
                     println!("newgame invalid pos");
                 }
             }
             "go" => {
                 if command.len() < 2 {
-                    println!("info error no depth");
+                    print_info(info_format, &[("error", "no depth".to_string())]);
                     continue;
                 }
-                let current_player = (history.len() & 1) == 0;
-                if let Ok(depth) = command[1].parse::<usize>() {
-                    if depth <= 0 {
-                        println!("info error invalid depth");
+
+                // `go movetime <ms>` searches iteratively deeper until the time runs out,
+                // `go mate <moves>` searches only for a forced win,
+                // while `go <depth>` searches to exactly the given depth.
+                let limit = if command[1] == "mate" {
+                    if command.len() < 3 {
+                        print_info(info_format, &[("error", "no mate".to_string())]);
+                        continue;
+                    }
+                    match command[2].parse::<usize>() {
+                        Ok(moves) if moves > 0 && 2 * moves - 1 <= MAX_PLY => {
+                            SearchLimit::Mate(moves)
+                        }
+                        Ok(moves) if moves > 0 => {
+                            print_info(info_format, &[("error", "overflow mate".to_string())]);
+                            continue;
+                        }
+                        _ => {
+                            print_info(info_format, &[("error", "invalid mate".to_string())]);
+                            continue;
+                        }
+                    }
+                } else if command[1] == "movetime" {
+                    if command.len() < 3 {
+                        print_info(info_format, &[("error", "no movetime".to_string())]);
+                        continue;
+                    }
+                    match command[2].parse::<u64>() {
+                        Ok(movetime) if movetime > 0 => {
+                            SearchLimit::MoveTime(TimeManager::new(movetime))
+                        }
+                        _ => {
+                            print_info(info_format, &[("error", "invalid movetime".to_string())]);
+                            continue;
+                        }
+                    }
+                } else if let Ok(depth) = command[1].parse::<usize>() {
+                    if depth == 0 {
+                        print_info(info_format, &[("error", "invalid depth".to_string())]);
                         continue;
                     }
                     if depth > MAX_PLY {
-                        println!("info error overflow depth");
+                        print_info(info_format, &[("error", "overflow depth".to_string())]);
                         continue;
                     }
-                    let board = history.last().unwrap().0;
-                    let start = Instant::now();
-                    let (eval, line) = alpha_beta(
-                        board,
-                        current_player,
-                        depth,
-                        OUTCOME_LOSS,
-                        OUTCOME_WIN,
-                        depth,
-                    );
-                    let duration = start.elapsed().as_millis();
-                    println!(
-                        "info depth {} pv {} eval {} time {}",
-                        depth,
-                        line.iter()
-                            .take_while(|&&m| m != NULL_MOVE)
-                            .map(|m| move_string(*m))
-                            .collect::<Vec<_>>()
-                            .join(" "),
-                        eval_string(eval, depth),
-                        duration
-                    );
-                    history.push((play_move(board, line[0], current_player), line[0]));
+                    SearchLimit::Depth(depth)
                 } else {
-                    println!("info error invalid depth");
+                    print_info(info_format, &[("error", "invalid depth".to_string())]);
+                    continue;
+                };
+                // The result is reported, and the move played, once the search thread has finished.
+                let (board, _, current_player, _) = *history.last().unwrap();
+                search = Some(start_search(
+                    board,
+                    current_player,
+                    limit,
+                    &table,
+                    iir,
+                    skill.fork(),
+                    sender.clone(),
+                ));
+            }
+            "stop" => {
+                if let Some(running) = &search {
+                    running.stop.store(true, Ordering::Relaxed);
                 }
             }
+            "isready" => println!("readyok"),
             "play" => {
-                if command.len() != 2 {
-                    println!("move invalid");
-                    continue;
-                }
-                if command[1] == "null" {
-                    let (last_board, last_move) = *history.last().clone().unwrap();
-                    history.push((last_board, last_move));
-                    println!("move pos {}", board_string(last_board));
-                } else if let Some(mv) = move_from_string(&command[1]) {
-                    let board = history.last().unwrap().0;
-                    if generate_moves(board).find(|&m| m == mv).is_some() {
-                        let current_player = (history.len() & 1) == 0;
-                        let new_board = play_move(board, mv, current_player);
-                        history.push((new_board, mv));
-                        println!("move pos {}", board_string(new_board));
-                    } else {
-                        println!("move illegal");
+                // A move given as two numbers is read as a row and column, as in `play 4 6`.
+                let parsed_move = match command.len() {
+                    2 => move_from_string(&command[1]),
+                    3 => move_from_row_col_string(&command[1], &command[2]),
+                    _ => {
+                        println!("move invalid");
+                        continue;
+                    }
+                };
+                if command.len() == 2 && command[1] == "null" {
+                    // A null move passes the turn to the other side without changing the board.
+                    let (last_board, _, side, _) = *history.last().unwrap();
+                    history.push((last_board, NULL_MOVE, !side, None));
+                    println!("move pos {}", board_string(last_board, !side));
+                } else if let Some(mv) = parsed_move {
+                    let (board, _, current_player, _) = *history.last().unwrap();
+                    match check_move(board, mv) {
+                        Ok(()) => {
+                            let new_board = play_move(board, mv, current_player);
+                            history.push((new_board, mv, !current_player, None));
+                            println!("move pos {}", board_string(new_board, !current_player));
+                        }
+                        Err(error) => println!("move illegal {}", illegal_move_string(error)),
                     }
                 } else {
                     println!("move invalid");
                 }
             }
             "undo" => {
-                if let Some((last_board, last_move)) = history.pop() {
+                if let Some(last_entry) = history.pop() {
                     if history.is_empty() {
-                        history.push((last_board, last_move));
+                        history.push(last_entry);
                         println!("undo stackempty");
                     } else {
+                        adjudicator.reset();
                         println!("undo ok");
                     }
                 } else {
                     println!("undo stackempty");
                 }
             }
-            "gamepos" => println!("{}", board_string(history.last().unwrap().0)),
-            "d" => print_board(history.last().unwrap().0),
-            "q" => break,
+            // The side given the cells is the weaker player, and their opponent moves first.
+            "handicap" => {
+                let side = match command.get(1).map(String::as_str) {
+                    Some("x") => false,
+                    Some("o") => true,
+                    Some(_) => {
+                        println!("handicap invalid side");
+                        continue;
+                    }
+                    None => {
+                        println!("handicap invalid args");
+                        continue;
+                    }
+                };
+                let Some(cells) = command[2..]
+                    .iter()
+                    .map(|mv| move_from_string(mv))
+                    .collect::<Option<Vec<Move>>>()
+                else {
+                    println!("handicap invalid move");
+                    continue;
+                };
+                match handicap_board(side, &cells) {
+                    Ok(new_board) => {
+                        history.clear();
+                        history.push((new_board, NULL_MOVE, !side, None));
+                        adjudicator.reset();
+                        table.clear();
+                        println!("handicap pos {}", board_string(new_board, !side));
+                    }
+                    Err(error) => println!("handicap invalid {}", handicap_error_string(error)),
+                }
+            }
+            "swap" => {
+                if !rules::pie() {
+                    println!("swap disabled");
+                } else if swap_first_move(&mut history) {
+                    adjudicator.reset();
+                    let (board, _, side, _) = *history.last().unwrap();
+                    println!("swap pos {}", board_string(board, side));
+                } else {
+                    println!("swap illegal");
+                }
+            }
+            "testsuite" => {
+                if command.len() < 3 {
+                    println!("testsuite invalid args");
+                    continue;
+                }
+                match command[2].parse::<usize>() {
+                    Ok(depth) if depth > 0 && depth <= MAX_PLY => {
+                        testsuite::run_testsuite(&command[1], depth, notation)
+                    }
+                    _ => println!("testsuite invalid depth"),
+                }
+            }
+            "bench" => match command.get(1).map(|d| d.parse::<usize>()) {
+                None => bench::run_bench(bench::BENCH_DEPTH),
+                Some(Ok(depth)) if depth > 0 && depth <= MAX_PLY => bench::run_bench(depth),
+                Some(_) => println!("bench invalid depth"),
+            },
+            "stats" => {
+                if !stats::ENABLED {
+                    println!("stats disabled");
+                } else if command.get(1).is_some_and(|arg| arg == "reset") {
+                    stats::reset();
+                    println!("stats ok");
+                } else {
+                    println!(
+                        "stats nodes leaf {} terminal {} cut {} all {}",
+                        stats::total(Counter::LeafNode),
+                        stats::total(Counter::TerminalNode),
+                        stats::total(Counter::CutNode),
+                        stats::total(Counter::AllNode)
+                    );
+                    println!(
+                        "stats cutoffs {}",
+                        stats::cutoff_histogram()
+                            .iter()
+                            .map(|count| count.to_string())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    );
+                    println!(
+                        "stats table hit {} miss {} cutoff {}",
+                        stats::total(Counter::TableHit),
+                        stats::total(Counter::TableMiss),
+                        stats::total(Counter::TableCutoff)
+                    );
+                    println!(
+                        "stats extensions {} reductions {}",
+                        stats::total(Counter::Extension),
+                        stats::total(Counter::Reduction)
+                    );
+                }
+            }
+            "perft" => {
+                if command.len() < 2 {
+                    println!("perft invalid args");
+                    continue;
+                }
+                // `perft divide <depth>` breaks the count down by the first move.
+                let divide = command[1] == "divide";
+                if divide && command.len() < 3 {
+                    println!("perft invalid args");
+                    continue;
+                }
+                let (board, _, side, _) = *history.last().unwrap();
+                match command[if divide { 2 } else { 1 }].parse::<usize>() {
+                    Ok(depth) if depth > 0 && depth <= perft::MAX_PERFT_DEPTH => {
+                        if divide {
+                            perft::run_perft_divide(board, side, depth, threads, notation);
+                        } else {
+                            perft::run_perft(board, side, depth, threads);
+                        }
+                    }
+                    _ => println!("perft invalid depth"),
+                }
+            }
+            "setoption" => {
+                if command.len() < 3 {
+                    println!("setoption invalid args");
+                    continue;
+                }
+                match &command[1].to_lowercase() as &str {
+                    "notation" => match &command[2].to_lowercase() as &str {
+                        "zone" => notation = Notation::Zone,
+                        "rowcol" => notation = Notation::RowCol,
+                        _ => {
+                            println!("setoption invalid value");
+                            continue;
+                        }
+                    },
+                    "infoformat" => match &command[2].to_lowercase() as &str {
+                        "text" => info_format = InfoFormat::Text,
+                        "keyvalue" => info_format = InfoFormat::KeyValue,
+                        _ => {
+                            println!("setoption invalid value");
+                            continue;
+                        }
+                    },
+                    "color" => match &command[2].to_lowercase() as &str {
+                        "on" => color = true,
+                        "off" => color = false,
+                        "auto" => color = stdout().is_terminal(),
+                        _ => {
+                            println!("setoption invalid value");
+                            continue;
+                        }
+                    },
+                    // Changing the table in any way empties it.
+                    "hash" => match command[2].parse::<usize>() {
+                        Ok(megabytes) if megabytes > 0 && megabytes <= MAX_HASH_MB => {
+                            hash_mb = megabytes;
+                            table = Arc::new(TranspositionTable::new(hash_mb, replacement));
+                        }
+                        _ => {
+                            println!("setoption invalid value");
+                            continue;
+                        }
+                    },
+                    "replacement" => {
+                        replacement = match &command[2].to_lowercase() as &str {
+                            "always" => Replacement::Always,
+                            "depth" => Replacement::Depth,
+                            "aging" => Replacement::Aging,
+                            _ => {
+                                println!("setoption invalid value");
+                                continue;
+                            }
+                        };
+                        table = Arc::new(TranspositionTable::new(hash_mb, replacement));
+                    }
+                    // The table holds results found under the old rules, so it is emptied.
+                    "variant" => {
+                        let Some(variant) = Variant::parse(&command[2]) else {
+                            println!("setoption invalid value");
+                            continue;
+                        };
+                        rules::set_variant(variant);
+                        table.clear();
+                    }
+                    "wonzones" => {
+                        let Some(rule) = WonZones::parse(&command[2]) else {
+                            println!("setoption invalid value");
+                            continue;
+                        };
+                        rules::set_won_zones(rule);
+                        table.clear();
+                    }
+                    "firstmove" => {
+                        let Some(rule) = FirstMove::parse(&command[2]) else {
+                            println!("setoption invalid value");
+                            continue;
+                        };
+                        rules::set_first_move(rule);
+                        table.clear();
+                    }
+                    "pie" => match &command[2].to_lowercase() as &str {
+                        "on" => rules::set_pie(true),
+                        "off" => rules::set_pie(false),
+                        _ => {
+                            println!("setoption invalid value");
+                            continue;
+                        }
+                    },
+                    "iir" => match &command[2].to_lowercase() as &str {
+                        "off" => iir.mode = IirMode::Off,
+                        "reduce" => iir.mode = IirMode::Reduce,
+                        "search" => iir.mode = IirMode::Search,
+                        _ => {
+                            println!("setoption invalid value");
+                            continue;
+                        }
+                    },
+                    "iirdepth" => match command[2].parse::<usize>() {
+                        Ok(depth) if (2..=MAX_PLY).contains(&depth) => iir.min_depth = depth,
+                        _ => {
+                            println!("setoption invalid value");
+                            continue;
+                        }
+                    },
+                    "skill" => match command[2].parse::<u32>() {
+                        Ok(level) if (MIN_SKILL..=MAX_SKILL).contains(&level) => {
+                            skill.level = level
+                        }
+                        _ => {
+                            println!("setoption invalid value");
+                            continue;
+                        }
+                    },
+                    // The level is kept, and only the source of its random choices starts again.
+                    "seed" => match command[2].parse::<u64>() {
+                        Ok(seed) => skill = Skill::new(skill.level, seed),
+                        _ => {
+                            println!("setoption invalid value");
+                            continue;
+                        }
+                    },
+                    "resignthreshold"
+                    | "resignmoves"
+                    | "adjudicatethreshold"
+                    | "adjudicatemoves" => {
+                        let value = match command[2].parse::<usize>() {
+                            Ok(value) if value <= OUTCOME_WIN as usize => value,
+                            _ => {
+                                println!("setoption invalid value");
+                                continue;
+                            }
+                        };
+                        let settings = &mut adjudicator.settings;
+                        match &command[1].to_lowercase() as &str {
+                            "resignthreshold" => settings.resign_threshold = value as i32,
+                            "resignmoves" => settings.resign_moves = value,
+                            "adjudicatethreshold" => settings.win_threshold = value as i32,
+                            _ => settings.win_moves = value,
+                        }
+                    }
+                    _ => {
+                        println!("setoption invalid name");
+                        continue;
+                    }
+                }
+                println!("setoption ok");
+            }
+            "edit" => {
+                let (board, _, side, _) = *history.last().unwrap();
+                editor = Some(Editor::new(board, side));
+                println!("edit ok");
+            }
+            "hint" => {
+                let depth = match command.get(1).map(|d| d.parse::<usize>()) {
+                    None => HINT_DEPTH,
+                    Some(Ok(depth)) if depth > 0 && depth <= MAX_PLY => depth,
+                    Some(_) => {
+                        println!("hint invalid depth");
+                        continue;
+                    }
+                };
+                // The search is the same as for `go`, but the game history is left untouched.
+                let (board, _, side, _) = *history.last().unwrap();
+                let (eval, line) = alpha_beta(
+                    board,
+                    side,
+                    depth,
+                    OUTCOME_LOSS,
+                    OUTCOME_WIN,
+                    depth,
+                    &mut SearchControl::new(None),
+                );
+                if line[0] == NULL_MOVE {
+                    println!("hint none");
+                } else {
+                    println!(
+                        "hint move {} eval {} pv {}",
+                        move_string(line[0], notation),
+                        eval_string(eval, depth),
+                        pv_string(&line, notation)
+                    );
+                }
+            }
+            "explain" => {
+                // Both arguments are optional, defaulting to a shallow search of the top few moves.
+                let depth = match command.get(1).map(|d| d.parse::<usize>()) {
+                    None => EXPLAIN_DEPTH,
+                    Some(Ok(depth)) if depth > 0 && depth <= MAX_PLY => depth,
+                    Some(_) => {
+                        println!("explain invalid depth");
+                        continue;
+                    }
+                };
+                let count = match command.get(2).map(|c| c.parse::<usize>()) {
+                    None => EXPLAIN_COUNT,
+                    Some(Ok(count)) if count > 0 => count,
+                    Some(_) => {
+                        println!("explain invalid count");
+                        continue;
+                    }
+                };
+                let (board, _, side, _) = *history.last().unwrap();
+                let scores =
+                    root_move_scores(board, side, depth, None, &mut SearchControl::new(None));
+                if scores.is_empty() {
+                    println!("explain none");
+                }
+                for (rank, (mv, eval, line)) in scores.iter().take(count).enumerate() {
+                    println!(
+                        "explain {} move {} eval {} pv {}",
+                        rank + 1,
+                        move_string(*mv, notation),
+                        eval_string(*eval, depth),
+                        pv_string(line, notation)
+                    );
+                }
+            }
+            "playvs" => {
+                let human = match command.get(1).map(String::as_str) {
+                    Some("x") => false,
+                    Some("o") => true,
+                    _ => {
+                        println!("playvs invalid side");
+                        continue;
+                    }
+                };
+                // The level is given as in `autoplay`, as a depth or `movetime <ms>`,
+                // and may be followed by a time control, as `tc <base>[+<increment>]`.
+                let tc_at = command.iter().position(|word| word == "tc");
+                let (level, tc) = command[2..].split_at(tc_at.unwrap_or(command.len()) - 2);
+                let (depth, movetime) = match level {
+                    [] => (None, None),
+                    [word, movetime] if word == "movetime" => match movetime.parse::<u64>() {
+                        Ok(movetime) if movetime > 0 => (None, Some(movetime)),
+                        _ => {
+                            println!("playvs invalid movetime");
+                            continue;
+                        }
+                    },
+                    [depth] => match depth.parse::<usize>() {
+                        Ok(depth) if depth > 0 && depth <= MAX_PLY => (Some(depth), None),
+                        _ => {
+                            println!("playvs invalid depth");
+                            continue;
+                        }
+                    },
+                    _ => {
+                        println!("playvs invalid args");
+                        continue;
+                    }
+                };
+                let mut game = PlayVs::new(human, depth, movetime, notation, color);
+                match tc {
+                    [] => {}
+                    [_, tc] => match arena::TimeControl::parse(tc) {
+                        Some(time_control) => game = game.with_time_control(time_control),
+                        None => {
+                            println!("playvs invalid tc");
+                            continue;
+                        }
+                    },
+                    _ => {
+                        println!("playvs invalid args");
+                        continue;
+                    }
+                }
+                if !game.start(&mut history, &table, iir, &mut skill) {
+                    playvs = Some(game);
+                }
+            }
+            "autoplay" => {
+                // `autoplay movetime <ms>` gives each move a time, and `autoplay <depth>` a depth.
+                let (depth, movetime) = match command.get(1).map(String::as_str) {
+                    None => (AUTOPLAY_DEPTH, None),
+                    Some("movetime") => match command.get(2).map(|ms| ms.parse::<u64>()) {
+                        Some(Ok(movetime)) if movetime > 0 => (MAX_PLY, Some(movetime)),
+                        _ => {
+                            println!("autoplay invalid movetime");
+                            continue;
+                        }
+                    },
+                    Some(depth) => match depth.parse::<usize>() {
+                        Ok(depth) if depth > 0 && depth <= MAX_PLY => (depth, None),
+                        _ => {
+                            println!("autoplay invalid depth");
+                            continue;
+                        }
+                    },
+                };
+                autoplay(
+                    &mut history,
+                    depth,
+                    movetime,
+                    &table,
+                    iir,
+                    &mut skill,
+                    notation,
+                );
+                adjudicator.reset();
+            }
+            "annotate" => {
+                // The depth and the three thresholds are optional, but the thresholds are given all together.
+                let depth = match command.get(1).map(|d| d.parse::<usize>()) {
+                    None => annotate::ANNOTATE_DEPTH,
+                    Some(Ok(depth)) if depth > 0 && depth <= MAX_PLY => depth,
+                    Some(_) => {
+                        println!("annotate invalid depth");
+                        continue;
+                    }
+                };
+                let thresholds = match &command[command.len().min(2)..] {
+                    [] => annotate::DEFAULT_THRESHOLDS,
+                    [inaccuracy, mistake, blunder] => {
+                        match (inaccuracy.parse(), mistake.parse(), blunder.parse()) {
+                            (Ok(inaccuracy), Ok(mistake), Ok(blunder))
+                                if 0 < inaccuracy
+                                    && inaccuracy <= mistake
+                                    && mistake <= blunder =>
+                            {
+                                [inaccuracy, mistake, blunder]
+                            }
+                            _ => {
+                                println!("annotate invalid thresholds");
+                                continue;
+                            }
+                        }
+                    }
+                    _ => {
+                        println!("annotate invalid args");
+                        continue;
+                    }
+                };
+                annotate::run_annotate(&history, depth, thresholds, notation);
+            }
+            "evalgraph" => {
+                if command.len() < 2 {
+                    println!("evalgraph invalid args");
+                    continue;
+                }
+                match write(&command[1], eval_graph_csv(&history, notation)) {
+                    Ok(()) => println!("evalgraph ok"),
+                    Err(_) => println!("evalgraph invalid file"),
+                }
+            }
+            "draw" => {
+                if command.len() < 2 {
+                    println!("draw invalid args");
+                    continue;
+                }
+                let (board, last_move, _, _) = *history.last().unwrap();
+                match write(&command[1], display::board_svg(board, last_move)) {
+                    Ok(()) => println!("draw ok"),
+                    Err(_) => println!("draw invalid file"),
+                }
+            }
+            "bookinspect" => {
+                let Some(path) = command.get(1) else {
+                    println!("bookinspect invalid args");
+                    continue;
+                };
+                let Some(book) = read(path).ok().and_then(|bytes| Book::from_bytes(&bytes)) else {
+                    println!("bookinspect invalid file");
+                    continue;
+                };
+                let (board, _, side, _) = *history.last().unwrap();
+                let entries = book.lookup(board, side);
+                for entry in &entries {
+                    println!(
+                        "bookinspect move {} weight {} learn {}",
+                        move_string(entry.mv, notation),
+                        entry.weight,
+                        entry.learn
+                    );
+                }
+                println!("bookinspect entries {}", entries.len());
+            }
+            "hash" => {
+                let (board, _, side, _) = *history.last().unwrap();
+                println!(
+                    "hash {:016x} canonical {:016x}",
+                    zobrist::hash(board, side),
+                    zobrist::canonical_hash(board, side)
+                );
+            }
+            // The canonical form is the one of the 8 transformed boards with the smallest hash, as in `hash`.
+            "canonical" => {
+                let (board, _, side, _) = *history.last().unwrap();
+                let (symmetry, key) = zobrist::canonical(board, side);
+                println!(
+                    "canonical hash {:016x} symmetry {} pos {}",
+                    key,
+                    symmetry,
+                    board_string(symmetry::transform_board(board, symmetry), side)
+                );
+            }
+            // The given position is only compared with the current one, so it need not be valid.
+            "diff" => {
+                if command.len() < 3 {
+                    println!("diff invalid args");
+                    continue;
+                }
+                let Some(other) = board_from_string(&command[1..command.len().min(4)].join(" "))
+                else {
+                    println!("diff invalid pos");
+                    continue;
+                };
+                let (board, _, side, _) = *history.last().unwrap();
+                let differences = display::board_differences((board, side), other);
+                for difference in &differences {
+                    println!("diff {}", difference);
+                }
+                println!("diff total {}", differences.len());
+            }
+            "verify" => verify::run_verify(&history),
+            "gamepos" => {
+                let (board, _, side, _) = *history.last().unwrap();
+                println!("{}", board_string(board, side));
+            }
+            // The side to move is kept with every entry of the game history, rather than worked out from its length,
+            // so it stays correct after `newgame`, `handicap`, `swap` and null moves.
+            "sidetomove" => {
+                let (_, _, side, _) = *history.last().unwrap();
+                println!("sidetomove {}", player_string(side));
+            }
+            "d" => {
+                let (board, last_move, side, _) = *history.last().unwrap();
+                if color {
+                    display::print_board_color(board, side, last_move);
+                } else {
+                    display::print_board(board, side, last_move);
+                }
+            }
+            "q" | "quit" => break,
             _ => println!("badkeyword"),
         }
     }
+    stdout().flush().ok();
 }
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/**
+ * Counts of the work done by a server, exposed at `/metrics` in the Prometheus text format
+ * so that a hosted engine can be monitored like any other service.
+ * Nodes are only counted where the searches run in the server's own process.
+ */
+#[derive(Default)]
+pub struct Metrics {
+    searches_started: AtomicU64,
+    searches_completed: AtomicU64,
+    depth_total: AtomicU64,
+    nodes_total: AtomicU64,
+    search_ms_total: AtomicU64,
+    active_sessions: AtomicU64,
+}
+
+impl Metrics {
+    pub fn search_started(&self) {
+        self.searches_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Records a finished search, with the number of nodes it searched if known.
+    pub fn search_completed(&self, depth: usize, nodes: Option<u64>, milliseconds: u64) {
+        self.searches_completed.fetch_add(1, Ordering::Relaxed);
+        self.depth_total.fetch_add(depth as u64, Ordering::Relaxed);
+        self.nodes_total
+            .fetch_add(nodes.unwrap_or(0), Ordering::Relaxed);
+        self.search_ms_total
+            .fetch_add(milliseconds, Ordering::Relaxed);
+    }
+
+    pub fn session_opened(&self) {
+        self.active_sessions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn session_closed(&self) {
+        self.active_sessions.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /**
+     * Writes every metric in the Prometheus text format.
+     * The node metrics are left out unless `nodes` is set, and the table occupancy unless it is given,
+     * for servers that cannot see them.
+     */
+    pub fn render(&self, nodes: bool, occupancy: Option<f64>) -> String {
+        let completed = self.searches_completed.load(Ordering::Relaxed);
+        let nodes_total = self.nodes_total.load(Ordering::Relaxed);
+        let search_ms = self.search_ms_total.load(Ordering::Relaxed);
+        let mut metrics = vec![
+            (
+                "searches_started_total",
+                "counter",
+                "Searches started.",
+                self.searches_started.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "searches_completed_total",
+                "counter",
+                "Searches that finished with a result.",
+                completed as f64,
+            ),
+            (
+                "search_seconds_total",
+                "counter",
+                "Time spent in finished searches.",
+                search_ms as f64 / 1000.0,
+            ),
+            (
+                "average_depth",
+                "gauge",
+                "Average depth reached by finished searches.",
+                self.depth_total.load(Ordering::Relaxed) as f64 / completed.max(1) as f64,
+            ),
+            (
+                "active_sessions",
+                "gauge",
+                "Sessions currently open.",
+                self.active_sessions.load(Ordering::Relaxed) as f64,
+            ),
+        ];
+        if nodes {
+            metrics.push((
+                "nodes_total",
+                "counter",
+                "Nodes searched by finished searches.",
+                nodes_total as f64,
+            ));
+            metrics.push((
+                "nodes_per_second",
+                "gauge",
+                "Average speed of finished searches.",
+                nodes_total as f64 * 1000.0 / search_ms.max(1) as f64,
+            ));
+        }
+        if let Some(occupancy) = occupancy {
+            metrics.push((
+                "tt_occupancy",
+                "gauge",
+                "Share of transposition table entries in use, averaged over sessions.",
+                occupancy,
+            ));
+        }
+        metrics
+            .iter()
+            .map(|(name, kind, help, value)| {
+                format!(
+                    "# HELP ut3b2l_{0} {1}\n# TYPE ut3b2l_{0} {2}\nut3b2l_{0} {3}\n",
+                    name, help, kind, value
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,314 @@
+// Conversions between the internal representations of the engine and the strings used to communicate them,
+// shared by the command protocol and the bindings for other languages.
+use crate::engine::*;
+
+// Arrays to readily convert integers in the 0-8 range to the
+// name of their corresponding zone.
+pub const ZONE_ARRAY_UPPER: [&str; 9] = ["NW", "N", "NE", "W", "C", "E", "SW", "S", "SE"];
+pub const ZONE_ARRAY_LOWER: [&str; 9] = ["nw", "n", "ne", "w", "c", "e", "sw", "s", "se"];
+
+// The notations that moves can be emitted in, selected with `setoption Notation`.
+// `Zone` is the `zone/square` form (e.g. `ne/c`),
+// while `RowCol` names the row and column of the 9x9 grid, counted from 1 (e.g. `r2c8`).
+#[derive(Clone, Copy, PartialEq)]
+pub enum Notation {
+    Zone,
+    RowCol,
+}
+
+// Converts a `u64` move representation to a string in the given notation.
+pub fn move_string(mv: Move, notation: Notation) -> String {
+    match notation {
+        Notation::Zone => format!(
+            "{0}/{1}",
+            ZONE_ARRAY_LOWER[(mv / 9) as usize],
+            ZONE_ARRAY_LOWER[(mv % 9) as usize]
+        ),
+        Notation::RowCol => {
+            let (row, col) = move_to_row_col(mv);
+            format!("r{0}c{1}", row + 1, col + 1)
+        }
+    }
+}
+
+// Converts a principal variation to a space separated list of moves,
+// ending at the first `NULL_MOVE`.
+pub fn pv_string(line: &[Move], notation: Notation) -> String {
+    line.iter()
+        .take_while(|&&m| m != NULL_MOVE)
+        .map(|m| move_string(*m, notation))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Returns the internal move representation from its string representation.
+// Both the `zone/square` and `r<row>c<col>` notations are accepted, regardless of the option.
+pub fn move_from_string(move_string: &str) -> Option<Move> {
+    if let Some((row, col)) = move_string
+        .strip_prefix('r')
+        .and_then(|row_col| row_col.split_once('c'))
+    {
+        return move_from_row_col_string(row, col);
+    }
+    let zone_and_square: Vec<_> = move_string.split('/').collect();
+    if zone_and_square.len() != 2 {
+        return None;
+    }
+    let zone = ZONE_ARRAY_LOWER
+        .iter()
+        .position(|&z| z == zone_and_square[0]);
+    let square = ZONE_ARRAY_LOWER
+        .iter()
+        .position(|&s| s == zone_and_square[1]);
+    if let (Some(z), Some(s)) = (zone, square) {
+        Some(9 * z as u64 + s as u64)
+    } else {
+        None
+    }
+}
+
+// Returns the move from separately given row and column numbers, both in the 1-9 range.
+pub fn move_from_row_col_string(row: &str, col: &str) -> Option<Move> {
+    match (row.parse::<u64>(), col.parse::<u64>()) {
+        (Ok(r @ 1..=9), Ok(c @ 1..=9)) => Some(move_from_row_col(r - 1, c - 1)),
+        _ => None,
+    }
+}
+
+// Converts a `i32` heuristic evaluation value to a string.
+pub fn eval_string(eval: i32, max_depth: usize) -> String {
+    if eval <= OUTCOME_LOSS + max_depth as i32 {
+        format!("L{0}", eval - OUTCOME_LOSS)
+    } else if eval >= OUTCOME_WIN - max_depth as i32 {
+        format!("W{0}", OUTCOME_WIN - eval)
+    } else if eval == OUTCOME_DRAW {
+        "D0".to_string()
+    } else {
+        format!("{:+0}", eval)
+    }
+}
+
+// Reads an evaluation written by `eval_string`, such as `+16`, `D0`, `W5` or `L3`.
+pub fn eval_from_string(eval: &str) -> Option<i32> {
+    if let Some(plies) = eval.strip_prefix('W') {
+        plies.parse::<i32>().ok().map(|plies| OUTCOME_WIN - plies)
+    } else if let Some(plies) = eval.strip_prefix('L') {
+        plies.parse::<i32>().ok().map(|plies| OUTCOME_LOSS + plies)
+    } else if eval == "D0" {
+        Some(OUTCOME_DRAW)
+    } else {
+        eval.parse().ok()
+    }
+}
+
+// Compressed inline string representation for compact passing of Board setups,
+// together with the side to move (`false` for Player X, `true` for Player O).
+pub fn board_string(board: Board, side: bool) -> String {
+    let (us, them, share) = board;
+    let zone = (share >> 54) & 0b1111;
+    let cells = (0..81).step_by(27).flat_map(move |i| {
+        (0..9).step_by(3).map(move |j| {
+            (0..27)
+                .step_by(9)
+                .flat_map(move |k| i + j + k..i + j + k + 3)
+        })
+    });
+    format!(
+        "{} {} {}",
+        cells
+            .map(|v| v
+                .map(|i| {
+                    if i > 62 {
+                        if ((share >> (i - 63)) & 1) == 1 {
+                            "x".to_string()
+                        } else if ((share >> (i - 45)) & 1) == 1 {
+                            "o".to_string()
+                        } else {
+                            ".".to_string()
+                        }
+                    } else if ((us >> i) & 1) == 1 {
+                        "x".to_string()
+                    } else if ((them >> i) & 1) == 1 {
+                        "o".to_string()
+                    } else {
+                        ".".to_string()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(""))
+            .collect::<Vec<_>>()
+            .join("/")
+            .replace(".........", "9")
+            .replace("........", "8")
+            .replace(".......", "7")
+            .replace("......", "6")
+            .replace(".....", "5")
+            .replace("....", "4")
+            .replace("...", "3")
+            .replace("..", "2")
+            .replace('.', "1"),
+        if zone == ZONE_ANY {
+            "any"
+        } else {
+            ZONE_ARRAY_LOWER[zone as usize]
+        },
+        player_string(side)
+    )
+}
+
+// Converts a side (`false` for Player X, `true` for Player O) to its name in position strings.
+pub fn player_string(side: bool) -> &'static str {
+    if side {
+        "o"
+    } else {
+        "x"
+    }
+}
+
+// Returns an internal board representation and the side to move from its string representation.
+// The side to move is the optional third field, and is inferred from the number of occupied cells
+// if absent, so that strings from before the field was introduced remain valid.
+pub fn board_from_string(board_string: &str) -> Option<(Board, bool)> {
+    let (mut us, mut them, mut share) = (0u64, 0u64, 0u64);
+    let decompressed_string = board_string
+        .replace('1', ".")
+        .replace('2', "..")
+        .replace('3', "...")
+        .replace('4', "....")
+        .replace('5', ".....")
+        .replace('6', "......")
+        .replace('7', ".......")
+        .replace('8', "........")
+        .replace('9', ".........");
+    let cell_and_zone: Vec<_> = decompressed_string.split_whitespace().collect();
+    if cell_and_zone.len() != 2 && cell_and_zone.len() != 3 {
+        return None;
+    }
+    let (cell, zone) = (cell_and_zone[0], cell_and_zone[1]);
+    let side = match cell_and_zone.get(2) {
+        Some(&"x") => Some(false),
+        Some(&"o") => Some(true),
+        Some(_) => return None,
+        None => None,
+    };
+    if let Some(z) = ZONE_ARRAY_LOWER.iter().position(|&z| z == zone) {
+        share |= (z as u64) << 54;
+    } else if zone == "any" {
+        share |= ZONE_ANY << 54;
+    } else {
+        return None;
+    }
+    let rows: Vec<_> = cell.split('/').collect();
+    if rows.len() != 9 {
+        return None;
+    }
+    if rows.iter().any(|row| row.len() != 9) {
+        return None;
+    }
+    decompressed_string
+        .replace('/', "")
+        .chars()
+        .zip((0..81).step_by(27).flat_map(move |i| {
+            (0..9).step_by(3).flat_map(move |j| {
+                (0..27)
+                    .step_by(9)
+                    .flat_map(move |k| i + j + k..i + j + k + 3)
+            })
+        }))
+        .for_each(|(c, i)| {
+            if i > 62 {
+                if c == 'x' {
+                    share |= 1 << (i - 63);
+                } else if c == 'o' {
+                    share |= 1 << (i - 45);
+                }
+            } else if c == 'x' {
+                us |= 1 << i;
+            } else if c == 'o' {
+                them |= 1 << i;
+            }
+        });
+    // A zone that is completely filled cannot be played in, so being sent to it lets the player play in any zone.
+    let board = with_playable_zone(with_large_grid((us, them, share)));
+    Some((board, side.unwrap_or_else(|| infer_side(board))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_read_in_either_notation() {
+        assert_eq!(move_from_string("nw/nw"), Some(0));
+        assert_eq!(move_from_string("ne/c"), Some(22));
+        assert_eq!(move_from_string("r2c8"), Some(22));
+        assert_eq!(move_from_string("r9c9"), Some(80));
+        for mv in 0..81 {
+            for notation in [Notation::Zone, Notation::RowCol] {
+                assert_eq!(move_from_string(&move_string(mv, notation)), Some(mv));
+            }
+        }
+    }
+
+    #[test]
+    fn positions_read_with_and_without_the_side_to_move() {
+        let empty = (0, 0, ZONE_ANY << 54);
+        assert_eq!(
+            board_from_string("9/9/9/9/9/9/9/9/9 any"),
+            Some((empty, false))
+        );
+        assert_eq!(
+            board_from_string("9/9/9/9/9/9/9/9/9 any o"),
+            Some((empty, true))
+        );
+        let first = play_move(empty, 0, false);
+        assert_eq!(
+            board_from_string("x8/9/9/9/9/9/9/9/9 nw"),
+            Some((first, true))
+        );
+        assert_eq!(
+            board_from_string("x8/9/9/9/9/9/9/9/9 nw x"),
+            Some((first, false))
+        );
+        assert_eq!(board_string(first, true), "x8/9/9/9/9/9/9/9/9 nw o");
+    }
+
+    #[test]
+    fn positions_survive_a_round_trip() {
+        let mut board = (0, 0, ZONE_ANY << 54);
+        let mut side = false;
+        for mv in ["c/c", "c/nw", "nw/c", "c/ne", "ne/c", "c/se", "se/c"] {
+            board = play_move(board, move_from_string(mv).unwrap(), side);
+            side = !side;
+            assert_eq!(
+                board_from_string(&board_string(board, side)),
+                Some((board, side))
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_positions_are_rejected() {
+        for invalid in [
+            "",
+            "9/9/9/9/9/9/9/9/9",
+            "9/9/9/9/9/9/9/9/9 somewhere",
+            "9/9/9/9/9/9/9/9/9 any y",
+            "9/9/9/9/9/9/9/9/9 any x extra",
+            "9/9/9/9/9/9/9/9 any",
+            "9/9/9/9/9/9/9/9/8 any",
+            "9/9/9/9/9/9/9/9/x9 any",
+        ] {
+            assert_eq!(board_from_string(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn invalid_moves_are_rejected() {
+        for invalid in [
+            "", "nw", "nw/", "nw/nw/nw", "NW/NW", "x/c", "r0c1", "r1c10", "r1", "rc", "r1c1c1",
+        ] {
+            assert_eq!(move_from_string(invalid), None, "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+use std::fs::write;
+use std::sync::Arc;
+
+use crate::arena::game_result;
+use crate::engine::*;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB};
+use crate::zobrist;
+use ut3b2l::notation::*;
+use ut3b2l::random::Random;
+
+// The default shape of a suite: the number of openings, the plies each is made of,
+// the depth each is searched to, and how far from a draw its evaluation may be.
+const DEFAULT_COUNT: usize = 20;
+const DEFAULT_PLIES: usize = 4;
+const DEFAULT_DEPTH: usize = 10;
+const DEFAULT_MARGIN: i32 = 30;
+
+// The number of lines tried for each opening wanted, before giving up on finding more.
+const ATTEMPTS_PER_OPENING: usize = 50;
+
+// A balanced opening: the moves of its line, the position they reach, and its evaluation.
+struct Opening {
+    moves: Vec<Move>,
+    board: Board,
+    side: bool,
+    eval: i32,
+}
+
+impl Opening {
+    // The opening as a line of a file read by `--openings`, with its moves and evaluation in a comment.
+    fn to_line(&self, depth: usize) -> String {
+        format!(
+            "{} # {} eval {}",
+            board_string(self.board, self.side),
+            pv_string(&self.moves, Notation::Zone),
+            eval_string(self.eval, depth)
+        )
+    }
+}
+
+/**
+ * Plays a line of random moves from the empty board, returning its moves and the position reached,
+ * or `None` if the game ended before the line was complete.
+ */
+fn random_line(random: &mut Random, plies: usize) -> Option<(Vec<Move>, Board, bool)> {
+    let (mut board, mut side) = ((0, 0, ZONE_ANY << 54), false);
+    let mut moves = Vec::new();
+    for _ in 0..plies {
+        let legal: Vec<_> = generate_moves(board).collect();
+        if legal.is_empty() {
+            return None;
+        }
+        let mv = legal[random.below(legal.len() as u64) as usize];
+        board = play_move(board, mv, side);
+        side = !side;
+        moves.push(mv);
+    }
+    game_result(board).is_none().then_some((moves, board, side))
+}
+
+/**
+ * Generates a suite of balanced openings for matches and tournaments, as
+ * `openings --out <file> [--count <openings>] [--plies <plies>] [--depth <depth>] [--margin <eval>] [--seed <seed>]`.
+ * Lines of random moves are searched to the depth, and those evaluated within the margin of a draw are kept,
+ * skipping positions symmetrical to one already tried, so that the games played from them are both even and varied.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut output = None;
+    let mut count = DEFAULT_COUNT;
+    let mut plies = DEFAULT_PLIES;
+    let mut depth = DEFAULT_DEPTH;
+    let mut margin = DEFAULT_MARGIN;
+    let mut seed = Random::clock_seed();
+    let mut args = args.iter();
+    let number = |value: &str, flag: &str| {
+        value
+            .parse::<usize>()
+            .ok()
+            .filter(|&number| number > 0)
+            .ok_or_else(|| format!("{} needs a positive number", flag))
+    };
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--out" => output = Some(value.clone()),
+            "--count" => count = number(value, arg)?,
+            "--plies" => plies = number(value, arg)?,
+            "--depth" => {
+                depth = number(value, arg)?;
+                if depth > MAX_PLY {
+                    return Err(format!("{} is at most {}", arg, MAX_PLY));
+                }
+            }
+            "--margin" => {
+                margin = value
+                    .parse()
+                    .ok()
+                    .filter(|&margin| margin >= 0)
+                    .ok_or_else(|| format!("{} needs a number", arg))?
+            }
+            "--seed" => {
+                seed = value
+                    .parse()
+                    .map_err(|_| format!("{} needs a number", arg))?
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    let Some(output) = output else {
+        return Err("openings needs --out".to_string());
+    };
+    // The seed is written out so that a run without one can be repeated.
+    println!("openings seed {}", seed);
+
+    let mut random = Random::new(seed);
+    let table = Arc::new(TranspositionTable::new(DEFAULT_HASH_MB, Replacement::Aging));
+    let mut seen = HashSet::new();
+    let mut openings = Vec::new();
+    let mut tried = 0;
+    for _ in 0..count * ATTEMPTS_PER_OPENING {
+        if openings.len() == count {
+            break;
+        }
+        let Some((moves, board, side)) = random_line(&mut random, plies) else {
+            continue;
+        };
+        if !seen.insert(zobrist::canonical_hash(board, side)) {
+            continue;
+        }
+        tried += 1;
+        table.new_search();
+        let mut control = SearchControl::new(None).with_table(table.clone());
+        let (_, eval, _) = iterative_deepening(board, side, depth, None, &mut control);
+        if eval.abs() > margin {
+            continue;
+        }
+        let opening = Opening {
+            moves,
+            board,
+            side,
+            eval,
+        };
+        println!(
+            "openings line {} eval {} moves {}",
+            openings.len() + 1,
+            eval_string(eval, depth),
+            pv_string(&opening.moves, Notation::Zone)
+        );
+        openings.push(opening);
+    }
+    if openings.is_empty() {
+        return Err("no balanced openings found".to_string());
+    }
+
+    let mut lines = vec![format!(
+        "# Openings of {} plies within {} of a draw at depth {}, generated with `ut3b2l openings --seed {}`.",
+        plies, margin, depth, seed
+    )];
+    lines.extend(openings.iter().map(|opening| opening.to_line(depth)));
+    write(&output, lines.join("\n") + "\n")
+        .map_err(|error| format!("cannot write {}: {}", output, error))?;
+    println!("openings tried {} kept {}", tried, openings.len());
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::engine::*;
+use crate::interrupt::interrupted;
+use crate::{move_string, Notation};
+
+// No game can last longer than the number of cells, so no deeper count is meaningful.
+pub const MAX_PERFT_DEPTH: usize = 81;
+
+/**
+ * Counts the leaf nodes of the tree of legal moves to the given depth, for testing move generation.
+ * Positions where the game has ended have no moves, so the lines ending in them are not counted.
+ * At the last ply, the moves are counted without being played ("bulk counting").
+ * Returns 0 if the count is abandoned because of an interrupt.
+ */
+pub fn perft(board: Board, side: bool, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    if depth == 1 {
+        return generate_moves(board).count() as u64;
+    }
+    // Only the larger subtrees check for interrupts, so that the check does not slow down the count.
+    if depth >= 3 && interrupted() {
+        return 0;
+    }
+    generate_moves(board)
+        .map(|mv| perft(play_move(board, mv, side), !side, depth - 1))
+        .sum()
+}
+
+/**
+ * Counts the leaf nodes to the given depth below each legal move from the position,
+ * returning the moves with their counts in move generation order.
+ * The moves are shared between `threads` worker threads, each taking the next uncounted move
+ * once it has finished its last, so that a few large subtrees do not leave the other threads idle.
+ */
+fn perft_by_move(board: Board, side: bool, depth: usize, threads: usize) -> Vec<(Move, u64)> {
+    let moves: Vec<Move> = generate_moves(board).collect();
+    let counts = Mutex::new(vec![0; moves.len()]);
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..threads.min(moves.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= moves.len() {
+                    break;
+                }
+                let nodes = perft(play_move(board, moves[i], side), !side, depth - 1);
+                counts.lock().unwrap()[i] = nodes;
+            });
+        }
+    });
+    moves
+        .into_iter()
+        .zip(counts.into_inner().unwrap())
+        .collect()
+}
+
+// Counts the leaf nodes to the given depth, splitting the count by the first move
+// across `threads` threads if there is more than one.
+fn parallel_perft(board: Board, side: bool, depth: usize, threads: usize) -> u64 {
+    if threads <= 1 || depth <= 1 {
+        return perft(board, side, depth);
+    }
+    perft_by_move(board, side, depth, threads)
+        .iter()
+        .map(|&(_, nodes)| nodes)
+        .sum()
+}
+
+// Outputs the number of leaf nodes at every depth up to the given depth,
+// together with the time taken to count them.
+pub fn run_perft(board: Board, side: bool, depth: usize, threads: usize) {
+    for d in 1..=depth {
+        let start = Instant::now();
+        let nodes = parallel_perft(board, side, d, threads);
+        if interrupted() {
+            return;
+        }
+        println!(
+            "perft depth {} nodes {} time {}",
+            d,
+            nodes,
+            start.elapsed().as_millis()
+        );
+    }
+}
+
+// Outputs the number of leaf nodes to the given depth below each legal move from the position,
+// so that a miscounted subtree can be traced to the move that leads to it.
+pub fn run_perft_divide(
+    board: Board,
+    side: bool,
+    depth: usize,
+    threads: usize,
+    notation: Notation,
+) {
+    let start = Instant::now();
+    let counts = perft_by_move(board, side, depth, threads.max(1));
+    if interrupted() {
+        return;
+    }
+    for &(mv, nodes) in counts.iter() {
+        println!("perft move {} nodes {}", move_string(mv, notation), nodes);
+    }
+    println!(
+        "perft total {} time {}",
+        counts.iter().map(|&(_, nodes)| nodes).sum::<u64>(),
+        start.elapsed().as_millis()
+    );
+}
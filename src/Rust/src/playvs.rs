@@ -0,0 +1,298 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::arena::{game_result, GameResult, TimeControl, MOVES_TO_GO};
+use crate::display::{print_board, print_board_color};
+use crate::engine::*;
+use crate::interrupt::interrupted;
+use crate::skill::Skill;
+use crate::tt::TranspositionTable;
+use crate::{
+    eval_string, illegal_move_string, move_from_row_col_string, move_from_string, move_string,
+    player_string, search_now, swap_first_move, Notation, SearchRecord,
+};
+
+// The default depth the engine searches for each of its moves.
+pub const PLAYVS_DEPTH: usize = 6;
+
+// The game history, as kept by the main loop.
+type History = Vec<(Board, Move, bool, Option<SearchRecord>)>;
+
+/**
+ * The state of a game against the engine, entered with the `playvs` command.
+ * Moves are played into the game history as usual, but the engine always plays the side the player did not choose,
+ * answering each of the player's moves straight away, so that neither `go` nor null moves are needed.
+ * The time each side takes for its moves is kept, and with a time control, each side has a clock,
+ * which it loses the game by running out of.
+ */
+pub struct PlayVs {
+    // The side the player plays.
+    human: bool,
+    // The engine's level: a depth, or a time for each move. Without either, the engine
+    // searches to `PLAYVS_DEPTH`, or with a time control, for a share of its remaining time.
+    depth: Option<usize>,
+    movetime: Option<u64>,
+    notation: Notation,
+    color: bool,
+    // The time control, and the time left on each side's clock.
+    time_control: Option<TimeControl>,
+    clocks: [u64; 2],
+    // The time each side has used so far, and when the turn being played began.
+    used: [u64; 2],
+    turn_start: Instant,
+}
+
+impl PlayVs {
+    pub fn new(
+        human: bool,
+        depth: Option<usize>,
+        movetime: Option<u64>,
+        notation: Notation,
+        color: bool,
+    ) -> Self {
+        Self {
+            human,
+            depth,
+            movetime,
+            notation,
+            color,
+            time_control: None,
+            clocks: [0; 2],
+            used: [0; 2],
+            turn_start: Instant::now(),
+        }
+    }
+
+    // Gives each side a clock, starting from the base time of the time control.
+    pub fn with_time_control(mut self, time_control: TimeControl) -> Self {
+        self.time_control = Some(time_control);
+        self.clocks = [time_control.base_ms; 2];
+        self
+    }
+
+    // The depth and time the engine searches its next move for, playing the given side.
+    fn engine_limit(&self, side: bool) -> (usize, Option<u64>) {
+        let clock = self.clocks[side as usize];
+        match (self.depth, self.movetime, self.time_control) {
+            (Some(depth), _, _) => (depth, None),
+            (None, Some(movetime), Some(_)) => (MAX_PLY, Some(movetime.min(clock).max(1))),
+            (None, Some(movetime), None) => (MAX_PLY, Some(movetime)),
+            (None, None, Some(time_control)) => {
+                let share = clock / MOVES_TO_GO + time_control.increment_ms;
+                (MAX_PLY, Some(share.min(clock).max(1)))
+            }
+            (None, None, None) => (PLAYVS_DEPTH, None),
+        }
+    }
+
+    /**
+     * Charges the time since the turn began to the side that has just moved, starting the next turn,
+     * and outputs `playvs clock <side> used <ms> x <ms> o <ms>`, giving the time both sides have left,
+     * or without a time control, the time both have used.
+     * Returns `false` if the side has run out of time, in which case its move does not count.
+     */
+    fn charge(&mut self, side: bool) -> bool {
+        let i = side as usize;
+        let elapsed = self.turn_start.elapsed().as_millis() as u64;
+        self.turn_start = Instant::now();
+        self.used[i] += elapsed;
+        let out_of_time = self.time_control.is_some_and(|time_control| {
+            let out_of_time = elapsed > self.clocks[i];
+            self.clocks[i] = self.clocks[i].saturating_sub(elapsed);
+            if !out_of_time {
+                self.clocks[i] += time_control.increment_ms;
+            }
+            out_of_time
+        });
+        let shown = if self.time_control.is_some() {
+            self.clocks
+        } else {
+            self.used
+        };
+        println!(
+            "playvs clock {} used {} x {} o {}",
+            player_string(side),
+            elapsed,
+            shown[0],
+            shown[1]
+        );
+        if out_of_time {
+            println!("playvs timeout {}", player_string(side));
+        }
+        !out_of_time
+    }
+
+    fn show_board(&self, history: &History) {
+        let (board, last_move, side, _) = *history.last().unwrap();
+        if self.color {
+            print_board_color(board, side, last_move);
+        } else {
+            print_board(board, side, last_move);
+        }
+    }
+
+    /**
+     * Announces the result if the game is over, returning whether it is.
+     * Otherwise, if it is the engine's turn, the engine plays its move first,
+     * and the player is prompted for theirs.
+     */
+    fn advance(
+        &mut self,
+        history: &mut History,
+        table: &Arc<TranspositionTable>,
+        iir: IirSettings,
+        skill: &mut Skill,
+    ) -> bool {
+        let (board, _, side, _) = *history.last().unwrap();
+        if let Some(result) = game_result(board) {
+            return self.announce(result);
+        }
+        if side != self.human {
+            let (depth, movetime) = self.engine_limit(side);
+            let (line, search) = search_now(board, side, depth, movetime, table, iir, skill);
+            // The program is about to exit, so the move being searched is not played.
+            if interrupted() {
+                return true;
+            }
+            if !self.charge(side) {
+                return self.announce(GameResult::win_for(!side));
+            }
+            let (eval, depth, duration) = search;
+            // Under the pie rule, the engine takes over the player's first move instead if it would rather have made it.
+            if eval < 0 && swap_first_move(history) {
+                println!("playvs engine swap");
+                self.show_board(history);
+                println!("playvs turn {}", player_string(self.human));
+                return false;
+            }
+            println!(
+                "playvs engine move {} eval {} depth {} time {}",
+                move_string(line[0], self.notation),
+                eval_string(eval, depth),
+                depth,
+                duration
+            );
+            let board = play_move(board, line[0], side);
+            history.push((board, line[0], !side, Some(search)));
+            self.show_board(history);
+            if let Some(result) = game_result(board) {
+                return self.announce(result);
+            }
+        }
+        println!("playvs turn {}", player_string(self.human));
+        false
+    }
+
+    // Outputs the result from the player's point of view, as `playvs result <score> <winner>`.
+    fn announce(&self, result: GameResult) -> bool {
+        let winner = match result {
+            GameResult::Draw => "draw",
+            _ if result == GameResult::win_for(self.human) => "you",
+            _ => "engine",
+        };
+        println!("playvs result {} {}", result.score_string(), winner);
+        true
+    }
+
+    // Starts the game from the last position of the history, returning whether it is already over.
+    pub fn start(
+        &mut self,
+        history: &mut History,
+        table: &Arc<TranspositionTable>,
+        iir: IirSettings,
+        skill: &mut Skill,
+    ) -> bool {
+        let mut limit = match (self.depth, self.movetime, self.time_control) {
+            (Some(depth), _, _) => format!("depth {}", depth),
+            (None, Some(movetime), _) => format!("movetime {}", movetime),
+            (None, None, Some(_)) => "clock".to_string(),
+            (None, None, None) => format!("depth {}", PLAYVS_DEPTH),
+        };
+        if let Some(time_control) = self.time_control {
+            limit += &format!(" tc {}+{}", time_control.base_ms, time_control.increment_ms);
+        }
+        println!(
+            "playvs you {} engine {} {}",
+            player_string(self.human),
+            player_string(!self.human),
+            limit
+        );
+        self.show_board(history);
+        self.turn_start = Instant::now();
+        self.advance(history, table, iir, skill)
+    }
+
+    // Handles a single command given during the game, returning whether the game has ended.
+    // All responses begin with the `playvs` keyword.
+    pub fn execute(
+        &mut self,
+        command: &[String],
+        history: &mut History,
+        table: &Arc<TranspositionTable>,
+        iir: IirSettings,
+        skill: &mut Skill,
+    ) -> bool {
+        match &command[0] as &str {
+            "d" => self.show_board(history),
+            // Undoing goes back to the player's previous turn, taking back the engine's reply as well.
+            "undo" => {
+                let turn = history[..history.len() - 1]
+                    .iter()
+                    .rposition(|&(_, _, side, _)| side == self.human);
+                match turn {
+                    Some(turn) => {
+                        history.truncate(turn + 1);
+                        println!("playvs undo ok");
+                        self.show_board(history);
+                        println!("playvs turn {}", player_string(self.human));
+                    }
+                    None => println!("playvs undo stackempty"),
+                }
+            }
+            "swap" => {
+                if !swap_first_move(history) {
+                    println!("playvs swap illegal");
+                    return false;
+                }
+                if !self.charge(self.human) {
+                    return self.announce(GameResult::win_for(!self.human));
+                }
+                println!("playvs swap ok");
+                self.show_board(history);
+                return self.advance(history, table, iir, skill);
+            }
+            "resign" => {
+                return self.announce(GameResult::win_for(!self.human));
+            }
+            "quit" | "exit" => {
+                println!("playvs stopped");
+                return true;
+            }
+            _ => {
+                // A move given as two numbers is read as a row and column, as with `play`.
+                let parsed_move = match command {
+                    [mv] => move_from_string(mv),
+                    [row, col] => move_from_row_col_string(row, col),
+                    _ => None,
+                };
+                let Some(mv) = parsed_move else {
+                    println!("playvs invalid move");
+                    return false;
+                };
+                let (board, _, side, _) = *history.last().unwrap();
+                if let Err(error) = check_move(board, mv) {
+                    println!("playvs illegal {}", illegal_move_string(error));
+                    return false;
+                }
+                if !self.charge(side) {
+                    return self.announce(GameResult::win_for(!side));
+                }
+                history.push((play_move(board, mv, side), mv, !side, None));
+                println!("playvs move {}", move_string(mv, self.notation));
+                self.show_board(history);
+                return self.advance(history, table, iir, skill);
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::VecDeque;
+use std::io::{stdin, stdout, BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::channel;
+use std::thread;
+
+use crate::engine::*;
+use crate::json::Json;
+use crate::notation::*;
+
+// The share of the remaining time spent on each move when a frontend gives clocks rather than a move time.
+const MOVES_TO_GO: u64 = 20;
+
+// The position string of the empty board, with Player X to move.
+const START_POSITION: &str = "9/9/9/9/9/9/9/9/9 any x";
+
+// What a line from the frontend is turned into: commands for the engine, or lines answered straight away.
+pub enum Action {
+    Engine(String),
+    Reply(String),
+}
+
+/**
+ * A frontend protocol, translating between what a frontend sends and expects
+ * and the command protocol of the engine described in `interface.md`.
+ * Each line from the frontend is turned into any number of actions, and each line the engine writes
+ * into any number of lines for the frontend, so that a protocol only needs to know the engine's own protocol,
+ * as it is written with the default `InfoFormat` of `text`.
+ * New protocols are added by implementing this trait and passing it to `run`.
+ */
+pub trait Protocol {
+    // Handles a line sent by the frontend.
+    fn command(&mut self, line: &str) -> Vec<Action>;
+
+    // Handles a line written by the engine.
+    fn output(&mut self, line: &str) -> Vec<String>;
+}
+
+// The engine's own protocol, passed through unchanged.
+pub struct Native;
+
+impl Protocol for Native {
+    fn command(&mut self, line: &str) -> Vec<Action> {
+        vec![Action::Engine(line.to_string())]
+    }
+
+    fn output(&mut self, line: &str) -> Vec<String> {
+        vec![line.to_string()]
+    }
+}
+
+// The value following a key in a line of words, as in `info depth 4 ...`.
+fn field<'a>(words: &[&'a str], key: &str) -> Option<&'a str> {
+    let index = words.iter().position(|&word| word == key)?;
+    words.get(index + 1).copied()
+}
+
+/**
+ * The Universal Game Interface, the generalisation of UCI to other games used by multi-game frontends.
+ * Positions are given as `position startpos` or `position fen <position string>`, each optionally followed
+ * by `moves <move> ...`, and searches are reported as `info depth <depth> score <score> pv <moves>` and `bestmove <move>`.
+ * Player 1 is Player X. The position is tracked here as well, to answer the `query` commands,
+ * and is only changed along with the engine's: by positions the engine accepts, and by the moves it plays.
+ */
+#[derive(Default)]
+pub struct Ugi {
+    board: Option<(Board, bool)>,
+    // For each search started and not yet reported, in order, whether it is a `go infinite`
+    // whose `bestmove` must wait for `stop`.
+    searches: VecDeque<bool>,
+    // The `bestmove` of an infinite search that finished before `stop` was given.
+    held: Option<String>,
+}
+
+impl Ugi {
+    fn board(&self) -> (Board, bool) {
+        self.board.unwrap_or(((0, 0, ZONE_ANY << 54), false))
+    }
+
+    // Sets up a position by starting a new game in the engine and playing each move in it.
+    fn position(&mut self, words: &[&str]) -> Vec<Action> {
+        let moves_at = words.iter().position(|&word| word == "moves");
+        let (setup, moves) = words.split_at(moves_at.unwrap_or(words.len()));
+        let position = match setup {
+            ["startpos"] => START_POSITION.to_string(),
+            ["fen", position @ ..] => position.join(" "),
+            _ => return vec![Action::Reply("info string invalid position".to_string())],
+        };
+        // A position the engine would reject leaves both it and the tracked position unchanged.
+        let Some((mut board, mut side)) = board_from_string(&position)
+            .filter(|&(board, side)| validate_position(board, side).is_ok())
+        else {
+            return vec![Action::Reply("info string invalid position".to_string())];
+        };
+        let mut actions = vec![Action::Engine(format!("newgame {}", position))];
+        for word in moves.iter().skip(1) {
+            match move_from_string(word).filter(|&mv| check_move(board, mv).is_ok()) {
+                Some(mv) => {
+                    board = play_move(board, mv, side);
+                    side = !side;
+                    actions.push(Action::Engine(format!("play {}", word)));
+                }
+                None => {
+                    actions.push(Action::Reply(format!("info string illegal move {}", word)));
+                    break;
+                }
+            }
+        }
+        self.board = Some((board, side));
+        actions
+    }
+
+    // Starts a search, by depth, move time or the clocks of both players, or until `stop`.
+    fn go(&mut self, words: &[&str]) -> Action {
+        let number = |key: &str| field(words, key).and_then(|value| value.parse::<u64>().ok());
+        let side = self.board().1;
+        let (time, increment) = if side {
+            (number("p2time"), number("p2inc"))
+        } else {
+            (number("p1time"), number("p1inc"))
+        };
+        let mut infinite = false;
+        let command = if let Some(depth) = number("depth") {
+            format!("go {}", depth.clamp(1, MAX_PLY as u64))
+        } else if let Some(movetime) = number("movetime") {
+            format!("go movetime {}", movetime.max(1))
+        } else if let Some(time) = time {
+            let movetime = (time / MOVES_TO_GO + increment.unwrap_or(0)).min(time);
+            format!("go movetime {}", movetime.max(1))
+        } else {
+            // `go infinite` searches as deep as the engine can, which `stop` ends early,
+            // and its `bestmove` is held back until `stop` even if the search finishes first.
+            infinite = true;
+            format!("go {}", MAX_PLY)
+        };
+        self.searches.push_back(infinite);
+        Action::Engine(command)
+    }
+
+    // Ends the output of a search with its `bestmove`, unless it is an infinite search still waiting for `stop`.
+    fn finish(&mut self, mut lines: Vec<String>, bestmove: String) -> Vec<String> {
+        if self.searches.pop_front().unwrap_or(false) {
+            self.held = Some(bestmove);
+        } else {
+            lines.push(bestmove);
+        }
+        lines
+    }
+
+    // Answers `query gameover`, `query p1turn` and `query result` from the tracked position.
+    fn query(&self, question: &str) -> Action {
+        let (board, side) = self.board();
+        let result = if let Some(side) = winner(board) {
+            Some(if side { "p2win" } else { "p1win" })
+        } else if generate_moves(board).next().is_none() {
+            Some(match full_grid_winner(board) {
+                Some(true) => "p2win",
+                Some(false) => "p1win",
+                None => "draw",
+            })
+        } else {
+            None
+        };
+        let answer = match question {
+            "gameover" => result.is_some().to_string(),
+            "p1turn" => (!side).to_string(),
+            "result" => result.unwrap_or("none").to_string(),
+            _ => return Action::Reply("info string invalid query".to_string()),
+        };
+        Action::Reply(format!("response {}", answer))
+    }
+}
+
+impl Protocol for Ugi {
+    fn command(&mut self, line: &str) -> Vec<Action> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["ugi", ..] => vec![
+                Action::Reply("id name ut3b2l".to_string()),
+                Action::Reply("id author TianLangHin".to_string()),
+                Action::Reply("ugiok".to_string()),
+            ],
+            ["isready", ..] => vec![Action::Engine("isready".to_string())],
+            ["uginewgame", ..] => {
+                self.board = None;
+                vec![Action::Engine(format!("newgame {}", START_POSITION))]
+            }
+            ["position", rest @ ..] => self.position(rest),
+            ["go", rest @ ..] => vec![self.go(rest)],
+            ["stop", ..] => {
+                self.searches
+                    .iter_mut()
+                    .for_each(|infinite| *infinite = false);
+                let mut actions = vec![Action::Engine("stop".to_string())];
+                actions.extend(self.held.take().map(Action::Reply));
+                actions
+            }
+            ["setoption", "name", name, "value", value, ..] => {
+                vec![Action::Engine(format!("setoption {} {}", name, value))]
+            }
+            ["query", question, ..] => vec![self.query(question)],
+            ["quit", ..] => vec![Action::Engine("q".to_string())],
+            _ => vec![Action::Reply(format!(
+                "info string unknown command {}",
+                line
+            ))],
+        }
+    }
+
+    fn output(&mut self, line: &str) -> Vec<String> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words.first() {
+            Some(&"readyok") => vec!["readyok".to_string()],
+            // A search for a forced win that found none has no move to play.
+            Some(&"info") if words.get(1) == Some(&"mate") => {
+                self.finish(Vec::new(), "bestmove 0000".to_string())
+            }
+            // A search that could not be started still owes the frontend a `bestmove`.
+            Some(&"info") if words.get(1) == Some(&"error") => self.finish(
+                vec![format!("info string {}", line)],
+                "bestmove 0000".to_string(),
+            ),
+            // Rejected commands, such as a position the engine cannot read, are passed on as information.
+            Some(&"badkeyword") => vec![format!("info string {}", line)],
+            Some(_) if matches!(words.get(1), Some(&"invalid" | &"illegal")) => {
+                vec![format!("info string {}", line)]
+            }
+            Some(&"info") if words.get(1) == Some(&"depth") => {
+                // The engine plays the move it finds into its own game, so the tracked position follows it.
+                let (Some(depth), Some(eval)) = (field(&words, "depth"), field(&words, "eval"))
+                else {
+                    return Vec::new();
+                };
+                let score = match (
+                    eval.get(..1),
+                    eval.get(1..).and_then(|n| n.parse::<i64>().ok()),
+                ) {
+                    (Some("W"), Some(plies)) => format!("mate {}", (plies + 1) / 2),
+                    (Some("L"), Some(plies)) => format!("mate -{}", (plies + 1) / 2),
+                    (Some("D"), _) => "cp 0".to_string(),
+                    _ => format!("cp {}", eval.trim_start_matches('+')),
+                };
+                let pv_at = words
+                    .iter()
+                    .position(|&word| word == "pv")
+                    .map_or(words.len(), |i| i + 1);
+                let pv: Vec<_> = words[pv_at..]
+                    .iter()
+                    .take_while(|&&word| word != "eval")
+                    .copied()
+                    .collect();
+                let (board, side) = self.board();
+                if let Some(mv) = pv.first().and_then(|mv| move_from_string(mv)) {
+                    self.board = Some((play_move(board, mv, side), !side));
+                }
+                self.finish(
+                    vec![format!(
+                        "info depth {} score {} pv {}",
+                        depth,
+                        score,
+                        pv.join(" ")
+                    )],
+                    format!("bestmove {}", pv.first().unwrap_or(&"0000")),
+                )
+            }
+            // Everything else, such as the answers to `newgame` and `play`, has no counterpart in UGI.
+            _ => Vec::new(),
+        }
+    }
+}
+
+/**
+ * JSON lines: each line from the frontend is an object holding a command of the engine's protocol,
+ * such as `{"command":"go 6"}`, and each line the engine writes is sent as an object keyed by its first word.
+ * Search results become `{"type":"info","depth":6,"pv":["c/c",...],"eval":"+16","time":12}`,
+ * and any other line `{"type":"<first word>","args":[<the other words>]}`.
+ */
+pub struct JsonLines;
+
+impl Protocol for JsonLines {
+    fn command(&mut self, line: &str) -> Vec<Action> {
+        match Json::parse(line)
+            .as_ref()
+            .and_then(|json| json.get("command"))
+            .and_then(Json::as_str)
+        {
+            Some(command) => vec![Action::Engine(command.to_string())],
+            None => vec![Action::Reply(
+                Json::object(vec![
+                    ("type", Json::String("error".to_string())),
+                    ("error", Json::String("invalid command".to_string())),
+                ])
+                .to_string(),
+            )],
+        }
+    }
+
+    fn output(&mut self, line: &str) -> Vec<String> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let Some((&kind, rest)) = words.split_first() else {
+            return Vec::new();
+        };
+        let number = |key: &str| field(&words, key).and_then(|value| value.parse::<i64>().ok());
+        let json = match (kind, number("depth"), field(&words, "eval"), number("time")) {
+            ("info", Some(depth), Some(eval), Some(time)) => {
+                let pv_at = words
+                    .iter()
+                    .position(|&word| word == "pv")
+                    .map_or(words.len(), |i| i + 1);
+                let pv = words[pv_at..]
+                    .iter()
+                    .take_while(|&&word| word != "eval")
+                    .map(|&mv| Json::String(mv.to_string()))
+                    .collect();
+                Json::object(vec![
+                    ("type", Json::String("info".to_string())),
+                    ("depth", Json::Number(depth)),
+                    ("pv", Json::Array(pv)),
+                    ("eval", Json::String(eval.to_string())),
+                    ("time", Json::Number(time)),
+                ])
+            }
+            _ => Json::object(vec![
+                ("type", Json::String(kind.to_string())),
+                (
+                    "args",
+                    Json::Array(
+                        rest.iter()
+                            .map(|&word| Json::String(word.to_string()))
+                            .collect(),
+                    ),
+                ),
+            ]),
+        };
+        vec![json.to_string()]
+    }
+}
+
+// The lines read from the frontend and from the engine, gathered on one channel.
+enum Line {
+    Frontend(String),
+    FrontendClosed,
+    Engine(String),
+    EngineExited,
+}
+
+/**
+ * Runs the engine at the given path as a child process, speaking the given protocol on standard input and output.
+ * Ends once the engine exits, which it does after `q` or once standard input closes.
+ */
+pub fn run(protocol: &mut dyn Protocol, engine: &str) -> std::io::Result<()> {
+    let mut child = Command::new(engine)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut input = child.stdin.take();
+    let output = child.stdout.take().unwrap();
+
+    let (sender, receiver) = channel();
+    let frontend = sender.clone();
+    thread::spawn(move || {
+        for line in stdin().lines().map_while(Result::ok) {
+            if frontend.send(Line::Frontend(line)).is_err() {
+                return;
+            }
+        }
+        frontend.send(Line::FrontendClosed).ok();
+    });
+    thread::spawn(move || {
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            if sender.send(Line::Engine(line)).is_err() {
+                return;
+            }
+        }
+        sender.send(Line::EngineExited).ok();
+    });
+
+    for line in receiver {
+        match line {
+            Line::Frontend(line) => {
+                for action in protocol.command(&line) {
+                    match action {
+                        // A command written after the engine has exited is dropped, since its exit is handled below.
+                        Action::Engine(command) => {
+                            if let Some(engine) = input.as_mut() {
+                                writeln!(engine, "{}", command).ok();
+                            }
+                        }
+                        Action::Reply(reply) => println!("{}", reply),
+                    }
+                }
+            }
+            // Closing the engine's input ends it as if its own standard input had closed.
+            Line::FrontendClosed => input = None,
+            Line::Engine(line) => {
+                for reply in protocol.output(&line) {
+                    println!("{}", reply);
+                }
+            }
+            Line::EngineExited => break,
+        }
+        stdout().flush()?;
+    }
+    child.wait()?;
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+use std::fs::write;
+
+use crate::arena::{game_result, GameRecord};
+use crate::db::positions;
+use crate::engine::*;
+use crate::zobrist;
+use ut3b2l::notation::*;
+
+// The depth each position is searched to when no depth is given.
+pub const DEFAULT_PUZZLE_DEPTH: usize = 8;
+
+// The kind of a puzzle: finding the only move that forces a win, or the only move that avoids a forced loss.
+#[derive(Clone, Copy, PartialEq)]
+enum PuzzleKind {
+    Win,
+    Save,
+}
+
+impl PuzzleKind {
+    fn name(self) -> &'static str {
+        match self {
+            PuzzleKind::Win => "win",
+            PuzzleKind::Save => "save",
+        }
+    }
+}
+
+// A position with a single solution, with the game and ply it was found at.
+struct Puzzle {
+    game: usize,
+    ply: usize,
+    board: Board,
+    side: bool,
+    kind: PuzzleKind,
+    solution: Move,
+    eval: i32,
+}
+
+impl Puzzle {
+    /**
+     * The puzzle as a line of a test suite file, as read by `testsuite`,
+     * so that a set of puzzles can be run as a suite.
+     * Winning puzzles also expect the win in the number of plies found.
+     */
+    fn to_line(&self, depth: usize) -> String {
+        let result = match self.kind {
+            PuzzleKind::Win => format!(" re {};", eval_string(self.eval, depth)),
+            PuzzleKind::Save => String::new(),
+        };
+        format!(
+            "{} bm {};{} id \"game {} ply {} {}\"",
+            board_string(self.board, self.side),
+            move_string(self.solution, Notation::Zone),
+            result,
+            self.game,
+            self.ply,
+            self.kind.name()
+        )
+    }
+}
+
+/**
+ * Finds whether a position is a puzzle at the given depth: either exactly one move forces a win,
+ * or every move but one allows a forced win for the opponent and that one does not.
+ * Positions with a single legal move have nothing to find, so are never puzzles.
+ */
+fn classify(board: Board, side: bool, depth: usize) -> Option<(PuzzleKind, Move, i32)> {
+    if game_result(board).is_some() || generate_moves(board).nth(1).is_none() {
+        return None;
+    }
+    let scores = root_move_scores(board, side, depth, None, &mut SearchControl::new(None));
+    let is_win = |eval: i32| eval >= OUTCOME_WIN - depth as i32;
+    let is_loss = |eval: i32| eval <= OUTCOME_LOSS + depth as i32;
+    // Only an interrupted search leaves moves unscored, and then there is nothing to tell.
+    let [(best, eval, _), (_, second, _), ..] = scores[..] else {
+        return None;
+    };
+    if is_win(eval) && !is_win(second) {
+        Some((PuzzleKind::Win, best, eval))
+    } else if !is_loss(eval) && is_loss(second) {
+        Some((PuzzleKind::Save, best, eval))
+    } else {
+        None
+    }
+}
+
+/**
+ * Searches every position of every game to the given depth, keeping those with a single solution.
+ * Positions symmetrical to one already searched are skipped, so each puzzle is only set once.
+ * Returns the number of positions searched, with the puzzles.
+ */
+fn find_puzzles(records: &[GameRecord], depth: usize) -> (usize, Vec<Puzzle>) {
+    let mut seen = HashSet::new();
+    let mut puzzles = Vec::new();
+    for (game, record) in records.iter().enumerate() {
+        for (ply, (board, side)) in positions(record).into_iter().enumerate() {
+            if !seen.insert(zobrist::canonical_hash(board, side)) {
+                continue;
+            }
+            if let Some((kind, solution, eval)) = classify(board, side, depth) {
+                println!(
+                    "db puzzle game {} ply {} {} move {} eval {}",
+                    game + 1,
+                    ply,
+                    kind.name(),
+                    move_string(solution, Notation::Zone),
+                    eval_string(eval, depth)
+                );
+                puzzles.push(Puzzle {
+                    game: game + 1,
+                    ply,
+                    board,
+                    side,
+                    kind,
+                    solution,
+                    eval,
+                });
+            }
+        }
+    }
+    (seen.len(), puzzles)
+}
+
+/**
+ * Writes the puzzles found in the positions of a file of game records as a test suite,
+ * and outputs `db puzzles positions <positions> puzzles <puzzles> win <wins> save <saves>`.
+ */
+pub fn write_puzzles(records: &[GameRecord], path: &str, depth: usize) -> Result<(), String> {
+    let (positions, puzzles) = find_puzzles(records, depth);
+    let mut lines = vec![format!(
+        "# Puzzles found at depth {} with `db puzzles`, for `testsuite`.",
+        depth
+    )];
+    lines.extend(puzzles.iter().map(|puzzle| puzzle.to_line(depth)));
+    write(path, lines.join("\n") + "\n")
+        .map_err(|error| format!("cannot write {}: {}", path, error))?;
+    let wins = puzzles
+        .iter()
+        .filter(|puzzle| puzzle.kind == PuzzleKind::Win)
+        .count();
+    println!(
+        "db puzzles positions {} puzzles {} win {} save {}",
+        positions,
+        puzzles.len(),
+        wins,
+        puzzles.len() - wins
+    );
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/**
+ * A small pseudorandom number generator (SplitMix64), for the parts of the engine that make random choices,
+ * such as the opening moves of self-play games.
+ * The same seed always gives the same sequence, so that a run can be repeated exactly.
+ */
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // A seed that differs between runs, for when none is given.
+    pub fn clock_seed() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // A number from 0 up to but not including `bound`, which must be positive.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        // The bias of taking the remainder is negligible for the small bounds used here.
+        self.next_u64() % bound
+    }
+}
@@ -0,0 +1,326 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::arena::{game_result, write_record, GameRecord, GameResult, TimeControl, MOVES_TO_GO};
+use crate::display::board_lines;
+use crate::engine::*;
+use crate::illegal_move_string;
+use crate::timeman::TimeManager;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB};
+use ut3b2l::notation::*;
+
+// How often the clock of the side to move is checked while waiting for its move.
+const CLOCK_POLL: Duration = Duration::from_millis(50);
+
+// Everything the arbiter waits on, from the thread accepting connections and the thread reading each one.
+enum Event {
+    Connected(TcpStream),
+    Line(usize, String),
+    Disconnected(usize),
+}
+
+// A connected client, seated as one of the players or watching as a spectator.
+struct Client {
+    stream: TcpStream,
+    name: String,
+    seat: Option<bool>,
+}
+
+// Reads the lines of a client on its own thread, until it disconnects.
+fn read_client(id: usize, stream: TcpStream, events: Sender<Event>) {
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        if events.send(Event::Line(id, line)).is_err() {
+            return;
+        }
+    }
+    events.send(Event::Disconnected(id)).ok();
+}
+
+// The game being arbitrated, with the remaining time of each player in milliseconds.
+struct Game {
+    board: Board,
+    side: bool,
+    clocks: [u64; 2],
+    turn_start: Instant,
+    record: GameRecord,
+}
+
+impl Game {
+    // The remaining time of each player, counting the time the side to move has used so far.
+    fn remaining(&self) -> [u64; 2] {
+        let mut clocks = self.clocks;
+        let elapsed = self.turn_start.elapsed().as_millis() as u64;
+        clocks[self.side as usize] = clocks[self.side as usize].saturating_sub(elapsed);
+        clocks
+    }
+
+    fn state(&self) -> String {
+        let [x, o] = self.remaining();
+        format!(
+            "relay position {} clock {} {}",
+            board_string(self.board, self.side),
+            x,
+            o
+        )
+    }
+
+    // Plays a move of the side to move, charging its clock and adding its increment,
+    // and returns whether it was played in time.
+    fn play(&mut self, mv: Move, search: Option<(i32, usize)>, increment_ms: u64) -> bool {
+        let i = self.side as usize;
+        let elapsed = self.turn_start.elapsed().as_millis() as u64;
+        if elapsed > self.clocks[i] {
+            return false;
+        }
+        self.clocks[i] = self.clocks[i] - elapsed + increment_ms;
+        self.turn_start = Instant::now();
+        self.board = play_move(self.board, mv, self.side);
+        self.side = !self.side;
+        self.record.moves.push((mv, search));
+        true
+    }
+}
+
+fn send(clients: &mut [Option<Client>], id: usize, line: &str) {
+    if let Some(client) = clients[id].as_mut() {
+        writeln!(client.stream, "{}", line).ok();
+    }
+}
+
+fn broadcast(clients: &mut [Option<Client>], line: &str) {
+    for client in clients.iter_mut().flatten() {
+        writeln!(client.stream, "{}", line).ok();
+    }
+}
+
+/**
+ * Hosts a game between two remote players, as `relay <address> [--tc <base>[+<increment>]] [--engine x|o] [--out <file>]`.
+ * The first two clients to connect play as Player X and Player O, or the first plays against the engine
+ * if it is given a side with `--engine`, and any later clients watch as spectators.
+ * The relay arbitrates the game: it accepts only legal moves from the side to move,
+ * runs each player's clock while it is their turn, and sends the position to every client after each move.
+ * The game is lost by a player who runs out of time, resigns or disconnects, after which the relay exits.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut args = args.iter();
+    let address = args.next().ok_or("relay needs an address")?.clone();
+    let mut time_control = TimeControl {
+        base_ms: 300000,
+        increment_ms: 2000,
+    };
+    let mut engine_side = None;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--tc" => {
+                time_control = TimeControl::parse(value)
+                    .ok_or_else(|| format!("invalid time control {}", value))?
+            }
+            "--engine" => {
+                engine_side = match value.as_str() {
+                    "x" => Some(false),
+                    "o" => Some(true),
+                    _ => return Err(format!("invalid side {}", value)),
+                }
+            }
+            "--out" => output = Some(value.clone()),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    let listener = TcpListener::bind(&address)
+        .map_err(|error| format!("cannot listen on {}: {}", address, error))?;
+    eprintln!("listening on {}", address);
+    let (sender, receiver) = channel();
+    let accept_sender = sender.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if accept_sender.send(Event::Connected(stream)).is_err() {
+                return;
+            }
+        }
+    });
+
+    let table = Arc::new(TranspositionTable::new(DEFAULT_HASH_MB, Replacement::Aging));
+    let mut clients: Vec<Option<Client>> = Vec::new();
+    // The client seated as each player, where the engine's seat is never filled by a client.
+    let mut seats: [Option<usize>; 2] = [None; 2];
+    let mut game: Option<Game> = None;
+    let (result, termination) = loop {
+        if let Some(game) = game.as_mut() {
+            if let Some(result) = game_result(game.board) {
+                break (result, "finished".to_string());
+            }
+            if game.remaining()[game.side as usize] == 0 {
+                break (
+                    GameResult::win_for(!game.side),
+                    format!("time forfeit {}", player_string(game.side)),
+                );
+            }
+            // The engine thinks in this thread, so clients wait for its move before being answered.
+            if engine_side == Some(game.side) {
+                let clock = game.remaining()[game.side as usize];
+                let movetime = (clock / MOVES_TO_GO + time_control.increment_ms).min(clock);
+                let time = TimeManager::new(movetime.max(1));
+                table.new_search();
+                let mut control = SearchControl::new(None).with_table(table.clone());
+                let (depth, eval, line) =
+                    iterative_deepening(game.board, game.side, MAX_PLY, Some(&time), &mut control);
+                let mover = game.side;
+                if game.play(line[0], Some((eval, depth)), time_control.increment_ms) {
+                    broadcast(
+                        &mut clients,
+                        &format!(
+                            "relay move {} {}",
+                            player_string(mover),
+                            move_string(line[0], Notation::Zone)
+                        ),
+                    );
+                    broadcast(&mut clients, &game.state());
+                }
+                continue;
+            }
+        }
+
+        let Ok(event) = receiver.recv_timeout(CLOCK_POLL) else {
+            continue;
+        };
+        match event {
+            Event::Connected(stream) => {
+                let id = clients.len();
+                let Ok(reader) = stream.try_clone() else {
+                    clients.push(None);
+                    continue;
+                };
+                let events = sender.clone();
+                thread::spawn(move || read_client(id, reader, events));
+                let seat = [false, true]
+                    .into_iter()
+                    .find(|&side| seats[side as usize].is_none() && engine_side != Some(side));
+                let name = stream
+                    .peer_addr()
+                    .map_or("unknown".to_string(), |address| address.to_string());
+                clients.push(Some(Client { stream, name, seat }));
+                if let Some(side) = seat {
+                    seats[side as usize] = Some(id);
+                }
+                let seat_name = seat.map_or("spectator", player_string);
+                send(&mut clients, id, &format!("relay seat {}", seat_name));
+
+                // The game starts once every seat not taken by the engine is filled.
+                let filled = [false, true]
+                    .map(|side| seats[side as usize].is_some() || engine_side == Some(side));
+                if game.is_none() && filled == [true, true] {
+                    let names = [false, true].map(|side| match seats[side as usize] {
+                        Some(id) => clients[id].as_ref().unwrap().name.clone(),
+                        None => "ut3b2l".to_string(),
+                    });
+                    let [x_name, o_name] = names;
+                    game = Some(Game {
+                        board: (0, 0, ZONE_ANY << 54),
+                        side: false,
+                        clocks: [time_control.base_ms; 2],
+                        turn_start: Instant::now(),
+                        record: GameRecord {
+                            x_name,
+                            o_name,
+                            position: "9/9/9/9/9/9/9/9/9 any x".to_string(),
+                            moves: Vec::new(),
+                            result: GameResult::Draw,
+                            termination: String::new(),
+                        },
+                    });
+                    broadcast(&mut clients, "relay start");
+                    broadcast(&mut clients, &game.as_ref().unwrap().state());
+                } else if let Some(game) = &game {
+                    send(&mut clients, id, &game.state());
+                }
+            }
+            Event::Line(id, line) => {
+                let Some(seat) = clients[id].as_ref().map(|client| client.seat) else {
+                    continue;
+                };
+                let words: Vec<_> = line.split_whitespace().collect();
+                match (words.first().copied(), game.as_mut()) {
+                    (Some("board"), Some(game)) => {
+                        send(&mut clients, id, &game.state());
+                        let last_move = game.record.moves.last().map_or(NULL_MOVE, |&(mv, _)| mv);
+                        for line in board_lines(game.board, game.side, last_move) {
+                            send(&mut clients, id, &line);
+                        }
+                    }
+                    (Some("play"), Some(game)) if seat == Some(game.side) => {
+                        let Some(mv) = words.get(1).and_then(|mv| move_from_string(mv)) else {
+                            send(&mut clients, id, "relay invalid move");
+                            continue;
+                        };
+                        if let Err(illegal) = check_move(game.board, mv) {
+                            let error = format!("relay illegal {}", illegal_move_string(illegal));
+                            send(&mut clients, id, &error);
+                            continue;
+                        }
+                        let mover = game.side;
+                        if game.play(mv, None, time_control.increment_ms) {
+                            broadcast(
+                                &mut clients,
+                                &format!(
+                                    "relay move {} {}",
+                                    player_string(mover),
+                                    move_string(mv, Notation::Zone)
+                                ),
+                            );
+                            broadcast(&mut clients, &game.state());
+                        }
+                    }
+                    (Some("play"), Some(_)) => send(&mut clients, id, "relay notyourturn"),
+                    (Some("resign"), Some(_)) if seat.is_some() => {
+                        let side = seat.unwrap();
+                        break (
+                            GameResult::win_for(!side),
+                            format!("resign {}", player_string(side)),
+                        );
+                    }
+                    (Some("board" | "play" | "resign"), None) => {
+                        send(&mut clients, id, "relay waiting")
+                    }
+                    _ => send(&mut clients, id, "relay invalid command"),
+                }
+            }
+            Event::Disconnected(id) => {
+                let Some(client) = clients[id].take() else {
+                    continue;
+                };
+                if let Some(side) = client.seat {
+                    // A player leaving before the game starts frees their seat for someone else.
+                    if game.is_some() {
+                        break (
+                            GameResult::win_for(!side),
+                            format!("disconnect {}", player_string(side)),
+                        );
+                    }
+                    seats[side as usize] = None;
+                }
+            }
+        }
+    };
+
+    broadcast(
+        &mut clients,
+        &format!("relay result {} {}", result.score_string(), termination),
+    );
+    println!("relay result {} {}", result.score_string(), termination);
+    if let Some(mut game) = game {
+        game.record.result = result;
+        game.record.termination = termination;
+        write_record(&output, &game.record)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::write;
+use std::sync::Arc;
+
+use crate::arena::game_result;
+use crate::engine::*;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB};
+use crate::zobrist;
+use ut3b2l::book::{Book, BookEntry};
+use ut3b2l::notation::*;
+
+// The defaults of the tree: how many plies it covers, how many moves are kept at each position, and the search depth.
+const DEFAULT_PLIES: usize = 4;
+const DEFAULT_TOP: usize = 2;
+const DEFAULT_DEPTH: usize = 8;
+
+// Everything gathered while expanding the tree.
+struct Tree {
+    top: usize,
+    depth: usize,
+    table: Arc<TranspositionTable>,
+    // The canonical hashes of the positions already expanded, so that each is only expanded once.
+    expanded: HashSet<u64>,
+    lines: Vec<String>,
+    entries: HashMap<(u64, Move), BookEntry>,
+}
+
+impl Tree {
+    /**
+     * Searches every move of a position, keeps the best `top` moves, and expands the position after each of them
+     * until `plies` plies have been played from the root.
+     * Of moves leading to positions symmetrical to each other, only the best is kept,
+     * and a position reached again by another order of moves is listed but not expanded again.
+     */
+    fn expand(&mut self, board: Board, side: bool, plies: usize, indent: usize) {
+        if plies == 0 || game_result(board).is_some() {
+            return;
+        }
+        let mut control = SearchControl::new(None).with_table(self.table.clone());
+        self.table.new_search();
+        let scores = root_move_scores(board, side, self.depth, None, &mut control);
+        let mut seen = HashSet::new();
+        let kept: Vec<_> = scores
+            .into_iter()
+            .filter(|&(mv, _, _)| {
+                seen.insert(zobrist::canonical_hash(play_move(board, mv, side), !side))
+            })
+            .take(self.top)
+            .collect();
+        for (rank, &(mv, eval, _)) in kept.iter().enumerate() {
+            let entry = Book::entry(board, side, mv, (self.top - rank) as u16, 0);
+            self.entries.entry((entry.key, entry.mv)).or_insert(entry);
+
+            let child = play_move(board, mv, side);
+            let first_visit = self.expanded.insert(zobrist::canonical_hash(child, !side));
+            self.lines.push(format!(
+                "{}{} {}{}",
+                "  ".repeat(indent),
+                move_string(mv, Notation::Zone),
+                eval_string(eval, self.depth),
+                if first_visit { "" } else { " transposition" }
+            ));
+            if first_visit {
+                self.expand(child, !side, plies - 1, indent + 1);
+            }
+        }
+    }
+}
+
+/**
+ * Expands the tree of the best openings from a position, as
+ * `repertoire [--plies <plies>] [--top <moves>] [--depth <depth>] [--position <position>] [--out <file>] [--book <file>]`.
+ * Every move of each position is searched to the given depth (8 by default), and the best `top` (2 by default)
+ * are kept and expanded in turn, to `plies` plies from the position (4 by default), which is the empty board by default.
+ * The tree is written as text, with each move on its own line, indented by two spaces for each ply,
+ * and followed by its evaluation, to the file given by `--out` or to standard output,
+ * and as an opening book to the file given by `--book`.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut plies = DEFAULT_PLIES;
+    let mut top = DEFAULT_TOP;
+    let mut depth = DEFAULT_DEPTH;
+    let mut position = "9/9/9/9/9/9/9/9/9 any x".to_string();
+    let mut output = None;
+    let mut book_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?;
+        let number = || {
+            value
+                .parse::<usize>()
+                .ok()
+                .filter(|&number| number > 0)
+                .ok_or_else(|| format!("{} needs a positive number", arg))
+        };
+        match arg.as_str() {
+            "--plies" => plies = number()?,
+            "--top" => top = number()?.min(u16::MAX as usize),
+            "--depth" => {
+                depth = Some(number()?)
+                    .filter(|&depth| depth <= MAX_PLY)
+                    .ok_or_else(|| format!("depth must be at most {}", MAX_PLY))?
+            }
+            "--position" => position = value.clone(),
+            "--out" => output = Some(value.clone()),
+            "--book" => book_path = Some(value.clone()),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    let (board, side) =
+        board_from_string(&position).ok_or_else(|| format!("invalid position {}", position))?;
+
+    let mut tree = Tree {
+        top,
+        depth,
+        table: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB, Replacement::Aging)),
+        expanded: HashSet::from([zobrist::canonical_hash(board, side)]),
+        lines: Vec::new(),
+        entries: HashMap::new(),
+    };
+    tree.expand(board, side, plies, 0);
+
+    let text = tree.lines.join("\n") + "\n";
+    match output {
+        Some(path) => {
+            write(&path, text).map_err(|error| format!("cannot write {}: {}", path, error))?
+        }
+        None => print!("{}", text),
+    }
+    let book = Book::new(tree.entries.into_values().collect());
+    if let Some(path) = book_path {
+        write(&path, book.to_bytes())
+            .map_err(|error| format!("cannot write {}: {}", path, error))?;
+    }
+    println!(
+        "repertoire moves {} positions {} entries {}",
+        tree.lines.len(),
+        tree.expanded.len(),
+        book.entries().len()
+    );
+    Ok(())
+}
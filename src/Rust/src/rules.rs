@@ -0,0 +1,178 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::notation::ZONE_ARRAY_LOWER;
+
+/**
+ * What a zone that has been won, but still has empty cells, allows, which differs between rule sets.
+ * Under `Closed`, the usual rules, no more moves can be made in it, and being sent to it lets the player play in any zone.
+ * Under `Open`, moves can still be made in it, and being sent to it means playing there like any other zone.
+ * Under `Free`, moves can still be made in it, but being sent to it lets the player play in any zone.
+ * Either way, a zone only ever counts for the player who won it first.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum WonZones {
+    Closed,
+    Open,
+    Free,
+}
+
+/**
+ * How the game is won, set with `setoption Variant`.
+ * Under `Standard`, the player who forms a line of zones wins, and under `Misere` that player loses.
+ * Under `Majority`, the player who forms a line of zones also wins, but a game that ends without one
+ * is won by the player who won more zones, and is only drawn if both won as many.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum Variant {
+    Standard,
+    Misere,
+    Majority,
+}
+
+/**
+ * Which first moves a game from the empty board allows, set with `setoption FirstMove`,
+ * as organised events use to reduce the advantage of moving first.
+ * Under `Any`, the usual rules, every cell is allowed, and under `NoCenter`, every cell but the centre of the centre zone.
+ * Under `Zone`, the first move must be made in the given zone.
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum FirstMove {
+    Any,
+    NoCenter,
+    Zone(u64),
+}
+
+/*
+ * Each rule is read from the value given to `setoption`, in any case,
+ * so that the processes that referee games between engines can apply the same rules as the engines.
+ */
+
+impl WonZones {
+    pub fn parse(value: &str) -> Option<Self> {
+        match &value.to_lowercase() as &str {
+            "closed" => Some(WonZones::Closed),
+            "open" => Some(WonZones::Open),
+            "free" => Some(WonZones::Free),
+            _ => None,
+        }
+    }
+}
+
+impl Variant {
+    pub fn parse(value: &str) -> Option<Self> {
+        match &value.to_lowercase() as &str {
+            "standard" => Some(Variant::Standard),
+            "misere" => Some(Variant::Misere),
+            "majority" => Some(Variant::Majority),
+            _ => None,
+        }
+    }
+}
+
+impl FirstMove {
+    // A zone is given by its name, such as `nw`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.to_lowercase();
+        match &value as &str {
+            "any" => Some(FirstMove::Any),
+            "nocenter" => Some(FirstMove::NoCenter),
+            _ => ZONE_ARRAY_LOWER
+                .iter()
+                .position(|&zone| zone == value)
+                .map(|zone| FirstMove::Zone(zone as u64)),
+        }
+    }
+}
+
+// The rules in force, shared by every search of the process and changed with `setoption`.
+static WON_ZONES: AtomicU8 = AtomicU8::new(WonZones::Closed as u8);
+static VARIANT: AtomicU8 = AtomicU8::new(Variant::Standard as u8);
+// The first-move rule is `Any` as 0, `NoCenter` as 1 and `Zone` as 2 more than its zone.
+static FIRST_MOVE: AtomicU8 = AtomicU8::new(0);
+// Whether the pie rule is in force, letting the second player take over the first move of a game with `swap`.
+static PIE: AtomicBool = AtomicBool::new(false);
+
+pub fn won_zones() -> WonZones {
+    match WON_ZONES.load(Ordering::Relaxed) {
+        1 => WonZones::Open,
+        2 => WonZones::Free,
+        _ => WonZones::Closed,
+    }
+}
+
+pub fn set_won_zones(rule: WonZones) {
+    WON_ZONES.store(rule as u8, Ordering::Relaxed);
+}
+
+pub fn variant() -> Variant {
+    match VARIANT.load(Ordering::Relaxed) {
+        1 => Variant::Misere,
+        2 => Variant::Majority,
+        _ => Variant::Standard,
+    }
+}
+
+pub fn set_variant(variant: Variant) {
+    VARIANT.store(variant as u8, Ordering::Relaxed);
+}
+
+pub fn first_move() -> FirstMove {
+    match FIRST_MOVE.load(Ordering::Relaxed) {
+        0 => FirstMove::Any,
+        1 => FirstMove::NoCenter,
+        zone => FirstMove::Zone(zone as u64 - 2),
+    }
+}
+
+pub fn set_first_move(rule: FirstMove) {
+    let value = match rule {
+        FirstMove::Any => 0,
+        FirstMove::NoCenter => 1,
+        FirstMove::Zone(zone) => zone as u8 + 2,
+    };
+    FIRST_MOVE.store(value, Ordering::Relaxed);
+}
+
+pub fn pie() -> bool {
+    PIE.load(Ordering::Relaxed)
+}
+
+pub fn set_pie(pie: bool) {
+    PIE.store(pie, Ordering::Relaxed);
+}
+
+// Puts the usual rules back in force.
+#[cfg(test)]
+fn reset() {
+    set_won_zones(WonZones::Closed);
+    set_variant(Variant::Standard);
+    set_first_move(FirstMove::Any);
+    set_pie(false);
+}
+
+/**
+ * The rules are shared by every test of the process, so a test that depends on them holds one of these,
+ * which gives it the usual rules to change as it likes, and puts them back when dropped.
+ */
+#[cfg(test)]
+pub(crate) struct TestRules {
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl TestRules {
+    pub(crate) fn standard() -> Self {
+        static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        // A test that failed while holding the lock has still put the rules back as it unwound.
+        let guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        reset();
+        Self { _lock: guard }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestRules {
+    fn drop(&mut self) {
+        reset();
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use crate::arena::{game_result, write_record, GameRecord, GameResult};
+use crate::engine::*;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB};
+use ut3b2l::notation::*;
+use ut3b2l::random::Random;
+
+// The default limits of a run: the number of games, the nodes searched for each move, and the random opening plies.
+const DEFAULT_GAMES: usize = 10;
+const DEFAULT_NODES: u64 = 100000;
+const DEFAULT_RANDOM_PLIES: usize = 4;
+
+// The name both players are given in the records.
+const PLAYER_NAME: &str = "selfplay";
+
+/**
+ * Plays one game of the engine against itself from the empty board.
+ * The first `random_plies` moves are chosen uniformly at random, and are recorded without a search,
+ * and every move after that is the result of a search limited to `nodes` nodes.
+ */
+fn play_game(
+    random: &mut Random,
+    table: &Arc<TranspositionTable>,
+    nodes: u64,
+    random_plies: usize,
+) -> GameRecord {
+    let (mut board, mut side) = ((0, 0, ZONE_ANY << 54), false);
+    let mut record = GameRecord {
+        x_name: PLAYER_NAME.to_string(),
+        o_name: PLAYER_NAME.to_string(),
+        position: board_string(board, side),
+        moves: Vec::new(),
+        result: GameResult::Draw,
+        termination: "finished".to_string(),
+    };
+    // Each game starts with an empty table, so that it does not depend on the games before it.
+    table.clear();
+    loop {
+        if let Some(result) = game_result(board) {
+            record.result = result;
+            return record;
+        }
+        let (mv, search) = if record.moves.len() < random_plies {
+            let moves: Vec<_> = generate_moves(board).collect();
+            (moves[random.below(moves.len() as u64) as usize], None)
+        } else {
+            table.new_search();
+            let mut control = SearchControl::new(None)
+                .with_table(table.clone())
+                .with_node_limit(nodes);
+            let (depth, eval, line) = iterative_deepening(board, side, MAX_PLY, None, &mut control);
+            (line[0], Some((eval, depth)))
+        };
+        board = play_move(board, mv, side);
+        side = !side;
+        record.moves.push((mv, search));
+    }
+}
+
+// Reads a positive number given after a flag.
+fn positive<T: std::str::FromStr + Default + PartialOrd>(
+    value: &str,
+    flag: &str,
+) -> Result<T, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|number| *number > T::default())
+        .ok_or_else(|| format!("{} needs a positive number", flag))
+}
+
+/**
+ * Plays the engine against itself, as
+ * `selfplay --out <file> [--games <games>] [--nodes <nodes>] [--random-plies <plies>] [--seed <seed>]`,
+ * appending a game record of each game to the file, as written by `--out` in matches.
+ * Games are searched in-process, with each move limited to a number of nodes rather than a time,
+ * so that a run with the same seed plays the same games on any machine.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut output = None;
+    let mut games = DEFAULT_GAMES;
+    let mut nodes = DEFAULT_NODES;
+    let mut random_plies = DEFAULT_RANDOM_PLIES;
+    let mut seed = Random::clock_seed();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--out" => output = Some(value.clone()),
+            "--games" => games = positive(value, arg)?,
+            "--nodes" => nodes = positive(value, arg)?,
+            "--random-plies" => {
+                random_plies = value
+                    .parse()
+                    .map_err(|_| format!("{} needs a number", arg))?
+            }
+            "--seed" => {
+                seed = value
+                    .parse()
+                    .map_err(|_| format!("{} needs a number", arg))?
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    if output.is_none() {
+        return Err("selfplay needs --out".to_string());
+    }
+    // The seed is written out so that a run without one can be repeated.
+    println!("selfplay seed {}", seed);
+
+    let mut random = Random::new(seed);
+    let table = Arc::new(TranspositionTable::new(DEFAULT_HASH_MB, Replacement::Aging));
+    // The games won by Player X, drawn and won by Player O.
+    let mut score = [0; 3];
+    for game in 0..games {
+        let record = play_game(&mut random, &table, nodes, random_plies);
+        write_record(&output, &record)?;
+        score[match record.result {
+            GameResult::XWins => 0,
+            GameResult::Draw => 1,
+            GameResult::OWins => 2,
+        }] += 1;
+        println!(
+            "selfplay game {} {} plies {}",
+            game + 1,
+            record.result.score_string(),
+            record.moves.len()
+        );
+    }
+    println!(
+        "selfplay games {} x {} draws {} o {}",
+        games, score[0], score[1], score[2]
+    );
+    Ok(())
+}
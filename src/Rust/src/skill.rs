@@ -0,0 +1,86 @@
+use crate::engine::*;
+use crate::random::Random;
+use crate::timeman::TimeManager;
+
+// The skill level of full strength, which is the default, and the lowest level.
+pub const MAX_SKILL: u32 = 10;
+pub const MIN_SKILL: u32 = 1;
+
+// How much noise is added to the evaluation of each root move for every level below full strength.
+const NOISE_PER_LEVEL: i32 = 20;
+
+/**
+ * A playing strength from `MIN_SKILL` to `MAX_SKILL`, set with `setoption Skill`, with the source of its random choices.
+ * Below full strength, the search is capped at a depth equal to the level, every root move is searched
+ * with a full window so that each has an exact evaluation, and the move played is the one whose evaluation is best
+ * after adding random noise to each, of up to `NOISE_PER_LEVEL` for every level below full strength.
+ * Weaker levels therefore both see less and often prefer a move that is a little worse than the best,
+ * while a move that wins or loses by force is still told apart from the others.
+ */
+pub struct Skill {
+    pub level: u32,
+    random: Random,
+}
+
+impl Skill {
+    pub fn new(level: u32, seed: u64) -> Self {
+        Self {
+            level,
+            random: Random::new(seed),
+        }
+    }
+
+    // The same level with a source of random choices of its own, for a search on another thread.
+    pub fn fork(&mut self) -> Self {
+        Self::new(self.level, self.random.next_u64())
+    }
+
+    pub fn is_full_strength(&self) -> bool {
+        self.level >= MAX_SKILL
+    }
+
+    // The deepest a search at this level may go.
+    pub fn depth_cap(&self) -> usize {
+        if self.is_full_strength() {
+            MAX_PLY
+        } else {
+            self.level as usize
+        }
+    }
+
+    fn noise(&self) -> i32 {
+        (MAX_SKILL - self.level.min(MAX_SKILL)) as i32 * NOISE_PER_LEVEL
+    }
+
+    /**
+     * Searches a position below full strength to at most the given depth, returning the depth searched,
+     * with the evaluation and principal variation of the move chosen.
+     * The move is chosen only among those whose search finished before the search was stopped or the time ran out.
+     * If none did, the moves are searched again to a depth of one without any limit, so that a move is always chosen.
+     * Where there are no moves, this is the same as `iterative_deepening` without a time limit.
+     */
+    pub fn search(
+        &mut self,
+        board: Board,
+        side: bool,
+        depth: usize,
+        time: Option<&TimeManager>,
+        control: &mut SearchControl,
+    ) -> (usize, i32, [Move; MAX_PLY]) {
+        let mut depth = depth.min(self.depth_cap());
+        let mut scores = root_move_scores(board, side, depth, time, control);
+        if scores.is_empty() && control.stopped {
+            depth = 1;
+            scores = root_move_scores(board, side, depth, None, &mut SearchControl::new(None));
+        }
+        let noise = self.noise() as u64;
+        let random = &mut self.random;
+        let chosen = scores.into_iter().max_by_key(|&(_, eval, _)| {
+            eval.saturating_add(random.below(2 * noise + 1) as i32 - noise as i32)
+        });
+        match chosen {
+            Some((_, eval, line)) => (depth, eval, line),
+            None => iterative_deepening(board, side, depth, None, control),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+/**
+ * Serves a single long-lived engine process on a Unix domain socket at the given path,
+ * so that supervising programs can attach to it and detach from it without owning its standard input and output.
+ * One client is attached at a time: its lines are passed to the engine as commands,
+ * and the engine's output is passed back to it, and is discarded while no client is attached.
+ * Closing the connection detaches the client, leaving the engine and its game as they were for the next one,
+ * while `q` ends the engine and the server with it.
+ */
+#[cfg(unix)]
+pub fn serve(path: &str) {
+    use std::fs::{remove_file, symlink_metadata};
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::process::{Command, Stdio};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    // A socket left behind by a server that did not exit cleanly is replaced,
+    // but one that is still being served, or any other kind of file, is left alone.
+    if symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket())
+        && UnixStream::connect(path).is_err()
+    {
+        remove_file(path).ok();
+    }
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("cannot listen on {}: {}", path, error);
+            return;
+        }
+    };
+    let child = std::env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+    });
+    let mut child = match child {
+        Ok(child) => child,
+        Err(error) => {
+            eprintln!("cannot start the engine: {}", error);
+            return;
+        }
+    };
+    let input = Arc::new(Mutex::new(child.stdin.take().unwrap()));
+    let output = child.stdout.take().unwrap();
+    eprintln!("listening on {}", path);
+
+    // The connection of the attached client, if any, which receives everything the engine writes.
+    // Each is numbered, so that a client that has left cannot detach the one after it.
+    let client: Arc<Mutex<Option<(usize, UnixStream)>>> = Arc::new(Mutex::new(None));
+    let accept_client = client.clone();
+    thread::spawn(move || {
+        for (id, stream) in listener.incoming().flatten().enumerate() {
+            let mut attached = accept_client.lock().unwrap();
+            if attached.is_some() {
+                let mut stream = stream;
+                writeln!(stream, "busy").ok();
+                continue;
+            }
+            let Ok(reader) = stream.try_clone() else {
+                continue;
+            };
+            *attached = Some((id, stream));
+            drop(attached);
+
+            let (input, client) = (input.clone(), accept_client.clone());
+            thread::spawn(move || {
+                for line in BufReader::new(reader).lines() {
+                    let Ok(line) = line else { break };
+                    if writeln!(input.lock().unwrap(), "{}", line).is_err() {
+                        break;
+                    }
+                }
+                let mut attached = client.lock().unwrap();
+                if attached
+                    .as_ref()
+                    .is_some_and(|&(attached_id, _)| attached_id == id)
+                {
+                    *attached = None;
+                }
+            });
+        }
+    });
+
+    for line in BufReader::new(output).lines() {
+        let Ok(line) = line else { break };
+        let mut attached = client.lock().unwrap();
+        if let Some((_, stream)) = attached.as_mut() {
+            if writeln!(stream, "{}", line).is_err() {
+                *attached = None;
+            }
+        }
+    }
+    child.wait().ok();
+    remove_file(path).ok();
+}
+
+// Named pipes on other platforms would need platform code beyond the standard library.
+#[cfg(not(unix))]
+pub fn serve(_path: &str) {
+    eprintln!("local sockets are only supported on Unix");
+}
@@ -0,0 +1,249 @@
+use crate::arena::*;
+
+// The default hypotheses, in Elo, and error rates of a test.
+const DEFAULT_ELO0: f64 = 0.0;
+const DEFAULT_ELO1: f64 = 5.0;
+const DEFAULT_ALPHA: f64 = 0.05;
+const DEFAULT_BETA: f64 = 0.05;
+
+// The z-score of the 95% confidence interval reported around the Elo estimate.
+const CONFIDENCE_Z: f64 = 1.96;
+
+// The count used in place of each pentanomial count of 0 when estimating the moments, as fishtest does,
+// so that the variance is never 0 and a test in which every pair scores the same still reaches a bound.
+const REGULARISATION: f64 = 1e-3;
+
+// The expected score of an engine that is the given number of Elo stronger than its opponent.
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// The Elo difference of an engine with the given expected score, which must lie strictly between 0 and 1.
+pub fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/**
+ * The results of a test so far, as the number of game pairs in which the tested engine scored
+ * each of 0, 1/2, 1, 3/2 and 2 points (the pentanomial counts).
+ * Counting pairs, where each opening is played once with each side, rather than single games
+ * accounts for the correlation between the two games of an opening.
+ */
+#[derive(Default)]
+pub struct Pentanomial {
+    pub counts: [u64; 5],
+}
+
+impl Pentanomial {
+    pub fn pairs(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    // The mean score per game, and the variance of the mean score of a pair, from the regularised counts.
+    fn moments(&self) -> (f64, f64) {
+        let counts = self.counts.map(|count| match count {
+            0 => REGULARISATION,
+            _ => count as f64,
+        });
+        let pairs = counts.iter().sum::<f64>();
+        let mean = (0..5).map(|i| counts[i] * i as f64 / 4.0).sum::<f64>() / pairs;
+        let variance = (0..5)
+            .map(|i| counts[i] * (i as f64 / 4.0 - mean).powi(2))
+            .sum::<f64>()
+            / pairs;
+        (mean, variance)
+    }
+
+    /**
+     * The log-likelihood ratio of `elo1` against `elo0`, by the normal approximation of the
+     * generalised SPRT, which needs no model of how likely draws are.
+     * This is 0 until at least two pairs have been played.
+     */
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        let (mean, variance) = self.moments();
+        if self.pairs() < 2 {
+            return 0.0;
+        }
+        let (s0, s1) = (expected_score(elo0), expected_score(elo1));
+        self.pairs() as f64 * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance)
+    }
+
+    // The estimated Elo difference, with the lower and upper ends of its 95% confidence interval.
+    pub fn elo(&self) -> (f64, f64, f64) {
+        let (mean, variance) = self.moments();
+        let margin = CONFIDENCE_Z * (variance / self.pairs() as f64).sqrt();
+        // Scores of exactly 0 or 1 have unbounded Elo, so they are kept just inside.
+        let bounded = |score: f64| elo_from_score(score.clamp(1e-6, 1.0 - 1e-6));
+        (
+            bounded(mean),
+            bounded(mean - margin),
+            bounded(mean + margin),
+        )
+    }
+}
+
+// Reads a number given after a flag.
+fn number(value: Option<&String>, flag: &str) -> Result<f64, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("{} needs a number", flag))
+}
+
+// How a test ended: by accepting `elo1` or `elo0`, or without a decision once `--max-pairs` pairs were played.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Decision {
+    Elo1,
+    Elo0,
+    Inconclusive,
+}
+
+// Runs a test as described for `test`, for the `sprt` subcommand.
+pub fn run(args: &[String]) -> Result<(), String> {
+    test(args).map(|_| ())
+}
+
+/**
+ * Runs a sequential probability ratio test of whether the first engine is stronger than the second,
+ * as `sprt --engine <path> ... --engine <path> ... [--elo0 <elo>] [--elo1 <elo>] [--alpha <rate>] [--beta <rate>]`.
+ * Pairs of games are played until the log-likelihood ratio of `elo1` (5 by default) against `elo0`
+ * (0 by default) passes the bound for accepting either, with error rates `alpha` and `beta` (both 0.05 by default),
+ * or until `--max-pairs` pairs have been played without a decision if given.
+ * The other arguments are those of `match`, and the state of the test is written after every pair.
+ */
+pub fn test(args: &[String]) -> Result<Decision, String> {
+    let settings = parse_settings(args)?;
+    if settings.engines.len() != 2 {
+        return Err("a test needs exactly two engines".to_string());
+    }
+    let (mut elo0, mut elo1) = (DEFAULT_ELO0, DEFAULT_ELO1);
+    let (mut alpha, mut beta) = (DEFAULT_ALPHA, DEFAULT_BETA);
+    let mut max_pairs = None;
+    let mut rest = settings.rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--elo0" => elo0 = number(rest.next(), arg)?,
+            "--elo1" => elo1 = number(rest.next(), arg)?,
+            "--alpha" => alpha = number(rest.next(), arg)?,
+            "--beta" => beta = number(rest.next(), arg)?,
+            "--max-pairs" => max_pairs = Some(number(rest.next(), arg)? as u64),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    if elo1 <= elo0 {
+        return Err("elo1 must be greater than elo0".to_string());
+    }
+    if [alpha, beta].iter().any(|&rate| rate <= 0.0 || rate >= 0.5) {
+        return Err("alpha and beta must be between 0 and 0.5".to_string());
+    }
+    let lower = (beta / (1.0 - alpha)).ln();
+    let upper = ((1.0 - beta) / alpha).ln();
+
+    let mut tested = EngineProcess::start(&settings.engines[0])?;
+    let mut base = EngineProcess::start(&settings.engines[1])?;
+    let mut results = Pentanomial::default();
+    loop {
+        let opening = &settings.openings[results.pairs() as usize % settings.openings.len()];
+        let mut points = 0;
+        for tested_side in [false, true] {
+            ensure_running(&mut tested)?;
+            ensure_running(&mut base)?;
+            let engines = if tested_side {
+                [&mut base, &mut tested]
+            } else {
+                [&mut tested, &mut base]
+            };
+            let record = play_game(
+                engines,
+                opening,
+                settings.time_control,
+                settings.adjudication,
+                settings.show_clock,
+            )?;
+            write_record(&settings.output, &record)?;
+            points += record.result.points(tested_side);
+        }
+        results.counts[points as usize] += 1;
+
+        let llr = results.llr(elo0, elo1);
+        let (elo, elo_low, elo_high) = results.elo();
+        let counts: Vec<_> = results.counts.iter().map(u64::to_string).collect();
+        println!(
+            "sprt pairs {} pentanomial {} elo {:+.1} [{:+.1}, {:+.1}] llr {:.2} [{:.2}, {:.2}]",
+            results.pairs(),
+            counts.join(" "),
+            elo,
+            elo_low,
+            elo_high,
+            llr,
+            lower,
+            upper
+        );
+        if llr >= upper {
+            println!("sprt accept elo1");
+            return Ok(Decision::Elo1);
+        }
+        if llr <= lower {
+            println!("sprt accept elo0");
+            return Ok(Decision::Elo0);
+        }
+        if max_pairs.is_some_and(|max_pairs| results.pairs() >= max_pairs) {
+            println!("sprt inconclusive");
+            return Ok(Decision::Inconclusive);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Compares two numbers to within a relative tolerance.
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn elo_and_score_convert_both_ways() {
+        assert_close(expected_score(0.0), 0.5);
+        assert_close(elo_from_score(0.75), 190.848501887865);
+        for elo in [-300.0, -5.0, 0.0, 5.0, 300.0] {
+            assert_close(elo_from_score(expected_score(elo)), elo);
+        }
+    }
+
+    #[test]
+    fn llr_and_elo_of_known_counts() {
+        let results = Pentanomial {
+            counts: [2, 10, 20, 15, 3],
+        };
+        assert_close(results.llr(0.0, 5.0), 0.205309640542331);
+        let (elo, low, high) = results.elo();
+        assert_close(elo, 24.36033165250981);
+        assert_close(low, -20.885583505717015);
+        assert_close(high, 70.45142775078713);
+    }
+
+    #[test]
+    fn llr_waits_for_two_pairs() {
+        let results = Pentanomial {
+            counts: [0, 0, 0, 1, 0],
+        };
+        assert_eq!(results.llr(0.0, 5.0), 0.0);
+    }
+
+    // Identical engines that draw every pair must still accept `elo0` instead of testing forever.
+    #[test]
+    fn identical_pairs_reach_a_bound() {
+        let results = Pentanomial {
+            counts: [0, 0, 30, 0, 0],
+        };
+        let llr = results.llr(DEFAULT_ELO0, DEFAULT_ELO1);
+        assert_close(llr, -37.27879441922374);
+        assert!(llr < (DEFAULT_BETA / (1.0 - DEFAULT_ALPHA)).ln());
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(feature = "stats")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// The events counted by the search when the `stats` feature is enabled.
+// Without the feature, counting is an empty function that compiles away entirely.
+#[derive(Clone, Copy)]
+pub enum Counter {
+    // Nodes at the full search depth, which are statically evaluated.
+    LeafNode,
+    // Nodes where the game is over, which have no moves to search.
+    TerminalNode,
+    // Nodes whose moves were searched, split by whether one of them caused a beta cutoff.
+    CutNode,
+    AllNode,
+    // Outcomes of probing the transposition table.
+    TableHit,
+    TableMiss,
+    TableCutoff,
+    // Nodes searched deeper or shallower than their nominal depth.
+    Extension,
+    Reduction,
+}
+
+#[cfg(feature = "stats")]
+const COUNTERS: usize = 9;
+
+// Cutoffs are recorded by the index of the move that caused them,
+// with all indices from `CUTOFF_BUCKETS - 1` onwards sharing the last bucket.
+pub const CUTOFF_BUCKETS: usize = 8;
+
+#[cfg(feature = "stats")]
+static COUNTS: [AtomicU64; COUNTERS] = [const { AtomicU64::new(0) }; COUNTERS];
+
+#[cfg(feature = "stats")]
+static CUTOFFS: [AtomicU64; CUTOFF_BUCKETS] = [const { AtomicU64::new(0) }; CUTOFF_BUCKETS];
+
+// Whether the counters are compiled in, so that commands can say when they are not.
+pub const ENABLED: bool = cfg!(feature = "stats");
+
+// Records a single occurrence of the given event.
+#[inline(always)]
+pub fn count(_counter: Counter) {
+    #[cfg(feature = "stats")]
+    COUNTS[_counter as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+// Records a beta cutoff caused by the move at the given index in move generation order.
+#[inline(always)]
+pub fn count_cutoff(_index: usize) {
+    #[cfg(feature = "stats")]
+    CUTOFFS[_index.min(CUTOFF_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+}
+
+// The total of a counter since the counters were last reset, or 0 without the `stats` feature.
+pub fn total(_counter: Counter) -> u64 {
+    #[cfg(feature = "stats")]
+    return COUNTS[_counter as usize].load(Ordering::Relaxed);
+    #[cfg(not(feature = "stats"))]
+    0
+}
+
+// The number of cutoffs caused by the move at each index, with the last bucket counting all later moves.
+pub fn cutoff_histogram() -> [u64; CUTOFF_BUCKETS] {
+    #[cfg(feature = "stats")]
+    return std::array::from_fn(|i| CUTOFFS[i].load(Ordering::Relaxed));
+    #[cfg(not(feature = "stats"))]
+    [0; CUTOFF_BUCKETS]
+}
+
+// Sets every counter back to 0.
+pub fn reset() {
+    #[cfg(feature = "stats")]
+    for count in COUNTS.iter().chain(CUTOFFS.iter()) {
+        count.store(0, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::engine::*;
+
+/**
+ * The board has the 8 symmetries of a square: the identity, three rotations and four reflections.
+ * Since the zones are arranged in the same way as the cells within each zone,
+ * applying a symmetry to the 9x9 grid of cells also applies it to the 3x3 grid of zones,
+ * so every transformed board is a valid board with the same evaluation.
+ */
+pub const SYMMETRIES: usize = 8;
+
+// Applies a symmetry to a row and column of an `n` by `n` grid.
+const fn transform_coordinates(row: u64, col: u64, n: u64, symmetry: usize) -> (u64, u64) {
+    let last = n - 1;
+    match symmetry {
+        0 => (row, col),
+        1 => (col, last - row),
+        2 => (last - row, last - col),
+        3 => (last - col, row),
+        4 => (row, last - col),
+        5 => (col, row),
+        6 => (last - row, col),
+        _ => (last - col, last - row),
+    }
+}
+
+// Returns the symmetry that undoes the given one.
+// Only the two quarter turns differ from their inverses; every other symmetry undoes itself.
+pub fn inverse_symmetry(symmetry: usize) -> usize {
+    match symmetry {
+        1 => 3,
+        3 => 1,
+        symmetry => symmetry,
+    }
+}
+
+// Returns the move at the position that the given move is sent to by a symmetry.
+pub fn transform_move(mv: Move, symmetry: usize) -> Move {
+    let (row, col) = move_to_row_col(mv);
+    let (new_row, new_col) = transform_coordinates(row, col, 9, symmetry);
+    move_from_row_col(new_row, new_col)
+}
+
+// Returns the zone that the given zone (or `ZONE_ANY`) is sent to by a symmetry.
+pub fn transform_zone(zone: u64, symmetry: usize) -> u64 {
+    if zone == ZONE_ANY {
+        return ZONE_ANY;
+    }
+    let (row, col) = transform_coordinates(zone / 3, zone % 3, 3, symmetry);
+    3 * row + col
+}
+
+// Returns the board with every cell, the zone to be played in and the owner of each zone moved by a symmetry.
+pub fn transform_board(board: Board, symmetry: usize) -> Board {
+    let zone = (board.2 >> 54) & 0b1111;
+    let mut new_board = set_zone((0, 0, 0), transform_zone(zone, symmetry));
+    for mv in 0..81 {
+        let (x_cells, o_cells) = zone_cells(board, mv / 9);
+        let occupant = if ((x_cells >> (mv % 9)) & 1) == 1 {
+            Some(false)
+        } else if ((o_cells >> (mv % 9)) & 1) == 1 {
+            Some(true)
+        } else {
+            continue;
+        };
+        new_board = set_cell(new_board, transform_move(mv, symmetry), occupant);
+    }
+    // The owners of the zones are moved with them, since they cannot always be told from the cells.
+    let mut share = new_board.2 & !(((1 << 18) - 1) << 36);
+    for zone in 0..9 {
+        for offset in [36, 45] {
+            if (board.2 >> (offset + zone)) & 1 == 1 {
+                share |= 1 << (offset + transform_zone(zone, symmetry));
+            }
+        }
+    }
+    (new_board.0, new_board.1, share)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::TestRules;
+
+    #[test]
+    fn symmetries_are_undone_by_their_inverses() {
+        for symmetry in 0..SYMMETRIES {
+            let inverse = inverse_symmetry(symmetry);
+            for mv in 0..81 {
+                assert_eq!(transform_move(transform_move(mv, symmetry), inverse), mv);
+            }
+            for zone in (0..9).chain([ZONE_ANY]) {
+                assert_eq!(
+                    transform_zone(transform_zone(zone, symmetry), inverse),
+                    zone
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn symmetrical_positions_have_symmetrical_moves() {
+        let _rules = TestRules::standard();
+        let mut board = (0, 0, ZONE_ANY << 54);
+        let mut side = false;
+        for ply in 0..30 {
+            let moves: Vec<_> = generate_moves(board).collect();
+            let Some(&mv) = moves.get(ply * 7 % moves.len().max(1)) else {
+                break;
+            };
+            for symmetry in 0..SYMMETRIES {
+                let transformed = transform_board(board, symmetry);
+                assert_eq!(
+                    transform_board(transformed, inverse_symmetry(symmetry)),
+                    board
+                );
+                let mut expected: Vec<_> = moves
+                    .iter()
+                    .map(|&mv| transform_move(mv, symmetry))
+                    .collect();
+                let mut generated: Vec<_> = generate_moves(transformed).collect();
+                expected.sort();
+                generated.sort();
+                assert_eq!(generated, expected);
+                assert_eq!(
+                    play_move(transformed, transform_move(mv, symmetry), side),
+                    transform_board(play_move(board, mv, side), symmetry)
+                );
+            }
+            board = play_move(board, mv, side);
+            side = !side;
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::fs::read_to_string;
+use std::time::Instant;
+
+use crate::engine::*;
+use crate::interrupt::interrupted;
+use crate::{board_from_string, eval_string, move_from_string, move_string, Notation};
+
+/**
+ * A single entry of a test suite file.
+ * Each entry holds a position along with what is expected of the search from it:
+ * a set of acceptable best moves, an expected result, or both.
+ */
+struct TestPosition {
+    id: String,
+    board: Board,
+    side: bool,
+    best_moves: Vec<Move>,
+    result: Option<String>,
+}
+
+// Parses one line of a test suite file, in a format inspired by EPD.
+// The position string comes first, followed by operations separated by `;`.
+// The supported operations are `bm` (best moves), `re` (expected result) and `id` (name).
+// For example: `2x6/9/9/9/9/9/9/9/9 ne bm ne/c ne/se; re W3; id "example"`.
+fn parse_line(line: &str, line_number: usize) -> Option<TestPosition> {
+    let fields: Vec<_> = line.split_whitespace().collect();
+    if fields.len() < 2 {
+        return None;
+    }
+
+    // The position string has an optional third field for the side to move.
+    let position_length = if matches!(fields.get(2), Some(&"x") | Some(&"o")) {
+        3
+    } else {
+        2
+    };
+    let (board, side) = board_from_string(&fields[..position_length].join(" "))?;
+
+    let mut test_position = TestPosition {
+        id: line_number.to_string(),
+        board,
+        side,
+        best_moves: Vec::new(),
+        result: None,
+    };
+
+    for operation in fields[position_length..].join(" ").split(';') {
+        let mut operands = operation.split_whitespace();
+        match operands.next() {
+            Some("bm") => {
+                for mv in operands {
+                    test_position.best_moves.push(move_from_string(mv)?);
+                }
+            }
+            Some("re") => test_position.result = Some(operands.next()?.to_string()),
+            Some("id") => {
+                test_position.id = operands
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .trim_matches('"')
+                    .to_string()
+            }
+            None => {}
+            Some(_) => return None,
+        }
+    }
+
+    // A position without any expectation cannot be marked as solved or failed.
+    if test_position.best_moves.is_empty() && test_position.result.is_none() {
+        None
+    } else {
+        Some(test_position)
+    }
+}
+
+/**
+ * Runs every position of the test suite in the file at `path` to the given depth,
+ * reporting the move chosen for each position, written in the given notation, as each one completes,
+ * followed by the number of positions solved and time statistics.
+ * Blank lines and lines starting with `#` are ignored.
+ */
+pub fn run_testsuite(path: &str, depth: usize, notation: Notation) {
+    let contents = match read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => {
+            println!("testsuite invalid file");
+            return;
+        }
+    };
+
+    // Parse the whole file before searching, so a malformed file is reported immediately.
+    let mut test_positions = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(test_position) = parse_line(line, i + 1) {
+            test_positions.push(test_position);
+        } else {
+            println!("testsuite invalid line {}", i + 1);
+            return;
+        }
+    }
+
+    let mut solved = 0;
+    let mut total_time = 0;
+    let mut max_time = 0;
+
+    for test_position in test_positions.iter() {
+        let start = Instant::now();
+        let (eval, line) = alpha_beta(
+            test_position.board,
+            test_position.side,
+            depth,
+            OUTCOME_LOSS,
+            OUTCOME_WIN,
+            depth,
+            &mut SearchControl::new(None),
+        );
+        // The remaining positions are abandoned along with the interrupted search.
+        if interrupted() {
+            return;
+        }
+        let duration = start.elapsed().as_millis();
+        total_time += duration;
+        max_time = max_time.max(duration);
+
+        // An expected result given only as its leading character (`W`, `L` or `D`)
+        // accepts that outcome in any number of moves.
+        let eval_text = eval_string(eval, depth);
+        let move_ok =
+            test_position.best_moves.is_empty() || test_position.best_moves.contains(&line[0]);
+        let result_ok = match &test_position.result {
+            Some(result) if result.len() == 1 => eval_text.starts_with(result.as_str()),
+            Some(result) => eval_text == *result,
+            None => true,
+        };
+        let solved_position = move_ok && result_ok;
+        if solved_position {
+            solved += 1;
+        }
+
+        println!(
+            "testsuite id {} {} move {} eval {} time {}",
+            test_position.id,
+            if solved_position { "solved" } else { "failed" },
+            if line[0] == NULL_MOVE {
+                "none".to_string()
+            } else {
+                move_string(line[0], notation)
+            },
+            eval_text,
+            duration
+        );
+    }
+
+    println!(
+        "testsuite solved {} total {} time {} avg {} max {}",
+        solved,
+        test_positions.len(),
+        total_time,
+        if test_positions.is_empty() {
+            0
+        } else {
+            total_time / test_positions.len() as u128
+        },
+        max_time
+    );
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use crate::clock::Instant;
+
+// Time reserved from every budget to account for the delay
+// between the engine choosing a move and the move being received.
+const MOVE_OVERHEAD_MS: u64 = 10;
+
+/**
+ * Decides how long a search may run, given the time budget for a single move.
+ * The search is stopped outright at the hard limit, while a new iteration of
+ * iterative deepening is only started before the soft limit, since each iteration
+ * typically takes several times longer than the one before it.
+ */
+pub struct TimeManager {
+    start: Instant,
+    soft_limit: Duration,
+    hard_limit: Duration,
+}
+
+impl TimeManager {
+    pub fn new(budget_ms: u64) -> Self {
+        // Even with a budget smaller than the overhead, at least one millisecond is given.
+        let hard_limit = Duration::from_millis(budget_ms.saturating_sub(MOVE_OVERHEAD_MS).max(1));
+        Self {
+            start: Instant::now(),
+            soft_limit: hard_limit / 2,
+            hard_limit,
+        }
+    }
+
+    // The instant at which any running search must be stopped.
+    pub fn deadline(&self) -> Instant {
+        self.start + self.hard_limit
+    }
+
+    // Whether enough time remains to begin searching one ply deeper.
+    pub fn can_start_iteration(&self) -> bool {
+        self.start.elapsed() < self.soft_limit
+    }
+}
@@ -0,0 +1,133 @@
+use crate::arena::*;
+use crate::sprt::elo_from_score;
+
+/**
+ * Plays a round-robin tournament between any number of engines, as
+ * `tournament --engine <path> ... --engine <path> ... [--engine <path> ...] [--rounds <count>]`.
+ * In each round every engine plays every other engine twice from the same opening, once as each side,
+ * with each round moving on to the next opening.
+ * The result of each game is written as it finishes, followed by a crosstable ordered by score,
+ * with the Elo each score corresponds to against the rest of the field.
+ * The other arguments are those of `match`.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let settings = parse_settings(args)?;
+    let count = settings.engines.len();
+    if count < 2 {
+        return Err("a tournament needs at least two engines".to_string());
+    }
+    let mut rounds = 1;
+    let mut rest = settings.rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--rounds" => {
+                rounds = rest
+                    .next()
+                    .and_then(|rounds| rounds.parse().ok())
+                    .filter(|&rounds| rounds > 0)
+                    .ok_or("--rounds needs a positive count")?;
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    let mut engines = settings
+        .engines
+        .iter()
+        .map(EngineProcess::start)
+        .collect::<Result<Vec<_>, _>>()?;
+    // The points each engine scored against each other engine, out of 2 per game.
+    let mut points = vec![vec![0; count]; count];
+    let mut games = vec![vec![0; count]; count];
+    let mut game = 0;
+    for round in 0..rounds {
+        let opening = &settings.openings[round % settings.openings.len()];
+        for first in 0..count {
+            for second in first + 1..count {
+                for (x, o) in [(first, second), (second, first)] {
+                    ensure_running(&mut engines[x])?;
+                    ensure_running(&mut engines[o])?;
+                    // Both engines are borrowed from the list at once by splitting it between them.
+                    let (low, high) = engines.split_at_mut(x.max(o));
+                    let (lower, higher) = (&mut low[x.min(o)], &mut high[0]);
+                    let players = if x < o {
+                        [lower, higher]
+                    } else {
+                        [higher, lower]
+                    };
+                    let record = play_game(
+                        players,
+                        opening,
+                        settings.time_control,
+                        settings.adjudication,
+                        settings.show_clock,
+                    )?;
+                    write_record(&settings.output, &record)?;
+                    points[x][o] += record.result.points(false);
+                    points[o][x] += record.result.points(true);
+                    games[x][o] += 1;
+                    games[o][x] += 1;
+                    game += 1;
+                    println!(
+                        "game {} {} vs {} {} {}",
+                        game,
+                        record.x_name,
+                        record.o_name,
+                        record.result.score_string(),
+                        record.termination
+                    );
+                }
+            }
+        }
+    }
+
+    let totals: Vec<u32> = points.iter().map(|row| row.iter().sum()).collect();
+    let mut order: Vec<usize> = (0..count).collect();
+    order.sort_by_key(|&engine| std::cmp::Reverse(totals[engine]));
+    let width = settings
+        .engines
+        .iter()
+        .map(|engine| engine.name.len())
+        .chain(["name".len()])
+        .max()
+        .unwrap_or(0);
+    // Each engine plays the same number of games, so every score is out of the same total.
+    let played: u32 = games[0].iter().sum();
+    let mut header = format!(
+        "{:>4} {:<width$} {:>9} {:>6}",
+        "rank", "name", "score", "elo"
+    );
+    for rank in 1..=count {
+        header += &format!(" {:>5}", rank);
+    }
+    println!("{}", header);
+    for (rank, &engine) in order.iter().enumerate() {
+        let score = totals[engine] as f64 / (2 * played) as f64;
+        let mut line = format!(
+            "{:>4} {:<width$} {:>9} {:>+6.0}",
+            rank + 1,
+            settings.engines[engine].name,
+            format!("{}/{}", half_points(totals[engine]), played),
+            // Adding zero turns the negative zero of an even score into a plain zero.
+            elo_from_score(score.clamp(1e-6, 1.0 - 1e-6)) + 0.0
+        );
+        for &opponent in &order {
+            if opponent == engine {
+                line += &format!(" {:>5}", "-");
+            } else {
+                line += &format!(" {:>5}", half_points(points[engine][opponent]));
+            }
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+// Writes a score kept in half points as a number of points, such as `3.5`.
+fn half_points(points: u32) -> String {
+    if points.is_multiple_of(2) {
+        (points / 2).to_string()
+    } else {
+        format!("{}.5", points / 2)
+    }
+}
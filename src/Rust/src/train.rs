@@ -0,0 +1,172 @@
+use std::fs::{copy, create_dir_all, write};
+use std::process::Command;
+
+use crate::sprt::Decision;
+use crate::{db, selfplay, sprt};
+use ut3b2l::random::Random;
+
+// The default number of iterations of a run, and of the most recent games kept in the replay buffer.
+const DEFAULT_ITERATIONS: usize = 1;
+const DEFAULT_WINDOW: usize = 1000;
+
+// Reads a positive number given after a flag.
+fn positive(value: &str, flag: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&number| number > 0)
+        .ok_or_else(|| format!("{} needs a positive number", flag))
+}
+
+/**
+ * Writes a sample of the replay buffer to `path`, as `sample` games drawn without replacement
+ * from the most recent `window` games in `games`, or every game of the window if there are no more than that.
+ * The games are written in the order they were played, so the same seed always exports the same samples.
+ */
+fn sample_replay_buffer(
+    games: &str,
+    path: &str,
+    window: usize,
+    sample: usize,
+    random: &mut Random,
+) -> Result<usize, String> {
+    let records = db::read_records(games)?;
+    let recent = &records[records.len().saturating_sub(window)..];
+    let mut indices: Vec<_> = (0..recent.len()).collect();
+    // A partial Fisher-Yates shuffle picks the sample, which is then put back in order.
+    let count = sample.min(recent.len());
+    for i in 0..count {
+        let j = i + random.below((indices.len() - i) as u64) as usize;
+        indices.swap(i, j);
+    }
+    let mut chosen = indices[..count].to_vec();
+    chosen.sort_unstable();
+    let text: String = chosen.iter().map(|&i| recent[i].to_text()).collect();
+    write(path, text).map_err(|error| format!("cannot write {}: {}", path, error))?;
+    Ok(count)
+}
+
+// Runs the program that updates the candidate from the training samples, as `<program> <samples> <candidate>`.
+fn update(program: &str, samples: &str, candidate: &str) -> Result<(), String> {
+    let status = Command::new(program)
+        .arg(samples)
+        .arg(candidate)
+        .status()
+        .map_err(|error| format!("cannot run {}: {}", program, error))?;
+    if !status.success() {
+        return Err(format!("{} failed with {}", program, status));
+    }
+    Ok(())
+}
+
+/**
+ * Runs iterations of a training loop, as
+ * `train --dir <dir> --candidate <path> --best <path> [--iterations <n>] [--games <games>] [--nodes <nodes>]
+ * [--random-plies <plies>] [--window <games>] [--sample <games>] [--seed <seed>] [--update <program>]`,
+ * followed by any arguments of `sprt`.
+ * Each iteration plays games with `selfplay` into `<dir>/games.txt`, which keeps the games of every iteration,
+ * draws a sample of the most recent `window` of them as the replay buffer into `<dir>/replay.txt`,
+ * exports the sample with `db export` to `<dir>/samples.bin` in the binary format,
+ * runs the update program if given, and then tests the candidate against the best engine with `sprt`,
+ * copying the candidate over the best engine if it is accepted as stronger.
+ * The engine has no trainable weights of its own, so updating the candidate is left to the program given with
+ * `--update`, which is run as `<program> <samples> <candidate>`, and this only chains the steps around it.
+ */
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (mut dir, mut candidate, mut best, mut program) = (None, None, None, None);
+    let mut iterations = DEFAULT_ITERATIONS;
+    let mut window = DEFAULT_WINDOW;
+    // The number of games drawn from the window, which is all of them unless given.
+    let mut sample = None;
+    let mut seed = Random::clock_seed();
+    // The arguments passed on to `selfplay` and to `sprt`.
+    let mut selfplay_args = Vec::new();
+    let mut sprt_args = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--dir" => dir = Some(value()?),
+            "--candidate" => candidate = Some(value()?),
+            "--best" => best = Some(value()?),
+            "--update" => program = Some(value()?),
+            "--iterations" => iterations = positive(&value()?, arg)?,
+            "--window" => window = positive(&value()?, arg)?,
+            "--sample" => sample = Some(positive(&value()?, arg)?),
+            "--seed" => {
+                seed = value()?
+                    .parse()
+                    .map_err(|_| format!("{} needs a number", arg))?
+            }
+            "--games" | "--nodes" | "--random-plies" => {
+                selfplay_args.extend([arg.clone(), value()?]);
+            }
+            _ => sprt_args.push(arg.clone()),
+        }
+    }
+    let (Some(dir), Some(candidate), Some(best)) = (dir, candidate, best) else {
+        return Err("train needs --dir, --candidate and --best".to_string());
+    };
+    create_dir_all(&dir).map_err(|error| format!("cannot create {}: {}", dir, error))?;
+    let games = format!("{}/games.txt", dir);
+    let replay = format!("{}/replay.txt", dir);
+    let samples = format!("{}/samples.bin", dir);
+    println!("train seed {}", seed);
+
+    // Each iteration plays different games, while the whole run can still be repeated from its seed.
+    let mut random = Random::new(seed);
+    let mut promotions = 0;
+    for iteration in 1..=iterations {
+        println!("train iteration {}", iteration);
+        let mut args = vec![
+            "--out".to_string(),
+            games.clone(),
+            "--seed".to_string(),
+            random.next_u64().to_string(),
+        ];
+        args.extend(selfplay_args.iter().cloned());
+        selfplay::run(&args)?;
+        let count = sample_replay_buffer(
+            &games,
+            &replay,
+            window,
+            sample.unwrap_or(window),
+            &mut random,
+        )?;
+        println!("train sample {}", count);
+        db::run(&[
+            "export".to_string(),
+            replay.clone(),
+            "binary".to_string(),
+            samples.clone(),
+        ])?;
+        if let Some(program) = &program {
+            update(program, &samples, &candidate)?;
+        }
+        let mut args = vec![
+            "--engine".to_string(),
+            candidate.clone(),
+            "--name".to_string(),
+            "candidate".to_string(),
+            "--engine".to_string(),
+            best.clone(),
+            "--name".to_string(),
+            "best".to_string(),
+            "--out".to_string(),
+            format!("{}/sprt-{}.txt", dir, iteration),
+        ];
+        args.extend(sprt_args.iter().cloned());
+        if sprt::test(&args)? == Decision::Elo1 {
+            copy(&candidate, &best)
+                .map_err(|error| format!("cannot copy {} to {}: {}", candidate, best, error))?;
+            promotions += 1;
+            println!("train promote {}", iteration);
+        }
+    }
+    println!("train iterations {} promotions {}", iterations, promotions);
+    Ok(())
+}
@@ -0,0 +1,295 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::engine::*;
+
+// The size of the table when none is given with `setoption Hash`, in megabytes.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+// The largest table that can be requested, in megabytes.
+pub const MAX_HASH_MB: usize = 4096;
+
+// The number of entries in a bucket, which together fill a 64-byte cache line.
+const BUCKET_ENTRIES: usize = 4;
+
+// The number of buckets looked at to estimate how full the table is.
+const OCCUPANCY_SAMPLE: usize = 1024;
+
+// How much less an entry is worth for each search it is older than the current one,
+// in plies of depth, when entries are replaced by age.
+const AGE_PENALTY: i32 = 4;
+
+// The ways an entry may be chosen to be overwritten when a bucket holds no entry for the position.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Replacement {
+    // Each position has a single entry in its bucket, which is always overwritten.
+    Always,
+    // The shallowest entry in the bucket is overwritten.
+    Depth,
+    // The shallowest entry is overwritten, with entries from earlier searches counted as shallower.
+    Aging,
+}
+
+// Whether a stored score is exact, or only a bound on the true score
+// because the search of the position failed high or low.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/**
+ * A single entry, unpacked from its 16 bytes.
+ * The data word holds the score (bits 0-31), the best move (32-38), the depth (39-44),
+ * the bound (45-46, where 0 marks an empty entry) and the generation of the search that stored it (47-54).
+ * The other word holds the key of the position XORed with the data, so that an entry torn
+ * by two threads writing it at once does not match either position.
+ */
+#[derive(Clone, Copy)]
+pub struct Entry {
+    pub score: i32,
+    pub mv: Move,
+    pub depth: usize,
+    pub bound: Bound,
+    generation: u8,
+}
+
+impl Entry {
+    fn pack(self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        (self.score as u32 as u64)
+            | (self.mv << 32)
+            | ((self.depth as u64) << 39)
+            | (bound << 45)
+            | ((self.generation as u64) << 47)
+    }
+
+    fn unpack(data: u64) -> Option<Self> {
+        let bound = match (data >> 45) & 0b11 {
+            0 => return None,
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Some(Self {
+            score: data as u32 as i32,
+            mv: (data >> 32) & 0b1111111,
+            depth: ((data >> 39) & 0b111111) as usize,
+            bound,
+            generation: (data >> 47) as u8,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+// Buckets are aligned to cache lines, so that probing a position reads a single line from memory.
+#[repr(align(64))]
+#[derive(Default)]
+struct Bucket {
+    slots: [Slot; BUCKET_ENTRIES],
+}
+
+/**
+ * The transposition table, storing the results of searched positions by their Zobrist hash,
+ * so that a position reached again by another order of moves, or in a later search, is not searched again.
+ * Entries are read and written with atomic operations, so that the table can be shared between threads.
+ * Scores of forced wins and losses are stored relative to the position rather than the root,
+ * so that they stay correct wherever in the search the position is reached.
+ */
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    replacement: Replacement,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    // Creates an empty table taking up the given number of megabytes.
+    pub fn new(megabytes: usize, replacement: Replacement) -> Self {
+        let count = (megabytes << 20) / size_of::<Bucket>();
+        Self {
+            buckets: (0..count.max(1)).map(|_| Bucket::default()).collect(),
+            replacement,
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    // Empties every entry.
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flat_map(|bucket| bucket.slots.iter()) {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    // Marks the start of a new search, making the entries of earlier searches older.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // The share of entries in use, estimated from the first buckets of the table.
+    pub fn occupancy(&self) -> f64 {
+        let sample = &self.buckets[..self.buckets.len().min(OCCUPANCY_SAMPLE)];
+        let used = sample
+            .iter()
+            .flat_map(|bucket| bucket.slots.iter())
+            .filter(|slot| Entry::unpack(slot.data.load(Ordering::Relaxed)).is_some())
+            .count();
+        used as f64 / (sample.len() * BUCKET_ENTRIES) as f64
+    }
+
+    // The bucket of a position, found by scaling its key to the number of buckets.
+    fn bucket(&self, key: u64) -> &Bucket {
+        &self.buckets[((key as u128 * self.buckets.len() as u128) >> 64) as usize]
+    }
+
+    /**
+     * Starts loading the bucket of a position into the cache, so that a probe soon after does not wait on memory.
+     * This does nothing unless the `prefetch` feature is enabled and the target is x86-64.
+     */
+    #[inline(always)]
+    pub fn prefetch(&self, _key: u64) {
+        #[cfg(all(feature = "prefetch", target_arch = "x86_64"))]
+        unsafe {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            _mm_prefetch::<_MM_HINT_T0>(self.bucket(_key) as *const Bucket as *const i8);
+        }
+    }
+
+    // Returns the entry stored for the position, with its score made relative to the given ply.
+    pub fn probe(&self, key: u64, ply: usize) -> Option<Entry> {
+        self.bucket(key).slots.iter().find_map(|slot| {
+            let data = slot.data.load(Ordering::Relaxed);
+            if slot.check.load(Ordering::Relaxed) ^ data != key {
+                return None;
+            }
+            let mut entry = Entry::unpack(data)?;
+            entry.score = match entry.score {
+                score if score >= OUTCOME_WIN - MAX_PLY as i32 => score - ply as i32,
+                score if score <= OUTCOME_LOSS + MAX_PLY as i32 => score + ply as i32,
+                score => score,
+            };
+            Some(entry)
+        })
+    }
+
+    // Stores the result of searching a position at the given ply.
+    pub fn store(&self, key: u64, ply: usize, score: i32, mv: Move, depth: usize, bound: Bound) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let score = match score {
+            score if score >= OUTCOME_WIN - MAX_PLY as i32 => score + ply as i32,
+            score if score <= OUTCOME_LOSS + MAX_PLY as i32 => score - ply as i32,
+            score => score,
+        };
+        let slots = &self.bucket(key).slots;
+
+        // An existing entry for the position is always overwritten first.
+        let existing = slots.iter().position(|slot| {
+            slot.check.load(Ordering::Relaxed) ^ slot.data.load(Ordering::Relaxed) == key
+        });
+        let index = existing.unwrap_or_else(|| match self.replacement {
+            // The lowest bits of the key, which barely affect the choice of bucket, choose the slot.
+            Replacement::Always => (key as usize) % BUCKET_ENTRIES,
+            Replacement::Depth | Replacement::Aging => (0..BUCKET_ENTRIES)
+                .min_by_key(
+                    |&i| match Entry::unpack(slots[i].data.load(Ordering::Relaxed)) {
+                        None => i32::MIN,
+                        Some(entry) if self.replacement == Replacement::Aging => {
+                            let age = generation.wrapping_sub(entry.generation) as i32;
+                            entry.depth as i32 - AGE_PENALTY * age
+                        }
+                        Some(entry) => entry.depth as i32,
+                    },
+                )
+                .unwrap(),
+        });
+
+        let data = Entry {
+            score,
+            mv,
+            depth,
+            bound,
+            generation,
+        }
+        .pack();
+        slots[index].check.store(key ^ data, Ordering::Relaxed);
+        slots[index].data.store(data, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A table of a single bucket, so that every position competes for its entries.
+    fn single_bucket(replacement: Replacement) -> TranspositionTable {
+        let table = TranspositionTable::new(0, replacement);
+        assert_eq!(table.buckets.len(), 1);
+        table
+    }
+
+    #[test]
+    fn entries_are_found_again() {
+        let table = TranspositionTable::new(1, Replacement::Aging);
+        table.store(0x1234, 0, -57, 80, 12, Bound::Lower);
+        let entry = table.probe(0x1234, 0).unwrap();
+        assert_eq!((entry.score, entry.mv, entry.depth), (-57, 80, 12));
+        assert!(entry.bound == Bound::Lower);
+        assert!(table.probe(0x1235, 0).is_none());
+        table.store(0x1234, 0, 31, 4, 3, Bound::Exact);
+        let entry = table.probe(0x1234, 0).unwrap();
+        assert_eq!((entry.score, entry.mv, entry.depth), (31, 4, 3));
+        assert!(entry.bound == Bound::Exact);
+        table.clear();
+        assert!(table.probe(0x1234, 0).is_none());
+        assert_eq!(table.occupancy(), 0.0);
+    }
+
+    #[test]
+    fn forced_results_are_stored_relative_to_the_position() {
+        let table = TranspositionTable::new(1, Replacement::Aging);
+        table.store(1, 3, OUTCOME_WIN - 5, 0, 4, Bound::Exact);
+        assert_eq!(table.probe(1, 1).unwrap().score, OUTCOME_WIN - 3);
+        table.store(2, 3, OUTCOME_LOSS + 5, 0, 4, Bound::Exact);
+        assert_eq!(table.probe(2, 1).unwrap().score, OUTCOME_LOSS + 3);
+        table.store(3, 3, 250, 0, 4, Bound::Exact);
+        assert_eq!(table.probe(3, 1).unwrap().score, 250);
+    }
+
+    #[test]
+    fn the_shallowest_entry_is_replaced() {
+        let table = single_bucket(Replacement::Depth);
+        for (key, depth) in [(1, 8), (2, 5), (3, 6), (4, 7)] {
+            table.store(key, 0, 0, 0, depth, Bound::Exact);
+        }
+        table.store(5, 0, 0, 0, 1, Bound::Exact);
+        assert!(table.probe(2, 0).is_none());
+        assert!([1, 3, 4, 5]
+            .iter()
+            .all(|&key| table.probe(key, 0).is_some()));
+    }
+
+    #[test]
+    fn older_entries_are_replaced_first_when_aging() {
+        let table = single_bucket(Replacement::Aging);
+        table.store(1, 0, 0, 0, 8, Bound::Exact);
+        table.new_search();
+        for (key, depth) in [(2, 5), (3, 6), (4, 7)] {
+            table.store(key, 0, 0, 0, depth, Bound::Exact);
+        }
+        table.store(5, 0, 0, 0, 1, Bound::Exact);
+        assert!(table.probe(1, 0).is_none());
+        assert!([2, 3, 4, 5]
+            .iter()
+            .all(|&key| table.probe(key, 0).is_some()));
+    }
+}
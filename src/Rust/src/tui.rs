@@ -0,0 +1,407 @@
+use std::io::{stdin, stdout, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::display::board_lines_color;
+use crate::engine::*;
+use crate::interrupt::{install_handler, interrupted};
+use crate::timeman::TimeManager;
+use crate::tt::{Replacement, TranspositionTable, DEFAULT_HASH_MB};
+use ut3b2l::notation::*;
+
+// How long the engine thinks for with `go` and `analyse`, unless a time is given.
+const DEFAULT_MOVETIME_MS: u64 = 1000;
+
+// How often the screen checks for an interrupt while waiting for input.
+const INTERRUPT_POLL: Duration = Duration::from_millis(50);
+
+// The column the panel to the right of the board starts at.
+const PANEL_COLUMN: usize = 36;
+
+// The number of rows of moves shown in the history pane.
+const HISTORY_ROWS: usize = 10;
+
+// The words that can be entered besides moves, which are completed along with them.
+const COMMANDS: [&str; 6] = ["analyse", "go", "new", "quit", "stop", "undo"];
+
+// Everything the interface waits on, from the input thread or the search thread.
+enum Event {
+    Key(u8),
+    InputClosed,
+    Iteration(usize, i32, Box<[Move; MAX_PLY]>),
+    SearchDone(Box<[Move; MAX_PLY]>),
+}
+
+// The latest result of a running or finished search.
+struct Analysis {
+    depth: usize,
+    eval: i32,
+    line: [Move; MAX_PLY],
+    time: u128,
+}
+
+// A search running on its own thread, which plays its best move once finished if `play` is set.
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    start: Instant,
+    play: bool,
+}
+
+struct Tui {
+    // Each entry holds a position, the move that led to it, the side to move in it,
+    // and the time in milliseconds taken over the move.
+    history: Vec<(Board, Move, bool, u128)>,
+    // When the turn being played began, from which the time taken over the next move is counted.
+    turn_start: Instant,
+    input: String,
+    message: String,
+    analysis: Option<Analysis>,
+    search: Option<RunningSearch>,
+    table: Arc<TranspositionTable>,
+}
+
+// Puts the terminal into a mode where keys are read as soon as they are pressed, without being echoed,
+// returning the previous settings to be restored, or `None` if standard input is not a terminal.
+fn enter_raw_mode() -> Option<String> {
+    let saved = Command::new("stty")
+        .arg("-g")
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Command::new("stty")
+        .args(["-icanon", "-echo", "min", "1"])
+        .stdin(Stdio::inherit())
+        .status()
+        .ok()?;
+    Some(String::from_utf8_lossy(&saved.stdout).trim().to_string())
+}
+
+fn leave_raw_mode(saved: &str) {
+    Command::new("stty")
+        .arg(saved)
+        .stdin(Stdio::inherit())
+        .status()
+        .ok();
+}
+
+// The longest string that every candidate starts with.
+fn common_prefix(candidates: &[String]) -> String {
+    let first = &candidates[0];
+    let length = (0..first.len())
+        .take_while(|&i| {
+            candidates
+                .iter()
+                .all(|candidate| candidate.as_bytes().get(i) == first.as_bytes().get(i))
+        })
+        .count();
+    first[..length].to_string()
+}
+
+impl Tui {
+    fn current(&self) -> (Board, bool) {
+        let (board, _, side, _) = *self.history.last().unwrap();
+        (board, side)
+    }
+
+    // Describes the state of the game for the panel.
+    fn status(&self) -> String {
+        let (board, side) = self.current();
+        let finished = generate_moves(board).next().is_none();
+        if let Some(side) = winner(board).or(full_grid_winner(board).filter(|_| finished)) {
+            format!("Player {} has won", player_string(side).to_uppercase())
+        } else if finished {
+            "The game is drawn".to_string()
+        } else if self.search.is_some() {
+            format!(
+                "Player {} to move, thinking",
+                player_string(side).to_uppercase()
+            )
+        } else {
+            format!("Player {} to move", player_string(side).to_uppercase())
+        }
+    }
+
+    // Redraws the whole screen: the board on the left, the panel on the right, and the input line below.
+    fn draw(&self) {
+        let (board, side) = self.current();
+        let last_move = self.history.last().unwrap().1;
+        let board_lines = board_lines_color(board, side, last_move);
+
+        let mut panel = vec![self.status(), String::new()];
+        match &self.analysis {
+            Some(analysis) => {
+                panel.push(format!(
+                    "Eval {}  Depth {}  Time {}ms",
+                    eval_string(analysis.eval, analysis.depth),
+                    analysis.depth,
+                    analysis.time
+                ));
+                panel.push(format!("PV {}", pv_string(&analysis.line, Notation::Zone)));
+            }
+            None => panel.extend(["Eval -".to_string(), "PV -".to_string()]),
+        }
+        // The time each side has taken over its moves, with the time taken over the last move.
+        let mut used = [0; 2];
+        for pair in self.history.windows(2) {
+            used[pair[0].2 as usize] += pair[1].3;
+        }
+        let seconds = |ms: u128| format!("{}.{}s", ms / 1000, ms % 1000 / 100);
+        panel.push(format!(
+            "Time X {}  O {}  Last {}",
+            seconds(used[0]),
+            seconds(used[1]),
+            seconds(self.history.last().unwrap().3)
+        ));
+        panel.push(String::new());
+        panel.push("Moves".to_string());
+        let moves: Vec<_> = self.history[1..]
+            .chunks(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                let moves: Vec<_> = pair
+                    .iter()
+                    .map(|&(_, mv, _, _)| move_string(mv, Notation::Zone))
+                    .collect();
+                format!("{:>3}. {}", i + 1, moves.join(" "))
+            })
+            .collect();
+        panel.extend(
+            moves[moves.len().saturating_sub(HISTORY_ROWS)..]
+                .iter()
+                .cloned(),
+        );
+
+        let mut screen = "\x1b[H\x1b[2J".to_string();
+        for (row, line) in board_lines.iter().enumerate() {
+            screen += &format!("\x1b[{};1H{}", row + 1, line);
+        }
+        for (row, line) in panel.iter().enumerate() {
+            screen += &format!("\x1b[{};{}H{}", row + 2, PANEL_COLUMN, line);
+        }
+        let bottom = board_lines.len().max(panel.len() + 1) + 2;
+        screen += &format!(
+            "\x1b[{};1H{}\x1b[{};1H> {}",
+            bottom,
+            self.message,
+            bottom + 1,
+            self.input
+        );
+        print!("{}", screen);
+        stdout().flush().ok();
+    }
+
+    // Starts a search on a new thread, whose iterations are shown as they complete.
+    fn start_search(&mut self, movetime: u64, play: bool, events: &Sender<Event>) {
+        let (board, side) = self.current();
+        if generate_moves(board).next().is_none() {
+            self.message = "The game is over".to_string();
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let iterations = events.clone();
+        self.table.new_search();
+        let mut control = SearchControl::new(None)
+            .with_stop_request(stop.clone())
+            .with_table(self.table.clone())
+            .with_iteration_callback(Box::new(move |depth, eval, line| {
+                iterations
+                    .send(Event::Iteration(depth, eval, Box::new(*line)))
+                    .ok();
+            }));
+        let done = events.clone();
+        thread::spawn(move || {
+            let time = TimeManager::new(movetime);
+            let (_, _, line) = iterative_deepening(board, side, MAX_PLY, Some(&time), &mut control);
+            done.send(Event::SearchDone(Box::new(line))).ok();
+        });
+        self.analysis = None;
+        self.search = Some(RunningSearch {
+            stop,
+            start: Instant::now(),
+            play,
+        });
+    }
+
+    fn play(&mut self, mv: Move) {
+        let (board, side) = self.current();
+        match check_move(board, mv) {
+            Ok(()) => {
+                let used = self.turn_start.elapsed().as_millis();
+                self.history
+                    .push((play_move(board, mv, side), mv, !side, used));
+                self.turn_start = Instant::now();
+                self.message.clear();
+            }
+            Err(_) => self.message = format!("{} is illegal", move_string(mv, Notation::Zone)),
+        }
+    }
+
+    // Completes the input to the longest prefix shared by the moves and commands it could be,
+    // listing them when there is more than one.
+    fn complete(&mut self) {
+        let (board, _) = self.current();
+        let candidates: Vec<String> = generate_moves(board)
+            .map(|mv| move_string(mv, Notation::Zone))
+            .chain(COMMANDS.iter().map(|command| command.to_string()))
+            .filter(|candidate| candidate.starts_with(&self.input))
+            .collect();
+        match candidates.len() {
+            0 => self.message = "No completions".to_string(),
+            1 => self.input = candidates[0].clone(),
+            _ => {
+                self.input = common_prefix(&candidates);
+                self.message = candidates.join(" ");
+            }
+        }
+    }
+
+    // Handles an entered line, returning `false` once the interface should close.
+    fn execute(&mut self, line: &str, events: &Sender<Event>) -> bool {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let Some(&first) = words.first() else {
+            return true;
+        };
+        if self.search.is_some() && first != "stop" && first != "quit" {
+            self.message = "Searching, enter stop first".to_string();
+            return true;
+        }
+        match first {
+            "quit" | "q" => return false,
+            "stop" => match &self.search {
+                Some(search) => search.stop.store(true, Ordering::Relaxed),
+                None => self.message = "No search is running".to_string(),
+            },
+            "go" | "analyse" => match words
+                .get(1)
+                .map_or(Ok(DEFAULT_MOVETIME_MS), |ms| ms.parse())
+            {
+                Ok(movetime) if movetime > 0 => {
+                    self.message.clear();
+                    self.start_search(movetime, first == "go", events);
+                }
+                _ => self.message = "Invalid time".to_string(),
+            },
+            "undo" => {
+                if self.history.len() > 1 {
+                    self.history.pop();
+                    self.turn_start = Instant::now();
+                    self.message.clear();
+                } else {
+                    self.message = "No moves to undo".to_string();
+                }
+            }
+            "new" => {
+                self.history.truncate(1);
+                self.turn_start = Instant::now();
+                self.analysis = None;
+                self.table.clear();
+                self.message.clear();
+            }
+            _ => match move_from_string(first) {
+                Some(mv) => self.play(mv),
+                None => self.message = format!("Unknown command or move {}", first),
+            },
+        }
+        true
+    }
+}
+
+/**
+ * Runs the interactive terminal interface, for playing and analysing games directly.
+ * The board is drawn with the playable cells highlighted, alongside the engine's evaluation and
+ * principal variation, which are updated live as a search deepens, and the moves of the game.
+ * Moves are entered in either notation, with Tab completing them, as well as the commands
+ * `go [ms]` (the engine plays a move), `analyse [ms]`, `stop`, `undo`, `new` and `quit`.
+ */
+pub fn run() {
+    let Some(saved) = enter_raw_mode() else {
+        eprintln!("the terminal interface needs a terminal");
+        return;
+    };
+    install_handler();
+    print!("\x1b[?1049h");
+
+    let (sender, receiver) = channel();
+    let input_sender = sender.clone();
+    thread::spawn(move || {
+        for byte in stdin().lock().bytes() {
+            let Ok(byte) = byte else { break };
+            if input_sender.send(Event::Key(byte)).is_err() {
+                return;
+            }
+        }
+        input_sender.send(Event::InputClosed).ok();
+    });
+
+    let mut tui = Tui {
+        history: vec![((0, 0, ZONE_ANY << 54), NULL_MOVE, false, 0)],
+        turn_start: Instant::now(),
+        input: String::new(),
+        message: "Enter a move, or Tab to list them".to_string(),
+        analysis: None,
+        search: None,
+        table: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB, Replacement::Aging)),
+    };
+    // Whether the bytes of an escape sequence, such as that of an arrow key, are being skipped.
+    let mut escape = false;
+    tui.draw();
+
+    while !interrupted() {
+        let event = match receiver.recv_timeout(INTERRUPT_POLL) {
+            Ok(event) => event,
+            Err(_) => continue,
+        };
+        match event {
+            Event::InputClosed => break,
+            Event::Key(0x1b) => escape = true,
+            Event::Key(byte) if escape => {
+                escape = byte == b'[' || (!byte.is_ascii_alphabetic() && byte != b'~')
+            }
+            Event::Key(b'\n' | b'\r') => {
+                let line = std::mem::take(&mut tui.input);
+                if !tui.execute(&line, &sender) {
+                    break;
+                }
+            }
+            Event::Key(b'\t') => tui.complete(),
+            Event::Key(0x7f | 0x08) => {
+                tui.input.pop();
+            }
+            Event::Key(byte) if byte.is_ascii_graphic() || byte == b' ' => {
+                tui.input.push(byte as char)
+            }
+            Event::Key(_) => {}
+            Event::Iteration(depth, eval, line) => {
+                let time = tui
+                    .search
+                    .as_ref()
+                    .map_or(0, |search| search.start.elapsed().as_millis());
+                tui.analysis = Some(Analysis {
+                    depth,
+                    eval,
+                    line: *line,
+                    time,
+                });
+            }
+            Event::SearchDone(line) => {
+                let search = tui.search.take().unwrap();
+                if search.play && line[0] != NULL_MOVE {
+                    tui.play(line[0]);
+                }
+            }
+        }
+        tui.draw();
+    }
+
+    if let Some(search) = &tui.search {
+        search.stop.store(true, Ordering::Relaxed);
+    }
+    print!("\x1b[?1049l");
+    stdout().flush().ok();
+    leave_raw_mode(&saved);
+}
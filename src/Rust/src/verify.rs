@@ -0,0 +1,176 @@
+use crate::engine::*;
+use crate::rules::{won_zones, WonZones};
+use crate::zobrist;
+use crate::SearchRecord;
+use crate::ZONE_ARRAY_LOWER;
+
+// The bits of each part of a board that hold nothing: above the 63 cells of `us` and `them`,
+// and above the zone in `share`.
+const UNUSED_CELL_BITS: u64 = 1 << 63;
+const UNUSED_SHARE_BITS: u64 = !0 << 58;
+
+// The number of cells occupied by Player X and by Player O.
+fn cell_counts(board: Board) -> (i32, i32) {
+    (0..9).fold((0, 0), |(x, o), zone| {
+        let (x_cells, o_cells) = zone_cells(board, zone);
+        (
+            x + x_cells.count_ones() as i32,
+            o + o_cells.count_ones() as i32,
+        )
+    })
+}
+
+// Checks the state of a single board that is derived from the rest of it, or must agree with it.
+fn check_board(board: Board) -> Vec<String> {
+    let (us, them, share) = board;
+    let mut mismatches = Vec::new();
+    if (us | them) & UNUSED_CELL_BITS != 0 || share & UNUSED_SHARE_BITS != 0 {
+        mismatches.push("unused".to_string());
+    }
+    // Under the rules that leave won zones open, either player may go on to form a line in a zone,
+    // but only the first to do so has it marked as won.
+    let mut large_grid = false;
+    for zone in 0..9 {
+        let (x_cells, o_cells) = zone_cells(board, zone);
+        if x_cells & o_cells != 0 {
+            mismatches.push(format!("overlap {}", ZONE_ARRAY_LOWER[zone as usize]));
+        }
+        let info = grid_info(x_cells, o_cells);
+        if won_zones() == WonZones::Closed
+            && info & (SUB_X_WON | SUB_O_WON) == SUB_X_WON | SUB_O_WON
+        {
+            mismatches.push(format!("subboard {}", ZONE_ARRAY_LOWER[zone as usize]));
+        }
+        let (x_marked, o_marked) = (
+            (share >> (36 + zone)) & 1 == 1,
+            (share >> (45 + zone)) & 1 == 1,
+        );
+        large_grid |= !match (x_marked, o_marked) {
+            (true, true) => false,
+            (true, false) => info & SUB_X_WON != 0,
+            (false, true) => info & SUB_O_WON != 0,
+            (false, false) => info & (SUB_X_WON | SUB_O_WON) == 0,
+        };
+    }
+    if large_grid {
+        mismatches.push("largegrid".to_string());
+    }
+    let (x_won, o_won) = (
+        line_presence((share >> 36) & 0b111111111),
+        line_presence((share >> 45) & 0b111111111),
+    );
+    if x_won && o_won {
+        mismatches.push("bothwon".to_string());
+    }
+    // Once the game is over, the zone no longer matters.
+    // A zone that is completely filled without a winner is closed like a won one, so no player can be sent there.
+    let zone = (share >> 54) & 0b1111;
+    if zone > ZONE_ANY {
+        mismatches.push("zone".to_string());
+    } else if zone != ZONE_ANY && !x_won && !o_won {
+        let (x_cells, o_cells) = zone_cells(board, zone);
+        if ((share >> (36 + zone)) | (share >> (45 + zone))) & 1 == 1 && won_zone_sends_anywhere()
+            || grid_info(x_cells, o_cells) & SUB_FULL != 0
+        {
+            mismatches.push("zone".to_string());
+        }
+    }
+    if zone <= ZONE_ANY
+        && generate_moves(board).collect::<Vec<_>>() != expected_moves(board, x_won || o_won)
+    {
+        mismatches.push("movegen".to_string());
+    }
+    mismatches
+}
+
+/**
+ * The legal moves of a board, found from its cells alone rather than from the bits `generate_moves` relies on:
+ * the empty cells of the zone to be played in, or of every zone that is neither full nor closed by being won.
+ * The first-move rule is left to `check_move`, since it only applies to the empty board.
+ */
+fn expected_moves(board: Board, game_over: bool) -> Vec<Move> {
+    if game_over {
+        return Vec::new();
+    }
+    let zone = (board.2 >> 54) & 0b1111;
+    let playable = |zone: u64| {
+        let (x_cells, o_cells) = zone_cells(board, zone);
+        let info = grid_info(x_cells, o_cells);
+        info & SUB_FULL == 0
+            && (won_zones() != WonZones::Closed || info & (SUB_X_WON | SUB_O_WON) == 0)
+    };
+    (0..81)
+        .filter(|&mv| zone == ZONE_ANY && playable(mv / 9) || mv / 9 == zone)
+        .filter(|&mv| {
+            let (x_cells, o_cells) = zone_cells(board, mv / 9);
+            ((x_cells | o_cells) >> (mv % 9)) & 1 == 0
+        })
+        .filter(|&mv| board != (0, 0, ZONE_ANY << 54) || check_move(board, mv).is_ok())
+        .collect()
+}
+
+/**
+ * Checks the game history for state that is kept redundantly and has fallen out of agreement,
+ * writing `verify mismatch <check>` for each disagreement found, then `verify ok` or `verify failed <mismatches>`.
+ * The current board is checked on its own: its cells against its large grid and its zone against closed zones.
+ * Each move of the history is replayed, and the board and hash reached are compared with those recorded
+ * and recomputed from scratch, and the cells gained by each side are compared with the moves each side made.
+ */
+pub fn run_verify(history: &[(Board, Move, bool, Option<SearchRecord>)]) {
+    let (board, _, side, _) = *history.last().unwrap();
+    let mut mismatches = check_board(board);
+
+    // Replaying the history relies on every earlier board having a zone that can be hashed.
+    let hashable = history
+        .iter()
+        .all(|&(board, _, _, _)| (board.2 >> 54) & 0b1111 <= ZONE_ANY);
+    let mut moves = (0, 0);
+    for (ply, pair) in history.windows(2).enumerate() {
+        let ((before, _, mover, _), (after, mv, next, _)) = (pair[0], pair[1]);
+        if next == mover {
+            mismatches.push(format!("side ply {}", ply));
+        }
+        if mv == NULL_MOVE {
+            if after != before {
+                mismatches.push(format!("replay ply {}", ply));
+            }
+            continue;
+        }
+        if mover {
+            moves.1 += 1;
+        } else {
+            moves.0 += 1;
+        }
+        if play_move(before, mv, mover) != after {
+            mismatches.push(format!("replay ply {}", ply));
+        }
+        if hashable
+            && zobrist::child_hash(zobrist::hash(before, mover), before, mv, mover, after)
+                != zobrist::hash(after, next)
+        {
+            mismatches.push(format!("hash ply {}", ply));
+        }
+    }
+
+    // Every move and null move passes the turn, so the side to move follows from how many there were.
+    let (start_x, start_o) = cell_counts(history[0].0);
+    let (x, o) = cell_counts(board);
+    let null_moves = history
+        .iter()
+        .skip(1)
+        .filter(|&&(_, mv, _, _)| mv == NULL_MOVE)
+        .count() as i32;
+    let turns = x - start_x + o - start_o + null_moves;
+    if (x - start_x, o - start_o) != moves || (turns % 2 == 1) != (side != history[0].2) {
+        mismatches.push("parity".to_string());
+    }
+
+    for mismatch in &mismatches {
+        println!("verify mismatch {}", mismatch);
+    }
+    if mismatches.is_empty() {
+        println!("verify ok");
+    } else {
+        println!("verify failed {}", mismatches.len());
+    }
+}
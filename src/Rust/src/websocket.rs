@@ -0,0 +1,384 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::metrics::Metrics;
+use ut3b2l::json::Json;
+
+// The value appended to a client's key to form the accepting key of the handshake, fixed by RFC 6455.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// The largest message accepted from a client, well beyond any command.
+const MAX_MESSAGE: usize = 1 << 20;
+
+// The frame types used by the server. Continuation frames have the opcode 0.
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+// The protocols a session can speak, as given to `--protocol`.
+pub const PROTOCOLS: [&str; 3] = ["native", "ugi", "json"];
+
+// Computes the SHA-1 digest of the given bytes, which the handshake requires.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes(block[4 * i..4 * i + 4].try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (i, state) in h.iter().enumerate() {
+        digest[4 * i..4 * i + 4].copy_from_slice(&state.to_be_bytes());
+    }
+    digest
+}
+
+// Encodes bytes in standard base64 with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0b111111) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Reads the HTTP request of a new connection, and returns its path,
+// along with the client's key if it asks for a WebSocket.
+// The protocol asked for in the query of a handshake's path, as in `/?protocol=json`.
+fn requested_protocol(path: &str) -> Option<&str> {
+    let (_, query) = path.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("protocol="))
+}
+
+// The command of the engine's protocol in a line sent by the client, which the JSON protocol wraps in an object.
+fn client_command(line: &str, protocol: &str) -> Option<String> {
+    match protocol {
+        "json" => Json::parse(line)?
+            .get("command")?
+            .as_str()
+            .map(str::to_string),
+        _ => Some(line.to_string()),
+    }
+}
+
+fn read_handshake(reader: &mut impl BufRead) -> Option<(String, Option<String>)> {
+    let mut key = None;
+    let mut upgrade = false;
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let path = line
+        .strip_prefix("GET ")?
+        .split_whitespace()
+        .next()?
+        .to_string();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match &name.trim().to_lowercase() as &str {
+                "sec-websocket-key" => key = Some(value.trim().to_string()),
+                "upgrade" => upgrade = value.trim().eq_ignore_ascii_case("websocket"),
+                _ => {}
+            }
+        }
+    }
+    Some((path, key.filter(|_| upgrade)))
+}
+
+// Writes a single unfragmented frame. Frames sent by a server are never masked.
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length if length < 126 => frame.push(length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+// Reads a single frame, returning whether it is the last of its message, its opcode and its unmasked payload.
+fn read_frame(stream: &mut impl Read) -> Option<(bool, u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header).ok()?;
+    let length = match header[1] & 0x7F {
+        126 => {
+            let mut length = [0; 2];
+            stream.read_exact(&mut length).ok()?;
+            u16::from_be_bytes(length) as usize
+        }
+        127 => {
+            let mut length = [0; 8];
+            stream.read_exact(&mut length).ok()?;
+            u64::from_be_bytes(length) as usize
+        }
+        length => length as usize,
+    };
+    if length > MAX_MESSAGE {
+        return None;
+    }
+    // Clients must mask every frame they send.
+    if header[1] & 0x80 == 0 {
+        return None;
+    }
+    let mut mask = [0; 4];
+    stream.read_exact(&mut mask).ok()?;
+    let mut payload = vec![0; length];
+    stream.read_exact(&mut payload).ok()?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Some((header[0] & 0x80 != 0, header[0] & 0x0F, payload))
+}
+
+/**
+ * Passes the messages of a client to the standard input of its engine process until either side closes.
+ * A message may hold several commands on separate lines, each of which is passed on in turn.
+ */
+fn forward_messages(
+    mut stream: impl Read,
+    sender: Arc<Mutex<TcpStream>>,
+    mut input: ChildStdin,
+    metrics: &Metrics,
+    protocol: &str,
+) {
+    let mut message = Vec::new();
+    while let Some((last, opcode, payload)) = read_frame(&mut stream) {
+        match opcode {
+            OPCODE_TEXT | OPCODE_CONTINUATION => {
+                message.extend_from_slice(&payload);
+                if message.len() > MAX_MESSAGE {
+                    break;
+                }
+                if !last {
+                    continue;
+                }
+                let text = String::from_utf8_lossy(&message).into_owned();
+                message.clear();
+                for line in text.lines() {
+                    let command = client_command(line, protocol).unwrap_or_default();
+                    if command.split_whitespace().next() == Some("go") {
+                        metrics.search_started();
+                    }
+                    if writeln!(input, "{}", line).is_err() {
+                        return;
+                    }
+                }
+            }
+            OPCODE_PING => {
+                write_frame(&mut *sender.lock().unwrap(), OPCODE_PONG, &payload).ok();
+            }
+            OPCODE_CLOSE => break,
+            _ => {}
+        }
+    }
+    // Closing the engine's input ends its session as if standard input had closed,
+    // after which the closing frame is sent once the engine process has exited.
+}
+
+/**
+ * Reads the depth and time of a finished search from a line written by an engine process,
+ * in either format of `info` lines, such as `info depth 4 pv nw/nw eval +16 time 3`,
+ * or as the object the JSON protocol writes for them. UGI reports no time, so its searches are not timed.
+ */
+fn search_result(line: &str, protocol: &str) -> Option<(usize, u64)> {
+    if protocol == "json" {
+        let json = Json::parse(line)?;
+        let value = |key: &str| json.get(key)?.as_i64();
+        return Some((value("depth")? as usize, value("time")? as u64));
+    }
+    let words: Vec<_> = line
+        .split_whitespace()
+        .flat_map(|word| word.split('='))
+        .collect();
+    if words.first() != Some(&"info") {
+        return None;
+    }
+    let value = |key: &str| {
+        let index = words.iter().position(|&word| word == key)?;
+        words.get(index + 1)?.parse().ok()
+    };
+    Some((value("depth")? as usize, value("time")?))
+}
+
+// Serves a single connection, from the handshake until either the client or its engine process closes it,
+// in the protocol asked for by the path of the handshake, or the server's own if it asks for none.
+// A plain request for `/metrics` is answered with the metrics of the server instead.
+fn serve_connection(
+    stream: TcpStream,
+    metrics: Arc<Metrics>,
+    default_protocol: &str,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (key, protocol) = match read_handshake(&mut reader) {
+        Some((path, Some(key))) => {
+            let protocol = requested_protocol(&path).unwrap_or(default_protocol);
+            if !PROTOCOLS.contains(&protocol) {
+                let mut stream = stream;
+                stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+                return Ok(());
+            }
+            (key, protocol.to_string())
+        }
+        Some((path, None)) if path == "/metrics" => {
+            // The engine processes keep their nodes and tables to themselves, so only counts are given.
+            let body = metrics.render(false, None);
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )?;
+            return Ok(());
+        }
+        _ => {
+            let mut stream = stream;
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+            return Ok(());
+        }
+    };
+    let accept = base64(&sha1(format!("{}{}", key, HANDSHAKE_GUID).as_bytes()));
+    let sender = Arc::new(Mutex::new(stream));
+    write!(
+        sender.lock().unwrap(),
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )?;
+
+    // Each session is a separate engine process, so that sessions share no state at all.
+    let mut command = Command::new(std::env::current_exe()?);
+    if protocol != "native" {
+        command.args(["--protocol", &protocol]);
+    }
+    let mut child: Child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let input = child.stdin.take().unwrap();
+    let output = child.stdout.take().unwrap();
+
+    metrics.session_opened();
+    let forward_sender = sender.clone();
+    let forward_metrics = metrics.clone();
+    let forward_protocol = protocol.clone();
+    thread::spawn(move || {
+        forward_messages(
+            reader,
+            forward_sender,
+            input,
+            &forward_metrics,
+            &forward_protocol,
+        )
+    });
+
+    // Each line the engine writes is sent as its own text message.
+    for line in BufReader::new(output).lines() {
+        let Ok(line) = line else { break };
+        if let Some((depth, milliseconds)) = search_result(&line, &protocol) {
+            metrics.search_completed(depth, None, milliseconds);
+        }
+        if write_frame(&mut *sender.lock().unwrap(), OPCODE_TEXT, line.as_bytes()).is_err() {
+            break;
+        }
+    }
+    metrics.session_closed();
+    child.kill().ok();
+    child.wait()?;
+    let mut stream = sender.lock().unwrap();
+    write_frame(&mut *stream, OPCODE_CLOSE, &[]).ok();
+    stream.shutdown(Shutdown::Both).ok();
+    Ok(())
+}
+
+/**
+ * Accepts WebSocket connections on the given address, each of which is an independent game session
+ * speaking the given protocol, one of `PROTOCOLS`, unless its handshake asks for another with `?protocol=<name>`:
+ * every text message sent by the client is a command, and every line of the response is sent back as a text message.
+ * Each session runs in its own engine process, which ends when the connection closes or `q` is sent.
+ * Metrics of the sessions and their searches are served at `/metrics` to plain HTTP requests.
+ */
+pub fn serve(address: &str, protocol: &str) {
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("cannot listen on {}: {}", address, error);
+            return;
+        }
+    };
+    eprintln!("listening on {}", address);
+    let metrics = Arc::new(Metrics::default());
+    for stream in listener.incoming().flatten() {
+        let metrics = metrics.clone();
+        let protocol = protocol.to_string();
+        thread::spawn(move || serve_connection(stream, metrics, &protocol));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::engine::*;
+use crate::symmetry::{transform_board, SYMMETRIES};
+
+// The bits of the large grid, once shifted down from bit 36: nine for Player X followed by nine for Player O.
+const LARGE_GRID_MASK: u64 = (1 << 18) - 1;
+
+// A fixed seed, so that the keys of a position are the same in every run and every build.
+const SEED: u64 = 0x5554_3342_324c_0001;
+
+// The SplitMix64 generator, used here only to fill the key tables at compile time.
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+/**
+ * The keys combined to form the hash of a position:
+ * one for each cell occupied by each player, one for each value of the zone, and one for the side to move.
+ * Each zone won by each player is also keyed, since when the rules leave won zones open,
+ * a zone in which both players have a line belongs to whoever formed theirs first, which the cells do not show.
+ */
+struct Keys {
+    cells: [[u64; 81]; 2],
+    zones: [u64; 10],
+    side: u64,
+    large: [u64; 18],
+}
+
+const KEYS: Keys = {
+    let mut keys = Keys {
+        cells: [[0; 81]; 2],
+        zones: [0; 10],
+        side: 0,
+        large: [0; 18],
+    };
+    let mut state = SEED;
+    let mut i = 0;
+    while i < 2 * 81 {
+        let (next_state, key) = splitmix64(state);
+        state = next_state;
+        keys.cells[i / 81][i % 81] = key;
+        i += 1;
+    }
+    let mut zone = 0;
+    while zone < 10 {
+        let (next_state, key) = splitmix64(state);
+        state = next_state;
+        keys.zones[zone] = key;
+        zone += 1;
+    }
+    // The side key is taken before the large grid keys, so that it is the same as before they were added.
+    let (next_state, key) = splitmix64(state);
+    state = next_state;
+    keys.side = key;
+    let mut bit = 0;
+    while bit < 18 {
+        let (next_state, key) = splitmix64(state);
+        state = next_state;
+        keys.large[bit] = key;
+        bit += 1;
+    }
+    keys
+};
+
+// Returns the combined keys of the zones won by each player, from bits 36 to 53 of the board.
+#[inline]
+fn large_grid_key(large: u64) -> u64 {
+    let mut key = 0;
+    let mut bits = large;
+    while bits != 0 {
+        key ^= KEYS.large[bits.trailing_zeros() as usize];
+        bits &= bits - 1;
+    }
+    key
+}
+
+/**
+ * Returns the Zobrist hash of a position and the side to move,
+ * which is equal for equal positions and almost always differs between different ones.
+ */
+pub fn hash(board: Board, side: bool) -> u64 {
+    let zone = (board.2 >> 54) & 0b1111;
+    let mut key = KEYS.zones[zone as usize]
+        ^ if side { KEYS.side } else { 0 }
+        ^ large_grid_key((board.2 >> 36) & LARGE_GRID_MASK);
+    for z in 0..9 {
+        let (x_cells, o_cells) = zone_cells(board, z);
+        for square in 0..9 {
+            if ((x_cells >> square) & 1) == 1 {
+                key ^= KEYS.cells[0][(9 * z + square) as usize];
+            } else if ((o_cells >> square) & 1) == 1 {
+                key ^= KEYS.cells[1][(9 * z + square) as usize];
+            }
+        }
+    }
+    key
+}
+
+/**
+ * Returns the hash of the position reached by playing `mv` for `side` from `board`,
+ * given the hash of `board` and the board reached, without hashing every cell again.
+ */
+#[inline]
+pub fn child_hash(key: u64, board: Board, mv: Move, side: bool, child: Board) -> u64 {
+    let zone = (board.2 >> 54) & 0b1111;
+    let child_zone = (child.2 >> 54) & 0b1111;
+    key ^ KEYS.cells[side as usize][mv as usize]
+        ^ KEYS.zones[zone as usize]
+        ^ KEYS.zones[child_zone as usize]
+        ^ KEYS.side
+        ^ large_grid_key(((board.2 ^ child.2) >> 36) & LARGE_GRID_MASK)
+}
+
+// Returns the symmetry whose transformed board has the smallest hash, together with that hash.
+// This is the same for all positions that are symmetrical to each other.
+pub fn canonical(board: Board, side: bool) -> (usize, u64) {
+    (0..SYMMETRIES)
+        .map(|symmetry| (symmetry, hash(transform_board(board, symmetry), side)))
+        .min_by_key(|&(_, key)| key)
+        .unwrap()
+}
+
+// Returns the hash of the canonical form of a position.
+pub fn canonical_hash(board: Board, side: bool) -> u64 {
+    canonical(board, side).1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::TestRules;
+
+    #[test]
+    fn incremental_hashes_match_full_ones() {
+        let _rules = TestRules::standard();
+        let mut board = (0, 0, ZONE_ANY << 54);
+        let mut side = false;
+        let mut key = hash(board, side);
+        let mut seen = vec![key];
+        for ply in 0..40 {
+            let moves: Vec<_> = generate_moves(board).collect();
+            let Some(&mv) = moves.get(ply * 5 % moves.len().max(1)) else {
+                break;
+            };
+            assert_ne!(hash(board, !side), key);
+            let child = play_move(board, mv, side);
+            key = child_hash(key, board, mv, side, child);
+            board = child;
+            side = !side;
+            assert_eq!(key, hash(board, side));
+            assert!(!seen.contains(&key));
+            seen.push(key);
+        }
+    }
+
+    #[test]
+    fn symmetrical_positions_share_a_canonical_form() {
+        let _rules = TestRules::standard();
+        let mut board = (0, 0, ZONE_ANY << 54);
+        let mut side = false;
+        for mv in [10, 14, 46, 12, 28] {
+            assert!(generate_moves(board).any(|legal| legal == mv));
+            board = play_move(board, mv, side);
+            side = !side;
+            let (symmetry, key) = canonical(board, side);
+            assert_eq!(hash(transform_board(board, symmetry), side), key);
+            for other in 0..SYMMETRIES {
+                let transformed = transform_board(board, other);
+                assert_eq!(canonical_hash(transformed, side), key);
+                assert!(hash(transformed, side) >= key);
+            }
+            assert_ne!(canonical_hash(board, !side), key);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use ut3b2l::engine::*;
+use ut3b2l::random::Random;
+use ut3b2l::rules::{self, FirstMove, WonZones};
+
+// The number of random games played under each combination of rules, and the seed they are played from.
+const GAMES: usize = 50;
+const SEED: u64 = 0x5554_3342_324c_0002;
+
+// The moves produced by a `MovePicker`, in the order it produces them.
+fn picked_moves(board: Board, side: bool, hash_move: Move, stack: &mut SearchStack) -> Vec<Move> {
+    let mut picker = MovePicker::new(board, side, 0, hash_move);
+    let mut moves = Vec::new();
+    while let Some(mv) = picker.next(stack) {
+        moves.push(mv);
+    }
+    moves
+}
+
+// Checks that the picker produces every legal move exactly once, with or without a hash move,
+// including a hash move that is not legal in the position.
+fn check_position(board: Board, side: bool, random: &mut Random, stack: &mut SearchStack) {
+    let mut legal: Vec<_> = generate_moves(board).collect();
+    legal.sort();
+    let hash_moves = [
+        NULL_MOVE,
+        random.below(81),
+        legal
+            .get(random.below(legal.len().max(1) as u64) as usize)
+            .copied()
+            .unwrap_or(NULL_MOVE),
+    ];
+    for hash_move in hash_moves {
+        let mut picked = picked_moves(board, side, hash_move, stack);
+        picked.sort();
+        assert_eq!(
+            picked, legal,
+            "position {:?} side {} hash move {}",
+            board, side, hash_move
+        );
+    }
+}
+
+/**
+ * Plays random games under every combination of the `WonZones` and `FirstMove` rules,
+ * comparing the moves of the picker with those of `generate_moves` in every position reached.
+ * The rules are global, so every combination is checked in turn within a single test.
+ */
+#[test]
+fn picker_matches_move_generation_under_every_rule() {
+    let mut random = Random::new(SEED);
+    let mut stack = SearchStack::new();
+    let first_moves = [
+        FirstMove::Any,
+        FirstMove::NoCenter,
+        FirstMove::Zone(0),
+        FirstMove::Zone(4),
+    ];
+    for won_zones in [WonZones::Closed, WonZones::Open, WonZones::Free] {
+        for first_move in first_moves {
+            rules::set_won_zones(won_zones);
+            rules::set_first_move(first_move);
+            for _ in 0..GAMES {
+                let (mut board, mut side) = ((0, 0, ZONE_ANY << 54), false);
+                loop {
+                    check_position(board, side, &mut random, &mut stack);
+                    let moves: Vec<_> = generate_moves(board).collect();
+                    if moves.is_empty() {
+                        break;
+                    }
+                    board = play_move(
+                        board,
+                        moves[random.below(moves.len() as u64) as usize],
+                        side,
+                    );
+                    side = !side;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use ut3b2l::engine::*;
+use ut3b2l::grid::{self, GridBoard};
+
+// The number of move sequences of each length from the empty board, as given for `perft` in `interface.md`.
+const EMPTY_BOARD_COUNTS: [u64; 5] = [81, 720, 6336, 55080, 473256];
+
+// Counts the move sequences of the given length, where sequences that end the game stop early.
+fn perft(board: Board, side: bool, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    generate_moves(board)
+        .map(|mv| perft(play_move(board, mv, side), !side, depth - 1))
+        .sum()
+}
+
+#[test]
+fn empty_board_counts() {
+    for (depth, &count) in EMPTY_BOARD_COUNTS.iter().enumerate() {
+        assert_eq!(
+            perft((0, 0, ZONE_ANY << 54), false, depth + 1),
+            count,
+            "depth {}",
+            depth + 1
+        );
+    }
+}
+
+// The board of any size, at the size of the usual game, must count the same sequences as the bitboards.
+#[test]
+fn grid_board_of_size_three_counts() {
+    let board = GridBoard::new(3);
+    for (depth, &count) in EMPTY_BOARD_COUNTS.iter().take(4).enumerate() {
+        assert_eq!(grid::perft(&board, depth + 1), count, "depth {}", depth + 1);
+    }
+}
@@ -0,0 +1,70 @@
+// A JavaScript API over the WebAssembly build of the engine, for browser interfaces.
+// Build the module with `cargo build --release --lib --target wasm32-unknown-unknown`,
+// which produces `target/wasm32-unknown-unknown/release/ut3b2l.wasm`, and load it with `load`.
+// The exports it wraps are defined in `src/exports.rs`.
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+// Fetches and instantiates the module at the given URL, returning the engine's API.
+export async function load(url) {
+    const imports = {
+        // The engine has no clock of its own when compiled to WebAssembly.
+        env: { now_ms: () => performance.now() },
+    };
+    const response = await fetch(url);
+    const { instance } = await WebAssembly.instantiateStreaming(response, imports);
+    return wrap(instance.exports);
+}
+
+// Builds the API from the exports of an instantiated module.
+export function wrap(exports) {
+    // Calls an export taking a string argument, which is copied into the module's memory.
+    function withString(f, string) {
+        const bytes = encoder.encode(string);
+        const ptr = exports.alloc(bytes.length);
+        new Uint8Array(exports.memory.buffer, ptr, bytes.length).set(bytes);
+        const result = f(ptr, bytes.length);
+        exports.dealloc(ptr, bytes.length);
+        return result;
+    }
+
+    // Reads the string output by the last export that produced one.
+    function output() {
+        const bytes = new Uint8Array(exports.memory.buffer, exports.output_ptr(), exports.output_len());
+        return decoder.decode(bytes);
+    }
+
+    return {
+        // Starts a new game from a position string, or from the empty board if none is given.
+        // Returns whether the position was valid.
+        newGame(position = "") {
+            return withString(exports.new_game, position) !== 0;
+        },
+
+        // Plays a move such as "ne/c" or "r2c8" for the side to move, returning whether it was legal.
+        play(move) {
+            return withString(exports.play, move) !== 0;
+        },
+
+        // The legal moves of the position, such as ["nw/nw", "nw/n", ...].
+        legalMoves() {
+            exports.legal_moves();
+            const moves = output();
+            return moves === "" ? [] : moves.split(" ");
+        },
+
+        // The position string of the current position, as accepted by newGame.
+        board() {
+            exports.board();
+            return output();
+        },
+
+        // Searches the current position, given either { depth } or { movetime } in milliseconds.
+        // Returns { move, depth, eval, pv }, where move is null if the game is over.
+        search({ depth = 0, movetime = 1000 } = {}) {
+            exports.search(depth, movetime);
+            return JSON.parse(output());
+        },
+    };
+}